@@ -0,0 +1,16 @@
+//! Feeds arbitrary bytes to the server as the contents of a connection.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rpcbind_server::{handle_client, registry::InMemoryRegistry};
+
+fuzz_target!(|input: &[u8]| {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let registry = InMemoryRegistry::new();
+    let stream = tokio::io::join(input, tokio::io::sink());
+    // Errors only close the connection, what matters is that nothing panics
+    let _ = runtime.block_on(handle_client(stream, &registry));
+});
@@ -0,0 +1,26 @@
+//! Decodes arbitrary arguments to every procedure, checking that whatever decodes encodes
+//! back to the same request.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use onc_rpc::{CallBody, auth::AuthFlavor};
+use rpcbind_rs::request::{PROGRAM, RpcRequest};
+
+fuzz_target!(|input: (u8, u8, &[u8])| {
+    let (version, procedure, payload) = input;
+    // Covers the unsupported versions and procedures around the real ones
+    let body = CallBody::new(
+        PROGRAM,
+        u32::from(version % 6),
+        u32::from(procedure % 16),
+        AuthFlavor::<&[u8]>::AuthNone(None),
+        AuthFlavor::AuthNone(None),
+        payload,
+    );
+    let Ok(request) = RpcRequest::from_body(&body) else {
+        return;
+    };
+    let encoded = request.to_call_body().expect("a decoded request encodes");
+    assert_eq!(RpcRequest::from_body(&encoded).unwrap(), request);
+});
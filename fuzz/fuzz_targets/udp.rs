@@ -0,0 +1,22 @@
+//! Feeds arbitrary datagrams to the UDP server, as calls from a caller when the first byte is
+//! even, and as replies to forwarded calls when it is odd.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rpcbind_server::{Datagram, forward, read_datagram, registry::InMemoryRegistry};
+
+fuzz_target!(|input: &[u8]| {
+    let Some((&kind, datagram)) = input.split_first() else {
+        return;
+    };
+    if kind % 2 == 1 {
+        let _ = forward::read_reply(datagram);
+        return;
+    }
+    let registry = InMemoryRegistry::new();
+    // Errors only drop the datagram, what matters is that nothing panics
+    if let Ok(Datagram::Forward(call)) = read_datagram(&registry, datagram) {
+        call.datagram(1).expect("a call to forward serialises");
+    }
+});
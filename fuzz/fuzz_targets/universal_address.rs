@@ -0,0 +1,20 @@
+//! Parses arbitrary universal addresses, alone and for each default netid.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rpcbind_rs::{netconfig::NetConfig, netid::Netid, universal_address::UniversalAddress};
+
+fuzz_target!(|input: &str| {
+    if let Ok(address) = input.parse::<UniversalAddress>() {
+        assert_eq!(
+            address.to_string().parse::<UniversalAddress>().unwrap(),
+            address
+        );
+        let _ = address.socket_addr();
+    }
+    let config = NetConfig::defaults();
+    for netid in ["tcp", "udp6", "local", "bogus"] {
+        let _ = UniversalAddress::for_netid(&config, &Netid::from(netid), input);
+    }
+});
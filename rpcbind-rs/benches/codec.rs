@@ -0,0 +1,81 @@
+//! Compares the facet codec with the hand written one on the arguments and results of the
+//! calls every client makes, GETPORT and GETADDR.
+
+use std::hint::black_box;
+
+use bytes::Bytes;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use rpcbind_rs::xdr_types::{
+    codec::{XdrBorrow, XdrCodec, XdrWriter},
+    port_mapper::Mapping,
+    rpcbind::{RPCB, RpcbRef},
+};
+
+fn getport(c: &mut Criterion) {
+    let mapping = Mapping {
+        prog: 100003,
+        vers: 3,
+        prot: 6,
+        port: 0,
+    };
+    // The payload as the server receives it, a slice of the message
+    let payload = Bytes::from(mapping.to_xdr());
+
+    let mut group = c.benchmark_group("getport");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("facet", |b| {
+        b.iter(|| {
+            let mapping: Mapping = facet_xdr::deserialize(black_box(&payload)).unwrap();
+            facet_xdr::to_vec(&mapping.prog).unwrap()
+        })
+    });
+    group.bench_function("hand_written", |b| {
+        b.iter(|| {
+            let mapping = Mapping::from_xdr(black_box(&payload)).unwrap();
+            mapping.prog.to_xdr()
+        })
+    });
+    group.finish();
+}
+
+fn getaddr(c: &mut Criterion) {
+    let rpcb = RPCB {
+        r_prog: 100003,
+        r_vers: 3,
+        r_netid: "tcp6".to_owned(),
+        r_addr: String::new(),
+        r_owner: "superuser".to_owned(),
+    };
+    let payload = Bytes::from(rpcb.to_xdr());
+    let universal_address = "fe80::1ff:fe23:4567:890a.8.1";
+
+    let mut group = c.benchmark_group("getaddr");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("facet", |b| {
+        b.iter(|| {
+            let rpcb: RPCB = facet_xdr::deserialize(black_box(&payload)).unwrap();
+            black_box(&rpcb.r_netid);
+            facet_xdr::to_vec(&universal_address.to_owned()).unwrap()
+        })
+    });
+    group.bench_function("hand_written", |b| {
+        b.iter(|| {
+            let rpcb = RPCB::from_xdr(black_box(&payload)).unwrap();
+            black_box(&rpcb.r_netid);
+            universal_address.to_owned().to_xdr()
+        })
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            let rpcb = RpcbRef::from_xdr_borrowed(black_box(&payload)).unwrap();
+            black_box(rpcb.r_netid);
+            let mut writer = XdrWriter::new();
+            writer.string(universal_address);
+            writer.into_bytes()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, getport, getaddr);
+criterion_main!(benches);
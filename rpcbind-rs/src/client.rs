@@ -0,0 +1,499 @@
+use std::{
+    io,
+    net::{Ipv6Addr, SocketAddr},
+    sync::{
+        LazyLock,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use facet_xdr::{XdrDeserError, XdrSerError};
+use onc_rpc::{AcceptedStatus, CallBody, MessageType, RejectedReply, RpcMessage, auth::AuthFlavor};
+use thiserror::Error;
+
+use crate::{
+    reply::accepted_payload,
+    request::{PortMapperRequest, RpcBindRequest, RpcRequest},
+    universal_address::UniversalAddress,
+    xdr_types::{
+        codec::XdrCodec,
+        port_mapper::{CallArgs, CallResult},
+        rpcbind::{RmtCallArgs, RmtCallRes},
+    },
+};
+
+#[cfg(feature = "tokio")]
+mod asynchronous;
+pub mod blocking;
+
+#[cfg(feature = "tokio")]
+pub use asynchronous::{PortMapperClient, RpcBindClient, broadcast, ping};
+
+pub use crate::request::PROGRAM;
+
+/// How long a call may take before giving up, matching libtirpc's default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(25);
+/// How long to wait for a UDP reply before sending the call again.
+pub const DEFAULT_RETRANSMIT_INTERVAL: Duration = Duration::from_secs(5);
+/// The link-local group rpcbind joins on IPv6, which has no broadcast address.
+pub const RPCB_MULTICAST_ADDR: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x202);
+
+const MSG_HEADER_LEN: usize = 4;
+const LAST_FRAGMENT_BIT: u32 = 1 << 31;
+// The largest payload a UDP datagram can carry
+const MAX_DATAGRAM_LEN: usize = 65535;
+/// The longest reply record read over TCP, which leaves room for dumps of a few hundred thousand
+/// registrations while refusing to buffer whatever length a fragment header claims.
+pub const MAX_REPLY_LEN: usize = 64 << 20;
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("i/o error: {0}")]
+    Io(#[from] io::Error),
+    #[error("malformed rpc message: {0}")]
+    Rpc(#[from] onc_rpc::Error),
+    #[error("failed to encode call: {0}")]
+    Encode(#[from] XdrSerError),
+    #[error("failed to decode reply: {0}")]
+    Decode(#[from] XdrDeserError),
+    #[error("server rejected the call: {0:?}")]
+    Rejected(RejectedReply),
+    #[error("server could not complete the call: {0:?}")]
+    Failed(AcceptedStatus<[u8; 0]>),
+    #[error("received a call where a reply was expected")]
+    NotAReply,
+    #[error("timed out waiting for a reply")]
+    TimedOut,
+    #[error("reply of at least {len} bytes is over the limit of {MAX_REPLY_LEN}")]
+    ReplyTooLong { len: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// The rpcbind protocol versions, which share the same procedures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcBindVersion {
+    V3,
+    V4,
+}
+
+impl RpcBindVersion {
+    fn request(self, request: RpcBindRequest) -> RpcRequest {
+        match self {
+            RpcBindVersion::V3 => RpcRequest::V3(request),
+            RpcBindVersion::V4 => RpcRequest::V4(request),
+        }
+    }
+}
+
+/// The procedure a broadcast asks each rpcbind to forward the call with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BroadcastVersion {
+    /// `PMAPPROC_CALLIT`, understood by every portmapper.
+    V2,
+    /// `RPCBPROC_BCAST`
+    V3,
+    /// `RPCBPROC_BCAST`
+    V4,
+}
+
+impl BroadcastVersion {
+    fn call(self, args: RmtCallArgs) -> Call<Forwarded> {
+        match self {
+            BroadcastVersion::V2 => {
+                let args = CallArgs {
+                    prog: args.prog,
+                    vers: args.vers,
+                    proc: args.proc,
+                    args: args.args,
+                };
+                Call::port_mapper(PortMapperRequest::CallIt(args), decode_call_result)
+            }
+            BroadcastVersion::V3 => Call::rpcbind(
+                RpcBindVersion::V3,
+                RpcBindRequest::Broadcast(args),
+                decode_rmtcall_res,
+            ),
+            BroadcastVersion::V4 => Call::rpcbind(
+                RpcBindVersion::V4,
+                RpcBindRequest::Broadcast(args),
+                decode_rmtcall_res,
+            ),
+        }
+    }
+}
+
+/// An answer to a broadcast call, from one of the servers running the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BroadcastReply {
+    /// The rpcbind that forwarded the call.
+    pub responder: SocketAddr,
+    /// Where the called program is served, as a universal address.
+    pub universal_address: String,
+    /// The encoded results of the called procedure.
+    pub results: Vec<u8>,
+}
+
+/// The result of a forwarded call, which portmapper only describes with a port on the
+/// responder.
+enum Forwarded {
+    PortMapper(CallResult),
+    RpcBind(RmtCallRes),
+}
+
+impl Forwarded {
+    /// The reply as seen from `responder`, or `None` if the port it gave is out of range.
+    fn into_reply(self, responder: SocketAddr) -> Option<BroadcastReply> {
+        Some(match self {
+            Forwarded::PortMapper(CallResult { port, res }) => BroadcastReply {
+                responder,
+                universal_address: UniversalAddress::from(SocketAddr::new(
+                    responder.ip(),
+                    port.try_into().ok()?,
+                ))
+                .to_string(),
+                results: res,
+            },
+            Forwarded::RpcBind(RmtCallRes { addr, results }) => BroadcastReply {
+                responder,
+                universal_address: addr,
+                results,
+            },
+        })
+    }
+}
+
+/// Collects the replies to one broadcast, keeping a single reply per responder.
+struct Broadcast {
+    exchange: Exchange<Forwarded>,
+    replies: Vec<BroadcastReply>,
+}
+
+impl Broadcast {
+    fn new(version: BroadcastVersion, args: RmtCallArgs) -> ClientResult<Self> {
+        Ok(Self {
+            exchange: Exchange::new(version.call(args))?,
+            replies: Vec::new(),
+        })
+    }
+
+    /// Records a datagram received from `responder`.
+    ///
+    /// Anything but a successful reply to this broadcast is ignored, as other servers may
+    /// still answer.
+    fn receive(&mut self, datagram: &[u8], responder: SocketAddr) {
+        if self
+            .replies
+            .iter()
+            .any(|reply| reply.responder == responder)
+        {
+            return;
+        }
+        let Ok(Some(forwarded)) = self.exchange.receive(datagram_record(datagram)) else {
+            return;
+        };
+        if let Some(reply) = forwarded.into_reply(responder) {
+            self.replies.push(reply);
+        }
+    }
+}
+
+/// A call being made, encoded and decoded the same way by both clients, which only differ in
+/// how they send it and wait for the reply.
+struct Exchange<T> {
+    xid: u32,
+    /// The call as a single record, including the record marking header.
+    record: Vec<u8>,
+    decode: fn(&[u8]) -> ClientResult<T>,
+}
+
+impl<T> Exchange<T> {
+    fn new(call: Call<T>) -> ClientResult<Self> {
+        let xid = next_xid();
+        Ok(Self {
+            xid,
+            record: call.encode(xid)?,
+            decode: call.decode,
+        })
+    }
+
+    /// The call as a datagram, which carries it without record marking.
+    fn datagram(&self) -> &[u8] {
+        &self.record[MSG_HEADER_LEN..]
+    }
+
+    /// Decodes the result from a reply record, or returns `None` if the record answers
+    /// another call.
+    fn receive(&self, record: Bytes) -> ClientResult<Option<T>> {
+        reply_payload(self.xid, record)?
+            .map(|payload| (self.decode)(&payload))
+            .transpose()
+    }
+}
+
+/// Whether a call failing with `error` may have left a stream part way through a record, so
+/// the next call has to start on a new connection.
+fn desynchronizes(error: &ClientError) -> bool {
+    matches!(
+        error,
+        ClientError::Io(_) | ClientError::TimedOut | ClientError::ReplyTooLong { .. }
+    )
+}
+
+/// A procedure call together with how to decode its result.
+pub(crate) struct Call<T> {
+    target: Target,
+    decode: fn(&[u8]) -> ClientResult<T>,
+}
+
+enum Target {
+    RpcBind(RpcRequest),
+    /// The NULL procedure of any program, used to check whether it is alive.
+    Null {
+        program: u32,
+        version: u32,
+    },
+}
+
+impl<T> Call<T> {
+    fn port_mapper(request: PortMapperRequest, decode: fn(&[u8]) -> ClientResult<T>) -> Self {
+        Self {
+            target: Target::RpcBind(RpcRequest::V2(request)),
+            decode,
+        }
+    }
+
+    fn rpcbind(
+        version: RpcBindVersion,
+        request: RpcBindRequest,
+        decode: fn(&[u8]) -> ClientResult<T>,
+    ) -> Self {
+        Self {
+            target: Target::RpcBind(version.request(request)),
+            decode,
+        }
+    }
+
+    fn null(program: u32, version: u32, decode: fn(&[u8]) -> ClientResult<T>) -> Self {
+        Self {
+            target: Target::Null { program, version },
+            decode,
+        }
+    }
+
+    /// Serialises the call as a single record, including the record marking header.
+    fn encode(&self, xid: u32) -> ClientResult<Vec<u8>> {
+        let body = match &self.target {
+            Target::RpcBind(request) => request.to_call_body()?,
+            Target::Null { program, version } => CallBody::new(
+                *program,
+                *version,
+                0,
+                AuthFlavor::AuthNone(None),
+                AuthFlavor::AuthNone(None),
+                Vec::new(),
+            ),
+        };
+        let message = RpcMessage::new(xid, MessageType::Call(body));
+        Ok(message.serialise()?)
+    }
+}
+
+fn decode_unit(_payload: &[u8]) -> ClientResult<()> {
+    Ok(())
+}
+
+/// Decodes a reply with its bounds-checked codec, so malformed replies are errors.
+fn decode_xdr<T: XdrCodec>(payload: &[u8]) -> ClientResult<T> {
+    Ok(T::from_xdr(payload)?)
+}
+
+fn decode_call_result(payload: &[u8]) -> ClientResult<Forwarded> {
+    Ok(Forwarded::PortMapper(decode_xdr(payload)?))
+}
+
+fn decode_rmtcall_res(payload: &[u8]) -> ClientResult<Forwarded> {
+    Ok(Forwarded::RpcBind(decode_xdr(payload)?))
+}
+
+/// The address to bind a UDP socket talking to `peer` on.
+fn unspecified_for(peer: SocketAddr) -> SocketAddr {
+    match peer {
+        SocketAddr::V4(_) => ([0u8; 4], 0).into(),
+        SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+    }
+}
+
+fn next_xid() -> u32 {
+    static XID: LazyLock<AtomicU32> = LazyLock::new(|| {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos();
+        AtomicU32::new(seed ^ std::process::id())
+    });
+    XID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Splits a record marking header into the fragment length and whether it is the last one.
+fn fragment_header(header: [u8; MSG_HEADER_LEN]) -> (usize, bool) {
+    let header = u32::from_be_bytes(header);
+    (
+        (header & !LAST_FRAGMENT_BIT) as usize,
+        header & LAST_FRAGMENT_BIT != 0,
+    )
+}
+
+/// Makes room at the end of `record` for a fragment of `len` bytes, returning where it starts.
+///
+/// Fails without allocating if the record would grow past [`MAX_REPLY_LEN`].
+fn grow_record(record: &mut BytesMut, len: usize) -> ClientResult<usize> {
+    let start = record.len();
+    let total = start - MSG_HEADER_LEN + len;
+    if total > MAX_REPLY_LEN {
+        return Err(ClientError::ReplyTooLong { len: total });
+    }
+    record.resize(start + len, 0);
+    Ok(start)
+}
+
+/// Creates an empty record with room for the header [`finish_record`] fills in.
+fn start_record() -> BytesMut {
+    let mut record = BytesMut::with_capacity(MAX_DATAGRAM_LEN);
+    record.put_u32(0);
+    record
+}
+
+/// Adds record marking to a datagram, which is a whole message in one fragment.
+fn datagram_record(datagram: &[u8]) -> Bytes {
+    let mut record = start_record();
+    record.extend_from_slice(datagram);
+    finish_record(record)
+}
+
+/// Writes a single last-fragment header, as onc-rpc only parses unfragmented records.
+fn finish_record(mut record: BytesMut) -> Bytes {
+    let len = (record.len() - MSG_HEADER_LEN) as u32;
+    record[..MSG_HEADER_LEN].copy_from_slice(&(len | LAST_FRAGMENT_BIT).to_be_bytes());
+    record.freeze()
+}
+
+/// Extracts the result payload from a reply record.
+///
+/// Returns `None` if the reply belongs to a different call than `xid`, such as a late
+/// answer to a retransmitted UDP call.
+fn reply_payload(xid: u32, record: Bytes) -> ClientResult<Option<Bytes>> {
+    let message = RpcMessage::try_from(record)?;
+    if message.xid() != xid {
+        return Ok(None);
+    }
+    let reply = message.reply_body().ok_or(ClientError::NotAReply)?;
+    Ok(Some(accepted_payload(reply)?.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use onc_rpc::{AcceptedReply, AcceptedStatus, MessageType, ReplyBody, RpcMessage};
+
+    use super::{Broadcast, BroadcastVersion};
+    use crate::{
+        request::{PortMapperRequest, RpcBindRequest, RpcRequest},
+        xdr_types::{
+            codec::XdrCodec,
+            port_mapper::{CallResult, Mapping, PMapList},
+            rpcbind::{RmtCallArgs, RmtCallRes},
+        },
+    };
+
+    pub(super) fn mapping(prog: u32, port: u32) -> Mapping {
+        Mapping {
+            prog,
+            vers: 1,
+            prot: 6,
+            port,
+        }
+    }
+
+    /// Answers a call record the way a tiny rpcbind server would.
+    pub(super) fn answer(record: &[u8]) -> Vec<u8> {
+        let message = RpcMessage::try_from(record).unwrap();
+        let payload = match RpcRequest::from_body(message.call_body().unwrap()).unwrap() {
+            RpcRequest::V2(PortMapperRequest::GetPort(mapping)) => {
+                facet_xdr::to_vec(&(mapping.prog - 100000)).unwrap()
+            }
+            RpcRequest::V2(PortMapperRequest::Dump) => {
+                PMapList::from(vec![mapping(100000, 111), mapping(100003, 2049)]).to_xdr()
+            }
+            RpcRequest::V4(RpcBindRequest::GetAddr(rpcb)) => {
+                facet_xdr::to_vec(&format!("127.0.0.1.{}.1", rpcb.r_prog - 100000)).unwrap()
+            }
+            // Forwarded calls echo their arguments back as the results
+            RpcRequest::V2(PortMapperRequest::CallIt(args)) => facet_xdr::to_vec(&CallResult {
+                port: 2049,
+                res: args.args,
+            })
+            .unwrap(),
+            RpcRequest::V3(RpcBindRequest::Broadcast(args))
+            | RpcRequest::V4(RpcBindRequest::Broadcast(args)) => facet_xdr::to_vec(&RmtCallRes {
+                addr: "127.0.0.1.8.1".to_owned(),
+                results: args.args,
+            })
+            .unwrap(),
+            request => panic!("unexpected request {request:?}"),
+        };
+        let reply = RpcMessage::new(
+            message.xid(),
+            MessageType::Reply(ReplyBody::Accepted(AcceptedReply::new(
+                onc_rpc::auth::AuthFlavor::<&[u8]>::AuthNone(None),
+                AcceptedStatus::Success(payload),
+            ))),
+        );
+        reply.serialise().unwrap()
+    }
+
+    #[test]
+    fn malformed_broadcast_replies_are_ignored() {
+        let responder = "127.0.0.1:111".parse().unwrap();
+        for version in [
+            BroadcastVersion::V2,
+            BroadcastVersion::V3,
+            BroadcastVersion::V4,
+        ] {
+            let mut broadcast = Broadcast::new(
+                version,
+                RmtCallArgs {
+                    prog: 100003,
+                    vers: 3,
+                    proc: 0,
+                    args: vec![0, 0, 0, 7],
+                },
+            )
+            .unwrap();
+            // Declares 100 bytes of results, or of address, where only one follows
+            let truncated: &[u8] = &[0, 0, 0, 100, b'a', 0, 0, 0];
+            let reply = RpcMessage::new(
+                broadcast.exchange.xid,
+                MessageType::Reply(ReplyBody::Accepted(AcceptedReply::new(
+                    onc_rpc::auth::AuthFlavor::<&[u8]>::AuthNone(None),
+                    AcceptedStatus::Success(truncated),
+                ))),
+            );
+            broadcast.receive(&reply.serialise().unwrap()[4..], responder);
+            assert!(broadcast.replies.is_empty());
+
+            // A well formed reply from the same responder still counts
+            let call = broadcast.exchange.record.clone();
+            broadcast.receive(&answer(&call)[4..], responder);
+            assert_eq!(broadcast.replies.len(), 1);
+            assert_eq!(broadcast.replies[0].results, [0, 0, 0, 7]);
+        }
+    }
+}
@@ -0,0 +1,483 @@
+use std::{net::SocketAddr, time::Duration};
+
+use bytes::Bytes;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+    time::{Instant, timeout_at},
+};
+
+use super::{
+    Broadcast, BroadcastReply, BroadcastVersion, Call, ClientError, ClientResult,
+    DEFAULT_RETRANSMIT_INTERVAL, DEFAULT_TIMEOUT, Exchange, MAX_DATAGRAM_LEN, MSG_HEADER_LEN,
+    Protocol, RpcBindVersion, datagram_record, decode_unit, decode_xdr, desynchronizes,
+    finish_record, fragment_header, grow_record, start_record, unspecified_for,
+};
+use crate::{
+    request::{PortMapperRequest, RpcBindRequest},
+    xdr_types::{
+        port_mapper::{Mapping, PMapList},
+        rpcbind::{EntryList, RPCB, RPList, RmtCallArgs, StatByVers},
+    },
+};
+
+enum Transport {
+    /// `None` once a call fails part way through, until the next call reconnects.
+    Tcp(Option<TcpStream>),
+    Udp(UdpSocket),
+}
+
+struct Connection {
+    addr: SocketAddr,
+    transport: Transport,
+    timeout: Duration,
+    retransmit_interval: Duration,
+}
+
+impl Connection {
+    /// Connects to `addr`, giving up on TCP after `timeout`, which then also bounds each call.
+    async fn connect(
+        addr: SocketAddr,
+        protocol: Protocol,
+        timeout: Duration,
+    ) -> ClientResult<Self> {
+        let transport = match protocol {
+            Protocol::Tcp => {
+                Transport::Tcp(Some(connect_tcp(addr, Instant::now() + timeout).await?))
+            }
+            Protocol::Udp => {
+                let socket = UdpSocket::bind(unspecified_for(addr)).await?;
+                socket.connect(addr).await?;
+                Transport::Udp(socket)
+            }
+        };
+        Ok(Self {
+            addr,
+            transport,
+            timeout,
+            retransmit_interval: DEFAULT_RETRANSMIT_INTERVAL,
+        })
+    }
+
+    async fn call<T>(&mut self, call: Call<T>) -> ClientResult<T> {
+        let exchange = Exchange::new(call)?;
+        let deadline = Instant::now() + self.timeout;
+        match &mut self.transport {
+            Transport::Tcp(connected) => {
+                let mut stream = match connected.take() {
+                    Some(stream) => stream,
+                    None => connect_tcp(self.addr, deadline).await?,
+                };
+                let result = timeout_at(deadline, call_tcp(&mut stream, &exchange))
+                    .await
+                    .unwrap_or(Err(ClientError::TimedOut));
+                if !result.as_ref().is_err_and(desynchronizes) {
+                    *connected = Some(stream);
+                }
+                result
+            }
+            Transport::Udp(socket) => {
+                call_udp(socket, &exchange, deadline, self.retransmit_interval).await
+            }
+        }
+    }
+}
+
+async fn connect_tcp(addr: SocketAddr, deadline: Instant) -> ClientResult<TcpStream> {
+    timeout_at(deadline, TcpStream::connect(addr))
+        .await
+        .map_err(|_| ClientError::TimedOut)?
+        .map_err(ClientError::from)
+}
+
+async fn call_tcp<T>(stream: &mut TcpStream, exchange: &Exchange<T>) -> ClientResult<T> {
+    stream.write_all(&exchange.record).await?;
+    loop {
+        if let Some(result) = exchange.receive(read_record(stream).await?)? {
+            return Ok(result);
+        }
+    }
+}
+
+/// Reads fragments until the last one of a record arrives.
+async fn read_record(stream: &mut TcpStream) -> ClientResult<Bytes> {
+    let mut record = start_record();
+    loop {
+        let mut header = [0u8; MSG_HEADER_LEN];
+        stream.read_exact(&mut header).await?;
+        let (len, last) = fragment_header(header);
+
+        let start = grow_record(&mut record, len)?;
+        stream.read_exact(&mut record[start..]).await?;
+        if last {
+            return Ok(finish_record(record));
+        }
+    }
+}
+
+async fn call_udp<T>(
+    socket: &UdpSocket,
+    exchange: &Exchange<T>,
+    deadline: Instant,
+    retransmit_interval: Duration,
+) -> ClientResult<T> {
+    let mut buffer = vec![0u8; MAX_DATAGRAM_LEN];
+
+    loop {
+        socket.send(exchange.datagram()).await?;
+        let resend_at = deadline.min(Instant::now() + retransmit_interval);
+        while let Ok(received) = timeout_at(resend_at, socket.recv(&mut buffer)).await {
+            if let Some(result) = exchange.receive(datagram_record(&buffer[..received?]))? {
+                return Ok(result);
+            }
+        }
+        if Instant::now() >= deadline {
+            return Err(ClientError::TimedOut);
+        }
+    }
+}
+
+/// Asks rpcbind at every address in `targets` to forward a call, collecting the replies that
+/// arrive within `timeout`.
+///
+/// Targets are usually broadcast addresses like `255.255.255.255:111`, or
+/// [`RPCB_MULTICAST_ADDR`](super::RPCB_MULTICAST_ADDR) on IPv6, and must all be of the same
+/// address family. rpcbind stays silent when it cannot forward the call, so only servers
+/// running the program answer.
+pub async fn broadcast(
+    targets: &[SocketAddr],
+    version: BroadcastVersion,
+    args: RmtCallArgs,
+    timeout: Duration,
+) -> ClientResult<Vec<BroadcastReply>> {
+    let Some(&first) = targets.first() else {
+        return Ok(Vec::new());
+    };
+    let mut broadcast = Broadcast::new(version, args)?;
+    let socket = UdpSocket::bind(unspecified_for(first)).await?;
+    socket.set_broadcast(true)?;
+    let deadline = Instant::now() + timeout;
+    let mut buffer = vec![0u8; MAX_DATAGRAM_LEN];
+
+    loop {
+        for target in targets {
+            socket
+                .send_to(broadcast.exchange.datagram(), target)
+                .await?;
+        }
+        let resend_at = deadline.min(Instant::now() + DEFAULT_RETRANSMIT_INTERVAL);
+        while let Ok(received) = timeout_at(resend_at, socket.recv_from(&mut buffer)).await {
+            let (received, responder) = received?;
+            broadcast.receive(&buffer[..received], responder);
+        }
+        if Instant::now() >= deadline {
+            return Ok(broadcast.replies);
+        }
+    }
+}
+
+/// Calls the NULL procedure of any `program` at `addr`, succeeding if it answers.
+///
+/// A server that has the program but not `version` fails with
+/// [`AcceptedStatus::ProgramMismatch`](onc_rpc::AcceptedStatus::ProgramMismatch), which
+/// carries the versions it does support.
+pub async fn ping(
+    addr: SocketAddr,
+    protocol: Protocol,
+    program: u32,
+    version: u32,
+    timeout: Duration,
+) -> ClientResult<()> {
+    let mut connection = Connection::connect(addr, protocol, timeout).await?;
+    connection
+        .call(Call::null(program, version, decode_unit))
+        .await
+}
+
+/// An asynchronous client for version 2 of the protocol, known as portmapper.
+pub struct PortMapperClient {
+    connection: Connection,
+}
+
+impl PortMapperClient {
+    pub async fn connect(addr: SocketAddr, protocol: Protocol) -> ClientResult<Self> {
+        Ok(Self {
+            connection: Connection::connect(addr, protocol, DEFAULT_TIMEOUT).await?,
+        })
+    }
+
+    /// Sets how long a call may take, including every UDP retransmission.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.connection.timeout = timeout;
+    }
+
+    pub fn set_retransmit_interval(&mut self, interval: Duration) {
+        self.connection.retransmit_interval = interval;
+    }
+
+    pub async fn null(&mut self) -> ClientResult<()> {
+        let call = Call::port_mapper(PortMapperRequest::Null, decode_unit);
+        self.connection.call(call).await
+    }
+
+    pub async fn set(&mut self, mapping: Mapping) -> ClientResult<bool> {
+        let call = Call::port_mapper(PortMapperRequest::Set(mapping), decode_xdr);
+        self.connection.call(call).await
+    }
+
+    pub async fn unset(&mut self, mapping: Mapping) -> ClientResult<bool> {
+        let call = Call::port_mapper(PortMapperRequest::Unset(mapping), decode_xdr);
+        self.connection.call(call).await
+    }
+
+    /// Returns the port `mapping` is registered on, or 0 if it is not registered.
+    pub async fn get_port(&mut self, mapping: Mapping) -> ClientResult<u32> {
+        let call = Call::port_mapper(PortMapperRequest::GetPort(mapping), decode_xdr);
+        self.connection.call(call).await
+    }
+
+    pub async fn dump(&mut self) -> ClientResult<PMapList> {
+        let call = Call::port_mapper(PortMapperRequest::Dump, decode_xdr);
+        self.connection.call(call).await
+    }
+}
+
+/// An asynchronous client for versions 3 and 4 of the rpcbind protocol.
+pub struct RpcBindClient {
+    connection: Connection,
+    version: RpcBindVersion,
+}
+
+impl RpcBindClient {
+    pub async fn connect(
+        addr: SocketAddr,
+        protocol: Protocol,
+        version: RpcBindVersion,
+    ) -> ClientResult<Self> {
+        Ok(Self {
+            connection: Connection::connect(addr, protocol, DEFAULT_TIMEOUT).await?,
+            version,
+        })
+    }
+
+    /// Sets how long a call may take, including every UDP retransmission.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.connection.timeout = timeout;
+    }
+
+    pub fn set_retransmit_interval(&mut self, interval: Duration) {
+        self.connection.retransmit_interval = interval;
+    }
+
+    pub async fn set(&mut self, rpcb: RPCB) -> ClientResult<bool> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::Set(rpcb), decode_xdr);
+        self.connection.call(call).await
+    }
+
+    pub async fn unset(&mut self, rpcb: RPCB) -> ClientResult<bool> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::Unset(rpcb), decode_xdr);
+        self.connection.call(call).await
+    }
+
+    /// Returns the universal address `rpcb` is registered at, or an empty string if it is not
+    /// registered.
+    pub async fn get_addr(&mut self, rpcb: RPCB) -> ClientResult<String> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::GetAddr(rpcb), decode_xdr);
+        self.connection.call(call).await
+    }
+
+    pub async fn dump(&mut self) -> ClientResult<RPList> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::Dump, decode_xdr);
+        self.connection.call(call).await
+    }
+
+    /// Returns the server's time in seconds since the unix epoch.
+    pub async fn get_time(&mut self) -> ClientResult<u32> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::GetTime, decode_xdr);
+        self.connection.call(call).await
+    }
+
+    /// Only available in version 4.
+    pub async fn get_addr_list(&mut self, rpcb: RPCB) -> ClientResult<EntryList> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::GetAddrList(rpcb), decode_xdr);
+        self.connection.call(call).await
+    }
+
+    /// Only available in version 4.
+    pub async fn get_stat(&mut self) -> ClientResult<StatByVers> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::GetStat, decode_xdr);
+        self.connection.call(call).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, time::Duration};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, UdpSocket},
+    };
+
+    use super::{PortMapperClient, RpcBindClient, broadcast};
+    use crate::{
+        client::{
+            BroadcastVersion, ClientError, Protocol, RpcBindVersion, finish_record, start_record,
+            tests::{answer, mapping},
+        },
+        xdr_types::rpcbind::{RPCB, RmtCallArgs},
+    };
+
+    #[tokio::test]
+    async fn udp_call_is_retransmitted() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 1024];
+            // Lose the first transmission
+            server.recv_from(&mut buffer).await.unwrap();
+            let (len, peer) = server.recv_from(&mut buffer).await.unwrap();
+            let mut record = start_record();
+            record.extend_from_slice(&buffer[..len]);
+            let reply = answer(&finish_record(record));
+            server.send_to(&reply[4..], peer).await.unwrap();
+        });
+
+        let mut client = PortMapperClient::connect(addr, Protocol::Udp)
+            .await
+            .unwrap();
+        client.set_retransmit_interval(Duration::from_millis(50));
+        assert_eq!(client.get_port(mapping(102049, 0)).await.unwrap(), 2049);
+    }
+
+    async fn serve_tcp() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            loop {
+                let mut record = vec![0u8; 4];
+                if stream.read_exact(&mut record).await.is_err() {
+                    return;
+                }
+                let len = u32::from_be_bytes(record[..4].try_into().unwrap()) & !(1 << 31);
+                record.resize(4 + len as usize, 0);
+                stream.read_exact(&mut record[4..]).await.unwrap();
+
+                // Send the reply as two fragments
+                let reply = answer(&record);
+                let (first, second) = reply[4..].split_at(8);
+                stream
+                    .write_all(&(first.len() as u32).to_be_bytes())
+                    .await
+                    .unwrap();
+                stream.write_all(first).await.unwrap();
+                stream
+                    .write_all(&(second.len() as u32 | 1 << 31).to_be_bytes())
+                    .await
+                    .unwrap();
+                stream.write_all(second).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn tcp_calls_reassemble_fragments() {
+        let addr = serve_tcp().await;
+        let mut client = PortMapperClient::connect(addr, Protocol::Tcp)
+            .await
+            .unwrap();
+
+        let list = client.dump().await.unwrap();
+        assert_eq!(*list, [mapping(100000, 111), mapping(100003, 2049)]);
+        assert_eq!(client.get_port(mapping(100111, 0)).await.unwrap(), 111);
+    }
+
+    #[tokio::test]
+    async fn tcp_call_after_a_timeout_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            // Stall part way through a reply, leaving the rest of its record unsent
+            let (mut stalled, _) = listener.accept().await.unwrap();
+            let mut buffer = [0u8; 1024];
+            let _ = stalled.read(&mut buffer).await.unwrap();
+            stalled.write_all(&[0x80, 0, 0, 100, 0, 0]).await.unwrap();
+
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut record = vec![0u8; 4];
+            stream.read_exact(&mut record).await.unwrap();
+            let len = u32::from_be_bytes(record[..4].try_into().unwrap()) & !(1 << 31);
+            record.resize(4 + len as usize, 0);
+            stream.read_exact(&mut record[4..]).await.unwrap();
+            stream.write_all(&answer(&record)).await.unwrap();
+            drop(stalled);
+        });
+
+        let mut client = PortMapperClient::connect(addr, Protocol::Tcp)
+            .await
+            .unwrap();
+        client.set_timeout(Duration::from_millis(100));
+        assert!(matches!(client.dump().await, Err(ClientError::TimedOut)));
+        client.set_timeout(Duration::from_secs(5));
+        let list = client.dump().await.unwrap();
+        assert_eq!(*list, [mapping(100000, 111), mapping(100003, 2049)]);
+    }
+
+    #[tokio::test]
+    async fn rpcbind_get_addr() {
+        let addr = serve_tcp().await;
+        let mut client = RpcBindClient::connect(addr, Protocol::Tcp, RpcBindVersion::V4)
+            .await
+            .unwrap();
+        let rpcb = RPCB {
+            r_prog: 100003,
+            r_vers: 3,
+            r_netid: "tcp".to_owned(),
+            r_addr: String::new(),
+            r_owner: String::new(),
+        };
+        assert_eq!(client.get_addr(rpcb).await.unwrap(), "127.0.0.1.3.1");
+    }
+
+    #[tokio::test]
+    async fn broadcast_ignores_duplicate_replies() {
+        let mut targets = Vec::new();
+        for _ in 0..2 {
+            let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            targets.push(server.local_addr().unwrap());
+            tokio::spawn(async move {
+                let mut buffer = [0u8; 1024];
+                let (len, peer) = server.recv_from(&mut buffer).await.unwrap();
+                let mut record = start_record();
+                record.extend_from_slice(&buffer[..len]);
+                let reply = answer(&finish_record(record));
+                // Answer twice, as a server seeing a retransmission would
+                server.send_to(&reply[4..], peer).await.unwrap();
+                server.send_to(&reply[4..], peer).await.unwrap();
+            });
+        }
+
+        let args = RmtCallArgs {
+            prog: 100003,
+            vers: 3,
+            proc: 0,
+            args: Vec::new(),
+        };
+        let replies = broadcast(
+            &targets,
+            BroadcastVersion::V4,
+            args,
+            Duration::from_millis(200),
+        )
+        .await
+        .unwrap();
+        assert_eq!(replies.len(), 2);
+        assert!(
+            replies
+                .iter()
+                .all(|reply| reply.universal_address == "127.0.0.1.8.1")
+        );
+    }
+}
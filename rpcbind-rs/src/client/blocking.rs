@@ -0,0 +1,577 @@
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+
+use super::{
+    Broadcast, BroadcastReply, BroadcastVersion, Call, ClientError, ClientResult,
+    DEFAULT_RETRANSMIT_INTERVAL, DEFAULT_TIMEOUT, Exchange, MAX_DATAGRAM_LEN, MSG_HEADER_LEN,
+    Protocol, RpcBindVersion, datagram_record, decode_unit, decode_xdr, desynchronizes,
+    finish_record, fragment_header, grow_record, start_record, unspecified_for,
+};
+use crate::{
+    request::{PortMapperRequest, RpcBindRequest},
+    xdr_types::{
+        port_mapper::{Mapping, PMapList},
+        rpcbind::{EntryList, RPCB, RPList, RmtCallArgs, StatByVers},
+    },
+};
+
+enum Transport {
+    /// `None` once a call fails part way through, until the next call reconnects.
+    Tcp(Option<TcpStream>),
+    Udp(UdpSocket),
+}
+
+struct Connection {
+    addr: SocketAddr,
+    transport: Transport,
+    timeout: Duration,
+    retransmit_interval: Duration,
+}
+
+impl Connection {
+    /// Connects to `addr`, giving up on TCP after `timeout`, which then also bounds each call.
+    fn connect(addr: SocketAddr, protocol: Protocol, timeout: Duration) -> ClientResult<Self> {
+        let transport = match protocol {
+            Protocol::Tcp => Transport::Tcp(Some(connect_tcp(addr, Instant::now() + timeout)?)),
+            Protocol::Udp => {
+                let socket = UdpSocket::bind(unspecified_for(addr))?;
+                socket.connect(addr)?;
+                Transport::Udp(socket)
+            }
+        };
+        Ok(Self {
+            addr,
+            transport,
+            timeout,
+            retransmit_interval: DEFAULT_RETRANSMIT_INTERVAL,
+        })
+    }
+
+    fn call<T>(&mut self, call: Call<T>) -> ClientResult<T> {
+        let exchange = Exchange::new(call)?;
+        let deadline = Instant::now() + self.timeout;
+        match &mut self.transport {
+            Transport::Tcp(connected) => {
+                let mut stream = match connected.take() {
+                    Some(stream) => stream,
+                    None => connect_tcp(self.addr, deadline)?,
+                };
+                let result = call_tcp(&mut stream, &exchange, deadline).map_err(timed_out);
+                if !result.as_ref().is_err_and(desynchronizes) {
+                    *connected = Some(stream);
+                }
+                result
+            }
+            Transport::Udp(socket) => {
+                call_udp(socket, &exchange, deadline, self.retransmit_interval).map_err(timed_out)
+            }
+        }
+    }
+}
+
+fn connect_tcp(addr: SocketAddr, deadline: Instant) -> ClientResult<TcpStream> {
+    remaining(deadline)
+        .and_then(|timeout| TcpStream::connect_timeout(&addr, timeout))
+        .map_err(|e| timed_out(e.into()))
+}
+
+/// Reports the i/o errors of a socket timing out as the call timing out.
+fn timed_out(error: ClientError) -> ClientError {
+    match error {
+        ClientError::Io(e) if is_timeout(&e) => ClientError::TimedOut,
+        e => e,
+    }
+}
+
+fn is_timeout(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// The time left until `deadline`, as an error once it has passed.
+fn remaining(deadline: Instant) -> io::Result<Duration> {
+    match deadline.checked_duration_since(Instant::now()) {
+        Some(remaining) if !remaining.is_zero() => Ok(remaining),
+        _ => Err(io::ErrorKind::TimedOut.into()),
+    }
+}
+
+fn call_tcp<T>(
+    stream: &mut TcpStream,
+    exchange: &Exchange<T>,
+    deadline: Instant,
+) -> ClientResult<T> {
+    stream.set_write_timeout(Some(remaining(deadline)?))?;
+    stream.write_all(&exchange.record)?;
+    loop {
+        if let Some(result) = exchange.receive(read_record(stream, deadline)?)? {
+            return Ok(result);
+        }
+    }
+}
+
+/// Reads fragments until the last one of a record arrives.
+fn read_record(stream: &mut TcpStream, deadline: Instant) -> ClientResult<Bytes> {
+    let mut record = start_record();
+    loop {
+        let mut header = [0u8; MSG_HEADER_LEN];
+        stream.set_read_timeout(Some(remaining(deadline)?))?;
+        stream.read_exact(&mut header)?;
+        let (len, last) = fragment_header(header);
+
+        let start = grow_record(&mut record, len)?;
+        stream.set_read_timeout(Some(remaining(deadline)?))?;
+        stream.read_exact(&mut record[start..])?;
+        if last {
+            return Ok(finish_record(record));
+        }
+    }
+}
+
+fn call_udp<T>(
+    socket: &UdpSocket,
+    exchange: &Exchange<T>,
+    deadline: Instant,
+    retransmit_interval: Duration,
+) -> ClientResult<T> {
+    let mut buffer = vec![0u8; MAX_DATAGRAM_LEN];
+
+    loop {
+        socket.send(exchange.datagram())?;
+        let resend_at = deadline.min(Instant::now() + retransmit_interval);
+        while let Ok(wait) = remaining(resend_at) {
+            socket.set_read_timeout(Some(wait))?;
+            let received = match socket.recv(&mut buffer) {
+                Ok(received) => received,
+                Err(e) if is_timeout(&e) => break,
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(result) = exchange.receive(datagram_record(&buffer[..received]))? {
+                return Ok(result);
+            }
+        }
+        remaining(deadline)?;
+    }
+}
+
+/// Asks rpcbind at every address in `targets` to forward a call, collecting the replies that
+/// arrive within `timeout`.
+///
+/// Targets are usually broadcast addresses like `255.255.255.255:111`, or
+/// [`RPCB_MULTICAST_ADDR`](super::RPCB_MULTICAST_ADDR) on IPv6, and must all be of the same
+/// address family. rpcbind stays silent when it cannot forward the call, so only servers
+/// running the program answer.
+pub fn broadcast(
+    targets: &[SocketAddr],
+    version: BroadcastVersion,
+    args: RmtCallArgs,
+    timeout: Duration,
+) -> ClientResult<Vec<BroadcastReply>> {
+    let Some(&first) = targets.first() else {
+        return Ok(Vec::new());
+    };
+    let mut broadcast = Broadcast::new(version, args)?;
+    let socket = UdpSocket::bind(unspecified_for(first))?;
+    socket.set_broadcast(true)?;
+    let deadline = Instant::now() + timeout;
+    let mut buffer = vec![0u8; MAX_DATAGRAM_LEN];
+
+    loop {
+        for target in targets {
+            socket.send_to(broadcast.exchange.datagram(), target)?;
+        }
+        let resend_at = deadline.min(Instant::now() + DEFAULT_RETRANSMIT_INTERVAL);
+        while let Ok(wait) = remaining(resend_at) {
+            socket.set_read_timeout(Some(wait))?;
+            match socket.recv_from(&mut buffer) {
+                Ok((received, responder)) => broadcast.receive(&buffer[..received], responder),
+                Err(e) if is_timeout(&e) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if remaining(deadline).is_err() {
+            return Ok(broadcast.replies);
+        }
+    }
+}
+
+/// Calls the NULL procedure of any `program` at `addr`, succeeding if it answers.
+///
+/// A server that has the program but not `version` fails with
+/// [`AcceptedStatus::ProgramMismatch`](onc_rpc::AcceptedStatus::ProgramMismatch), which
+/// carries the versions it does support.
+pub fn ping(
+    addr: SocketAddr,
+    protocol: Protocol,
+    program: u32,
+    version: u32,
+    timeout: Duration,
+) -> ClientResult<()> {
+    let mut connection = Connection::connect(addr, protocol, timeout)?;
+    connection.call(Call::null(program, version, decode_unit))
+}
+
+/// A blocking client for version 2 of the protocol, known as portmapper.
+pub struct PortMapperClient {
+    connection: Connection,
+}
+
+impl PortMapperClient {
+    pub fn connect(addr: SocketAddr, protocol: Protocol) -> ClientResult<Self> {
+        Ok(Self {
+            connection: Connection::connect(addr, protocol, DEFAULT_TIMEOUT)?,
+        })
+    }
+
+    /// Sets how long a call may take, including every UDP retransmission.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.connection.timeout = timeout;
+    }
+
+    pub fn set_retransmit_interval(&mut self, interval: Duration) {
+        self.connection.retransmit_interval = interval;
+    }
+
+    pub fn null(&mut self) -> ClientResult<()> {
+        let call = Call::port_mapper(PortMapperRequest::Null, decode_unit);
+        self.connection.call(call)
+    }
+
+    pub fn set(&mut self, mapping: Mapping) -> ClientResult<bool> {
+        let call = Call::port_mapper(PortMapperRequest::Set(mapping), decode_xdr);
+        self.connection.call(call)
+    }
+
+    pub fn unset(&mut self, mapping: Mapping) -> ClientResult<bool> {
+        let call = Call::port_mapper(PortMapperRequest::Unset(mapping), decode_xdr);
+        self.connection.call(call)
+    }
+
+    /// Returns the port `mapping` is registered on, or 0 if it is not registered.
+    pub fn get_port(&mut self, mapping: Mapping) -> ClientResult<u32> {
+        let call = Call::port_mapper(PortMapperRequest::GetPort(mapping), decode_xdr);
+        self.connection.call(call)
+    }
+
+    pub fn dump(&mut self) -> ClientResult<PMapList> {
+        let call = Call::port_mapper(PortMapperRequest::Dump, decode_xdr);
+        self.connection.call(call)
+    }
+}
+
+/// A blocking client for versions 3 and 4 of the rpcbind protocol.
+pub struct RpcBindClient {
+    connection: Connection,
+    version: RpcBindVersion,
+}
+
+impl RpcBindClient {
+    pub fn connect(
+        addr: SocketAddr,
+        protocol: Protocol,
+        version: RpcBindVersion,
+    ) -> ClientResult<Self> {
+        Ok(Self {
+            connection: Connection::connect(addr, protocol, DEFAULT_TIMEOUT)?,
+            version,
+        })
+    }
+
+    /// Sets how long a call may take, including every UDP retransmission.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.connection.timeout = timeout;
+    }
+
+    pub fn set_retransmit_interval(&mut self, interval: Duration) {
+        self.connection.retransmit_interval = interval;
+    }
+
+    pub fn set(&mut self, rpcb: RPCB) -> ClientResult<bool> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::Set(rpcb), decode_xdr);
+        self.connection.call(call)
+    }
+
+    pub fn unset(&mut self, rpcb: RPCB) -> ClientResult<bool> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::Unset(rpcb), decode_xdr);
+        self.connection.call(call)
+    }
+
+    /// Returns the universal address `rpcb` is registered at, or an empty string if it is not
+    /// registered.
+    pub fn get_addr(&mut self, rpcb: RPCB) -> ClientResult<String> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::GetAddr(rpcb), decode_xdr);
+        self.connection.call(call)
+    }
+
+    pub fn dump(&mut self) -> ClientResult<RPList> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::Dump, decode_xdr);
+        self.connection.call(call)
+    }
+
+    /// Returns the server's time in seconds since the unix epoch.
+    pub fn get_time(&mut self) -> ClientResult<u32> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::GetTime, decode_xdr);
+        self.connection.call(call)
+    }
+
+    /// Only available in version 4.
+    pub fn get_addr_list(&mut self, rpcb: RPCB) -> ClientResult<EntryList> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::GetAddrList(rpcb), decode_xdr);
+        self.connection.call(call)
+    }
+
+    /// Only available in version 4.
+    pub fn get_stat(&mut self) -> ClientResult<StatByVers> {
+        let call = Call::rpcbind(self.version, RpcBindRequest::GetStat, decode_xdr);
+        self.connection.call(call)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpListener, UdpSocket},
+        thread,
+        time::Duration,
+    };
+
+    use onc_rpc::{AcceptedReply, AcceptedStatus, MessageType, ReplyBody, RpcMessage};
+
+    use super::{PortMapperClient, RpcBindClient, broadcast, ping};
+    use crate::{
+        client::{
+            BroadcastVersion, ClientError, Protocol, RpcBindVersion, finish_record, start_record,
+            tests::{answer, mapping},
+        },
+        xdr_types::rpcbind::{RPCB, RmtCallArgs},
+    };
+
+    #[test]
+    fn udp_call_is_retransmitted() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 1024];
+            // Lose the first transmission
+            server.recv_from(&mut buffer).unwrap();
+            let (len, peer) = server.recv_from(&mut buffer).unwrap();
+            let mut record = start_record();
+            record.extend_from_slice(&buffer[..len]);
+            let reply = answer(&finish_record(record));
+            server.send_to(&reply[4..], peer).unwrap();
+        });
+
+        let mut client = PortMapperClient::connect(addr, Protocol::Udp).unwrap();
+        client.set_retransmit_interval(Duration::from_millis(50));
+        assert_eq!(client.get_port(mapping(102049, 0)).unwrap(), 2049);
+    }
+
+    #[test]
+    fn tcp_call_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Read the call but never answer it
+            let mut buffer = [0u8; 1024];
+            while stream.read(&mut buffer).unwrap_or(0) > 0 {}
+        });
+
+        let mut client = PortMapperClient::connect(addr, Protocol::Tcp).unwrap();
+        client.set_timeout(Duration::from_millis(100));
+        assert!(matches!(client.dump(), Err(ClientError::TimedOut)));
+        drop(client);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn tcp_call_after_a_timeout_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            // Stall part way through a reply, leaving the rest of its record unsent
+            let (mut stalled, _) = listener.accept().unwrap();
+            let mut buffer = [0u8; 1024];
+            let _ = stalled.read(&mut buffer).unwrap();
+            stalled.write_all(&[0x80, 0, 0, 100, 0, 0]).unwrap();
+
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut record = vec![0u8; 4];
+            stream.read_exact(&mut record).unwrap();
+            let len = u32::from_be_bytes(record[..4].try_into().unwrap()) & !(1 << 31);
+            record.resize(4 + len as usize, 0);
+            stream.read_exact(&mut record[4..]).unwrap();
+            stream.write_all(&answer(&record)).unwrap();
+            drop(stalled);
+        });
+
+        let mut client = PortMapperClient::connect(addr, Protocol::Tcp).unwrap();
+        client.set_timeout(Duration::from_millis(100));
+        assert!(matches!(client.dump(), Err(ClientError::TimedOut)));
+        client.set_timeout(Duration::from_secs(5));
+        let list = client.dump().unwrap();
+        assert_eq!(*list, [mapping(100000, 111), mapping(100003, 2049)]);
+    }
+
+    #[test]
+    fn tcp_dump() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut record = vec![0u8; 4];
+            stream.read_exact(&mut record).unwrap();
+            let len = u32::from_be_bytes(record[..4].try_into().unwrap()) & !(1 << 31);
+            record.resize(4 + len as usize, 0);
+            stream.read_exact(&mut record[4..]).unwrap();
+            stream.write_all(&answer(&record)).unwrap();
+        });
+
+        let mut client = PortMapperClient::connect(addr, Protocol::Tcp).unwrap();
+        let list = client.dump().unwrap();
+        assert_eq!(*list, [mapping(100000, 111), mapping(100003, 2049)]);
+    }
+
+    #[test]
+    fn tcp_reply_over_the_limit() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buffer = [0u8; 1024];
+            let _ = stream.read(&mut buffer).unwrap();
+            // Claim a 2 GiB fragment without sending it
+            stream.write_all(&u32::MAX.to_be_bytes()).unwrap();
+        });
+
+        let mut client = PortMapperClient::connect(addr, Protocol::Tcp).unwrap();
+        assert!(matches!(
+            client.dump(),
+            Err(ClientError::ReplyTooLong { len: 0x7fff_ffff })
+        ));
+    }
+
+    #[test]
+    fn truncated_reply_is_an_error() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 1024];
+            let (len, peer) = server.recv_from(&mut buffer).unwrap();
+            let mut record = start_record();
+            record.extend_from_slice(&buffer[..len]);
+            let call = RpcMessage::try_from(finish_record(record)).unwrap();
+            // A string of 100 bytes that ends after the first
+            let payload = [0, 0, 0, 100, b'a', 0, 0, 0];
+            let reply = RpcMessage::new(
+                call.xid(),
+                MessageType::Reply(ReplyBody::Accepted(AcceptedReply::new(
+                    onc_rpc::auth::AuthFlavor::<&[u8]>::AuthNone(None),
+                    AcceptedStatus::Success(payload.as_slice()),
+                ))),
+            );
+            server
+                .send_to(&reply.serialise().unwrap()[4..], peer)
+                .unwrap();
+        });
+
+        let mut client = RpcBindClient::connect(addr, Protocol::Udp, RpcBindVersion::V4).unwrap();
+        let rpcb = RPCB {
+            r_prog: 100003,
+            r_vers: 3,
+            r_netid: "tcp".to_owned(),
+            r_addr: String::new(),
+            r_owner: String::new(),
+        };
+        assert!(matches!(client.get_addr(rpcb), Err(ClientError::Decode(_))));
+    }
+
+    #[test]
+    fn ping_reports_supported_versions() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 1024];
+            let (len, peer) = server.recv_from(&mut buffer).unwrap();
+            let mut record = start_record();
+            record.extend_from_slice(&buffer[..len]);
+            let call = RpcMessage::try_from(finish_record(record)).unwrap();
+            assert_eq!(call.call_body().unwrap().program(), 100003);
+            let reply = RpcMessage::new(
+                call.xid(),
+                MessageType::Reply(ReplyBody::Accepted(AcceptedReply::new(
+                    onc_rpc::auth::AuthFlavor::<&[u8]>::AuthNone(None),
+                    AcceptedStatus::<&[u8]>::ProgramMismatch { low: 3, high: 4 },
+                ))),
+            );
+            server
+                .send_to(&reply.serialise().unwrap()[4..], peer)
+                .unwrap();
+        });
+
+        let result = ping(addr, Protocol::Udp, 100003, 0, Duration::from_secs(5));
+        assert!(matches!(
+            result,
+            Err(ClientError::Failed(AcceptedStatus::ProgramMismatch {
+                low: 3,
+                high: 4
+            }))
+        ));
+    }
+
+    /// Starts a portmapper on loopback that answers every call, or none if `silent`.
+    fn responder(silent: bool) -> SocketAddr {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 1024];
+            while let Ok((len, peer)) = server.recv_from(&mut buffer) {
+                if silent {
+                    continue;
+                }
+                let mut record = start_record();
+                record.extend_from_slice(&buffer[..len]);
+                let reply = answer(&finish_record(record));
+                server.send_to(&reply[4..], peer).unwrap();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn broadcast_collects_every_responder() {
+        let targets = [responder(false), responder(true), responder(false)];
+        let args = RmtCallArgs {
+            prog: 100003,
+            vers: 3,
+            proc: 0,
+            args: vec![0, 0, 0, 7],
+        };
+        let mut replies = broadcast(
+            &targets,
+            BroadcastVersion::V2,
+            args,
+            Duration::from_millis(300),
+        )
+        .unwrap();
+        replies.sort_by_key(|reply| reply.responder);
+        let mut answering = [targets[0], targets[2]];
+        answering.sort();
+
+        assert_eq!(replies.len(), 2);
+        for (reply, target) in replies.iter().zip(answering) {
+            assert_eq!(reply.responder, target);
+            // portmapper only answers with a port on the responder
+            assert_eq!(reply.universal_address, "127.0.0.1.8.1");
+            assert_eq!(reply.results, [0, 0, 0, 7]);
+        }
+    }
+}
@@ -2,4 +2,18 @@ pub mod xdr_types;
 
 pub mod request;
 
-pub type RpcBindResult<T> = Result<T, onc_rpc::AcceptedStatus<[u8; 0]>>;
+pub mod response;
+
+pub mod client;
+
+pub mod netconfig;
+
+pub mod netid;
+
+pub mod rpc_names;
+
+mod reply;
+
+pub mod universal_address;
+
+pub type RpcBindResult<T> = Result<T, request::RequestError>;
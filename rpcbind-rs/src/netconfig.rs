@@ -0,0 +1,319 @@
+//! The network configuration database in `/etc/netconfig`, which describes the transports
+//! named by netids like `tcp6`.
+
+use std::{env, fmt, fs, io, path::PathBuf, str::FromStr};
+
+use thiserror::Error;
+
+pub const DEFAULT_PATH: &str = "/etc/netconfig";
+
+/// The table libtirpc ships, used when the system has no netconfig file.
+const DEFAULTS: &str = "\
+udp        tpi_clts      v     inet     udp     -       -
+tcp        tpi_cots_ord  v     inet     tcp     -       -
+udp6       tpi_clts      v     inet6    udp     -       -
+tcp6       tpi_cots_ord  v     inet6    tcp     -       -
+rawip      tpi_raw       -     inet      -      -       -
+local      tpi_cots_ord  -     loopback  -      -       -
+unix       tpi_cots_ord  -     loopback  -      -       -
+";
+
+#[derive(Debug, Error)]
+pub enum NetConfigError {
+    #[error("failed to read {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error("line {line}: missing {field}")]
+    MissingField { line: usize, field: &'static str },
+    #[error("line {line}: unknown semantics {value}")]
+    UnknownSemantics { line: usize, value: String },
+    #[error("line {line}: unknown flag {flag:?}")]
+    UnknownFlag { line: usize, flag: char },
+    #[error("line {line}: unexpected field {value}")]
+    TrailingField { line: usize, value: String },
+}
+
+/// How a transport delivers data, the `nc_semantics` of libtirpc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Semantics {
+    /// Connectionless, like UDP.
+    Clts,
+    /// Connection oriented.
+    Cots,
+    /// Connection oriented with orderly release, like TCP.
+    CotsOrd,
+    Raw,
+}
+
+impl Semantics {
+    /// The `NC_TPI_*` constant sent in `rpcb_entry.r_nc_semantics`.
+    pub fn code(self) -> u32 {
+        match self {
+            Semantics::Clts => 1,
+            Semantics::Cots => 2,
+            Semantics::CotsOrd => 3,
+            Semantics::Raw => 4,
+        }
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        Some(match code {
+            1 => Semantics::Clts,
+            2 => Semantics::Cots,
+            3 => Semantics::CotsOrd,
+            4 => Semantics::Raw,
+            _ => return None,
+        })
+    }
+}
+
+impl FromStr for Semantics {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, ()> {
+        Ok(match value {
+            "tpi_clts" => Semantics::Clts,
+            "tpi_cots" => Semantics::Cots,
+            "tpi_cots_ord" => Semantics::CotsOrd,
+            "tpi_raw" => Semantics::Raw,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl fmt::Display for Semantics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Semantics::Clts => "tpi_clts",
+            Semantics::Cots => "tpi_cots",
+            Semantics::CotsOrd => "tpi_cots_ord",
+            Semantics::Raw => "tpi_raw",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag {
+    /// `v`: the transport is part of the default search path when `NETPATH` is unset.
+    Visible,
+    /// `b`: the transport supports broadcast.
+    Broadcast,
+}
+
+/// A line of the database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetConfigEntry {
+    pub netid: String,
+    pub semantics: Semantics,
+    pub flags: Vec<Flag>,
+    /// The protocol family, like `inet6` or `loopback`.
+    pub protofmly: String,
+    /// The protocol, like `tcp`, or `None` when the family has none.
+    pub proto: Option<String>,
+    pub device: Option<String>,
+    pub nametoaddr_libs: Vec<String>,
+}
+
+impl NetConfigEntry {
+    pub fn is_visible(&self) -> bool {
+        self.flags.contains(&Flag::Visible)
+    }
+
+    pub fn supports_broadcast(&self) -> bool {
+        self.flags.contains(&Flag::Broadcast)
+    }
+
+    fn parse(line: usize, content: &str) -> Result<Self, NetConfigError> {
+        let mut fields = content.split_whitespace();
+        let mut field = |field| {
+            fields
+                .next()
+                .ok_or(NetConfigError::MissingField { line, field })
+        };
+        let optional = |value: &str| (value != "-").then(|| value.to_owned());
+
+        let netid = field("netid")?.to_owned();
+        let semantics = field("semantics")?;
+        let semantics = semantics
+            .parse()
+            .map_err(|()| NetConfigError::UnknownSemantics {
+                line,
+                value: semantics.to_owned(),
+            })?;
+        let flags = field("flags")?;
+        let flags = match flags {
+            "-" => Vec::new(),
+            flags => flags
+                .chars()
+                .map(|flag| match flag {
+                    'v' => Ok(Flag::Visible),
+                    'b' => Ok(Flag::Broadcast),
+                    flag => Err(NetConfigError::UnknownFlag { line, flag }),
+                })
+                .collect::<Result<_, _>>()?,
+        };
+        let protofmly = field("protocol family")?.to_owned();
+        let proto = optional(field("protocol")?);
+        let device = optional(field("device")?);
+        let nametoaddr_libs = optional(field("name-to-address libraries")?)
+            .map(|libs| libs.split(',').map(str::to_owned).collect())
+            .unwrap_or_default();
+        if let Some(value) = fields.next() {
+            return Err(NetConfigError::TrailingField {
+                line,
+                value: value.to_owned(),
+            });
+        }
+
+        Ok(Self {
+            netid,
+            semantics,
+            flags,
+            protofmly,
+            proto,
+            device,
+            nametoaddr_libs,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetConfig {
+    entries: Vec<NetConfigEntry>,
+}
+
+impl NetConfig {
+    /// Reads [`DEFAULT_PATH`], falling back to [`NetConfig::defaults`] if it does not exist.
+    pub fn load() -> Result<Self, NetConfigError> {
+        match Self::from_path(DEFAULT_PATH) {
+            Err(NetConfigError::Io { source, .. }) if source.kind() == io::ErrorKind::NotFound => {
+                Ok(Self::defaults())
+            }
+            result => result,
+        }
+    }
+
+    pub fn from_path(path: impl Into<PathBuf>) -> Result<Self, NetConfigError> {
+        let path = path.into();
+        match fs::read_to_string(&path) {
+            Ok(content) => Self::parse(&content),
+            Err(source) => Err(NetConfigError::Io { path, source }),
+        }
+    }
+
+    /// Parses the database, where `#` starts a comment.
+    pub fn parse(content: &str) -> Result<Self, NetConfigError> {
+        let mut entries = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let line_content = line.split('#').next().unwrap_or_default();
+            if line_content.trim().is_empty() {
+                continue;
+            }
+            entries.push(NetConfigEntry::parse(index + 1, line_content)?);
+        }
+        Ok(Self { entries })
+    }
+
+    /// The transports libtirpc knows without a netconfig file.
+    pub fn defaults() -> Self {
+        Self::parse(DEFAULTS).expect("the built-in netconfig is valid")
+    }
+
+    pub fn entries(&self) -> &[NetConfigEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, netid: &str) -> Option<&NetConfigEntry> {
+        self.entries.iter().find(|entry| entry.netid == netid)
+    }
+
+    /// The transports to try in order, as chosen by the `NETPATH` environment variable.
+    pub fn netpath(&self) -> Vec<&NetConfigEntry> {
+        self.resolve_netpath(env::var("NETPATH").ok().as_deref())
+    }
+
+    /// The transports named by a colon separated `netpath`, skipping unknown netids.
+    ///
+    /// An unset or empty path selects every visible transport in file order.
+    pub fn resolve_netpath(&self, netpath: Option<&str>) -> Vec<&NetConfigEntry> {
+        match netpath.filter(|netpath| !netpath.is_empty()) {
+            Some(netpath) => netpath
+                .split(':')
+                .filter_map(|netid| self.get(netid))
+                .collect(),
+            None => self
+                .entries
+                .iter()
+                .filter(|entry| entry.is_visible())
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Flag, NetConfig, NetConfigError, Semantics};
+
+    #[test]
+    fn parses_defaults() {
+        let config = NetConfig::defaults();
+        assert_eq!(config.entries().len(), 7);
+
+        let tcp6 = config.get("tcp6").unwrap();
+        assert_eq!(tcp6.semantics, Semantics::CotsOrd);
+        assert_eq!(tcp6.flags, [Flag::Visible]);
+        assert_eq!(tcp6.protofmly, "inet6");
+        assert_eq!(tcp6.proto.as_deref(), Some("tcp"));
+
+        let local = config.get("local").unwrap();
+        assert!(!local.is_visible());
+        assert_eq!(local.proto, None);
+    }
+
+    #[test]
+    fn netpath() {
+        let config = NetConfig::parse(
+            "# comment\n\
+             udp  tpi_clts     vb inet  udp - -\n\
+             \n\
+             tcp  tpi_cots_ord v  inet  tcp - - # trailing\n\
+             unix tpi_cots_ord -  loopback - - -\n",
+        )
+        .unwrap();
+        assert!(config.get("udp").unwrap().supports_broadcast());
+
+        let netids = |netpath| {
+            config
+                .resolve_netpath(netpath)
+                .into_iter()
+                .map(|entry| entry.netid.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(netids(None), ["udp", "tcp"]);
+        assert_eq!(netids(Some("")), ["udp", "tcp"]);
+        assert_eq!(netids(Some("unix:bogus:tcp")), ["unix", "tcp"]);
+    }
+
+    #[test]
+    fn reports_line_numbers() {
+        let error = |content| NetConfig::parse(content).unwrap_err();
+        assert!(matches!(
+            error("udp tpi_clts v inet udp - -\ntcp tpi_cots_ord v inet\n"),
+            NetConfigError::MissingField {
+                line: 2,
+                field: "protocol"
+            }
+        ));
+        assert!(matches!(
+            error("\n\nudp tpi_bogus v inet udp - -"),
+            NetConfigError::UnknownSemantics { line: 3, .. }
+        ));
+        assert!(matches!(
+            error("udp tpi_clts vx inet udp - -"),
+            NetConfigError::UnknownFlag { line: 1, flag: 'x' }
+        ));
+        assert!(matches!(
+            error("udp tpi_clts v inet udp - - extra"),
+            NetConfigError::TrailingField { line: 1, .. }
+        ));
+    }
+}
@@ -0,0 +1,167 @@
+//! Netids, the names rpcbind keys transports by, like `tcp6`.
+
+use std::{convert::Infallible, fmt, str::FromStr};
+
+use crate::universal_address::Family;
+
+/// The `IPPROTO_TCP` protocol number portmapper uses for TCP.
+pub const IPPROTO_TCP: u32 = 6;
+/// The `IPPROTO_UDP` protocol number portmapper uses for UDP.
+pub const IPPROTO_UDP: u32 = 17;
+
+/// A netid, with the transports in libtirpc's default netconfig named.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Netid {
+    Tcp,
+    Udp,
+    Tcp6,
+    Udp6,
+    Local,
+    Unix,
+    /// Any netid not named by another variant, which only [`Netid::from`] can make so every
+    /// netid has a single representation.
+    Other(OtherNetid),
+}
+
+/// The name of a netid without a variant of its own in [`Netid`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OtherNetid(String);
+
+impl OtherNetid {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Netid {
+    /// The netid portmapper registrations with protocol number `protocol` are kept under.
+    ///
+    /// Portmapper only knows IPv4 TCP and UDP, so other protocols have no netid.
+    pub fn from_protocol(protocol: u32) -> Option<Self> {
+        match protocol {
+            IPPROTO_TCP => Some(Netid::Tcp),
+            IPPROTO_UDP => Some(Netid::Udp),
+            _ => None,
+        }
+    }
+
+    /// The IP protocol number of the transport, for the TCP and UDP netids.
+    pub fn protocol(&self) -> Option<u32> {
+        match self {
+            Netid::Tcp | Netid::Tcp6 => Some(IPPROTO_TCP),
+            Netid::Udp | Netid::Udp6 => Some(IPPROTO_UDP),
+            _ => None,
+        }
+    }
+
+    /// The protocol number a portmapper reports the transport with, which is only defined
+    /// for IPv4.
+    pub fn portmapper_protocol(&self) -> Option<u32> {
+        match self {
+            Netid::Tcp | Netid::Udp => self.protocol(),
+            _ => None,
+        }
+    }
+
+    /// The address family of a named netid.
+    ///
+    /// Other netids are only described by the netconfig database.
+    pub fn family(&self) -> Option<Family> {
+        match self {
+            Netid::Tcp | Netid::Udp => Some(Family::Inet),
+            Netid::Tcp6 | Netid::Udp6 => Some(Family::Inet6),
+            Netid::Local | Netid::Unix => Some(Family::Local),
+            Netid::Other(_) => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Netid::Tcp => "tcp",
+            Netid::Udp => "udp",
+            Netid::Tcp6 => "tcp6",
+            Netid::Udp6 => "udp6",
+            Netid::Local => "local",
+            Netid::Unix => "unix",
+            Netid::Other(netid) => netid.as_str(),
+        }
+    }
+}
+
+impl From<&str> for Netid {
+    fn from(netid: &str) -> Self {
+        match netid {
+            "tcp" => Netid::Tcp,
+            "udp" => Netid::Udp,
+            "tcp6" => Netid::Tcp6,
+            "udp6" => Netid::Udp6,
+            "local" => Netid::Local,
+            "unix" => Netid::Unix,
+            netid => Netid::Other(OtherNetid(netid.to_owned())),
+        }
+    }
+}
+
+impl FromStr for Netid {
+    type Err = Infallible;
+
+    fn from_str(netid: &str) -> Result<Self, Infallible> {
+        Ok(netid.into())
+    }
+}
+
+impl fmt::Display for Netid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Netid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Netid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(String::deserialize(deserializer)?.as_str().into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IPPROTO_TCP, IPPROTO_UDP, Netid};
+
+    #[test]
+    fn names() {
+        for netid in ["tcp", "udp", "tcp6", "udp6", "local", "unix", "ticotsord"] {
+            assert_eq!(Netid::from(netid).to_string(), netid);
+        }
+        assert_eq!(Netid::from("tcp6"), Netid::Tcp6);
+        let Netid::Other(rdma) = Netid::from("rdma") else {
+            panic!("rdma has no variant");
+        };
+        assert_eq!(rdma.as_str(), "rdma");
+    }
+
+    #[test]
+    fn portmapper_protocols() {
+        assert_eq!(Netid::from_protocol(IPPROTO_TCP), Some(Netid::Tcp));
+        assert_eq!(Netid::from_protocol(IPPROTO_UDP), Some(Netid::Udp));
+        // ICMP
+        assert_eq!(Netid::from_protocol(1), None);
+
+        assert_eq!(Netid::Tcp6.protocol(), Some(IPPROTO_TCP));
+        assert_eq!(Netid::Udp6.protocol(), Some(IPPROTO_UDP));
+        assert_eq!(Netid::Udp6.portmapper_protocol(), None);
+        assert_eq!(Netid::Local.protocol(), None);
+        for netid in [Netid::Tcp, Netid::Udp] {
+            assert_eq!(
+                Netid::from_protocol(netid.portmapper_protocol().unwrap()),
+                Some(netid)
+            );
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! Reading replies, for the clients and for decoding a reply into a response.
+
+use onc_rpc::{AcceptedStatus, RejectedReply, ReplyBody};
+
+use crate::client::{ClientError, ClientResult};
+
+/// The result payload of a reply, or why the server did not produce one.
+pub(crate) fn accepted_payload<P: AsRef<[u8]>>(
+    reply: &ReplyBody<impl AsRef<[u8]>, P>,
+) -> ClientResult<&P> {
+    match reply {
+        ReplyBody::Accepted(reply) => match reply.status() {
+            AcceptedStatus::Success(payload) => Ok(payload),
+            AcceptedStatus::ProgramUnavailable => {
+                Err(ClientError::Failed(AcceptedStatus::ProgramUnavailable))
+            }
+            AcceptedStatus::ProgramMismatch { low, high } => {
+                Err(ClientError::Failed(AcceptedStatus::ProgramMismatch {
+                    low: *low,
+                    high: *high,
+                }))
+            }
+            AcceptedStatus::ProcedureUnavailable => {
+                Err(ClientError::Failed(AcceptedStatus::ProcedureUnavailable))
+            }
+            AcceptedStatus::GarbageArgs => Err(ClientError::Failed(AcceptedStatus::GarbageArgs)),
+            AcceptedStatus::SystemError => Err(ClientError::Failed(AcceptedStatus::SystemError)),
+        },
+        ReplyBody::Denied(rejected_reply) => Err(ClientError::Rejected(match rejected_reply {
+            RejectedReply::RpcVersionMismatch { low, high } => RejectedReply::RpcVersionMismatch {
+                low: *low,
+                high: *high,
+            },
+            RejectedReply::AuthError(auth_error) => RejectedReply::AuthError(auth_error.clone()),
+        })),
+    }
+}
@@ -1,15 +1,69 @@
 use facet::Facet;
-use onc_rpc::{AcceptedStatus, CallBody};
+use facet_xdr::{XdrDeserError, XdrSerError};
+use onc_rpc::{AcceptedStatus, CallBody, auth::AuthFlavor};
+use thiserror::Error;
 
+mod arguments;
 mod port_mapper;
 mod rpcbind;
 
+pub use arguments::Limits;
 pub use port_mapper::PortMapperRequest;
 pub use rpcbind::RpcBindRequest;
 
-use crate::RpcBindResult;
+use arguments::{Arguments, Invalid};
 
-#[derive(Debug)]
+use crate::{RpcBindResult, xdr_types::codec::XdrReader};
+
+/// The program number shared by portmapper and rpcbind.
+pub const PROGRAM: u32 = 100000;
+
+/// Why a call could not be decoded into an [`RpcRequest`].
+#[derive(Debug, Error)]
+pub enum RequestError {
+    #[error("program {0} is not rpcbind")]
+    UnknownProgram(u32),
+    #[error("version {0} is not supported, only versions 2 to 4 are")]
+    UnsupportedVersion(u32),
+    #[error("version {version} has no procedure {procedure}")]
+    UnknownProcedure { version: u32, procedure: u32 },
+    #[error("malformed arguments to version {version} procedure {procedure}: {source}")]
+    GarbageArgs {
+        version: u32,
+        procedure: u32,
+        source: XdrDeserError,
+    },
+    #[error(
+        "version {version} procedure {procedure}: {field} of {len} bytes is over the limit of {limit}"
+    )]
+    TooLong {
+        version: u32,
+        procedure: u32,
+        field: &'static str,
+        len: usize,
+        limit: usize,
+    },
+}
+
+impl RequestError {
+    /// The status to reply to the rejected call with.
+    pub fn accepted_status<P: AsRef<[u8]>>(&self) -> AcceptedStatus<P> {
+        match self {
+            Self::UnknownProgram(_) => AcceptedStatus::ProgramUnavailable,
+            Self::UnsupportedVersion(_) => AcceptedStatus::ProgramMismatch { low: 2, high: 4 },
+            Self::UnknownProcedure { .. } => AcceptedStatus::ProcedureUnavailable,
+            Self::GarbageArgs { .. } | Self::TooLong { .. } => AcceptedStatus::GarbageArgs,
+        }
+    }
+}
+
+impl<P: AsRef<[u8]>> From<RequestError> for AcceptedStatus<P> {
+    fn from(error: RequestError) -> Self {
+        error.accepted_status()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum RpcRequest {
     V2(PortMapperRequest),
     // V4 Is backwa5rds compatible with V3 Requests
@@ -18,16 +72,320 @@ pub enum RpcRequest {
 }
 
 impl RpcRequest {
+    /// Decodes a call, within the [default limits](Limits::DEFAULT).
     pub fn from_body(value: &CallBody<impl AsRef<[u8]>, impl AsRef<[u8]>>) -> RpcBindResult<Self> {
+        Self::from_body_with_limits(value, &Limits::DEFAULT)
+    }
+
+    /// Decodes a call, rejecting arguments with fields longer than `limits` allow.
+    pub fn from_body_with_limits(
+        value: &CallBody<impl AsRef<[u8]>, impl AsRef<[u8]>>,
+        limits: &Limits,
+    ) -> RpcBindResult<Self> {
+        if value.program() != PROGRAM {
+            return Err(RequestError::UnknownProgram(value.program()));
+        }
         Ok(match value.program_version() {
-            2 => Self::V2(PortMapperRequest::from_body(value)?),
-            3 => Self::V3(RpcBindRequest::from_body(value)?),
-            4 => Self::V4(RpcBindRequest::from_body(value)?),
-            _ => return Err(AcceptedStatus::ProgramMismatch { low: 2, high: 4 }),
+            2 => Self::V2(PortMapperRequest::from_body(value, limits)?),
+            3 => Self::V3(RpcBindRequest::from_body(value, limits)?),
+            4 => Self::V4(RpcBindRequest::from_body(value, limits)?),
+            version => return Err(RequestError::UnsupportedVersion(version)),
         })
     }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            Self::V2(_) => 2,
+            Self::V3(_) => 3,
+            Self::V4(_) => 4,
+        }
+    }
+
+    pub fn procedure(&self) -> u32 {
+        match self {
+            Self::V2(request) => request.procedure(),
+            Self::V3(request) | Self::V4(request) => request.procedure(),
+        }
+    }
+
+    pub fn encode_payload(&self) -> Result<Vec<u8>, XdrSerError> {
+        match self {
+            Self::V2(request) => request.encode_payload(),
+            Self::V3(request) | Self::V4(request) => request.encode_payload(),
+        }
+    }
+
+    /// The inverse of [`RpcRequest::from_body`], authenticated with `AUTH_NONE`.
+    pub fn to_call_body(&self) -> Result<CallBody<&'static [u8], Vec<u8>>, XdrSerError> {
+        Ok(CallBody::new(
+            PROGRAM,
+            self.version(),
+            self.procedure(),
+            AuthFlavor::AuthNone(None),
+            AuthFlavor::AuthNone(None),
+            self.encode_payload()?,
+        ))
+    }
+}
+
+/// Decodes the arguments of the call in `body`.
+fn decode_payload<T: Arguments>(
+    body: &CallBody<impl AsRef<[u8]>, impl AsRef<[u8]>>,
+    limits: &Limits,
+) -> RpcBindResult<T> {
+    let (version, procedure) = (body.program_version(), body.procedure());
+    T::decode(&mut XdrReader::new(body.payload().as_ref()), limits).map_err(|e| match e {
+        Invalid::Xdr(source) => RequestError::GarbageArgs {
+            version,
+            procedure,
+            source,
+        },
+        Invalid::TooLong { field, len, limit } => RequestError::TooLong {
+            version,
+            procedure,
+            field,
+            len,
+            limit,
+        },
+    })
+}
+
+fn unknown_procedure(body: &CallBody<impl AsRef<[u8]>, impl AsRef<[u8]>>) -> RequestError {
+    RequestError::UnknownProcedure {
+        version: body.program_version(),
+        procedure: body.procedure(),
+    }
 }
 
-fn deserialize_payload<'f, T: Facet<'f>, P: AsRef<[u8]>>(payload: P) -> RpcBindResult<T> {
-    facet_xdr::deserialize(payload.as_ref()).map_err(|_| AcceptedStatus::GarbageArgs)
+fn serialize_payload<'f, T: Facet<'f>>(payload: &'f T) -> Result<Vec<u8>, XdrSerError> {
+    facet_xdr::to_vec(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use onc_rpc::{AcceptedStatus, CallBody, MessageType, RpcMessage, auth::AuthFlavor};
+
+    use super::{Limits, PortMapperRequest, RequestError, RpcBindRequest, RpcRequest};
+    use crate::xdr_types::{
+        port_mapper::{CallArgs, Mapping},
+        rpcbind::{NetBuf, RPCB, RmtCallArgs},
+    };
+
+    fn round_trip(request: RpcRequest) {
+        let body = request.to_call_body().unwrap();
+        assert_eq!(RpcRequest::from_body(&body).unwrap(), request);
+
+        // And again after going over the wire
+        let message = RpcMessage::new(7, MessageType::Call(body))
+            .serialise()
+            .unwrap();
+        let message = RpcMessage::try_from(message.as_slice()).unwrap();
+        let body: &CallBody<_, _> = message.call_body().unwrap();
+        assert_eq!(body.program(), 100000);
+        assert_eq!(RpcRequest::from_body(body).unwrap(), request);
+    }
+
+    fn rpcb() -> RPCB {
+        RPCB {
+            r_prog: 100003,
+            r_vers: 3,
+            r_netid: "tcp6".to_owned(),
+            r_addr: "::1.8.1".to_owned(),
+            r_owner: "superuser".to_owned(),
+        }
+    }
+
+    #[test]
+    fn port_mapper_round_trip() {
+        let mapping = Mapping {
+            prog: 100003,
+            vers: 3,
+            prot: 17,
+            port: 2049,
+        };
+        for request in [
+            PortMapperRequest::Null,
+            PortMapperRequest::Set(mapping.clone()),
+            PortMapperRequest::Unset(mapping.clone()),
+            PortMapperRequest::GetPort(mapping),
+            PortMapperRequest::Dump,
+            PortMapperRequest::CallIt(CallArgs {
+                prog: 100005,
+                vers: 1,
+                proc: 0,
+                args: vec![1, 2, 3],
+            }),
+        ] {
+            round_trip(RpcRequest::V2(request));
+        }
+    }
+
+    #[test]
+    fn rpcbind_round_trip() {
+        for request in [
+            RpcBindRequest::Null,
+            RpcBindRequest::Set(rpcb()),
+            RpcBindRequest::Unset(rpcb()),
+            RpcBindRequest::GetAddr(rpcb()),
+            RpcBindRequest::Dump,
+            RpcBindRequest::Broadcast(RmtCallArgs {
+                prog: 100003,
+                vers: 3,
+                proc: 0,
+                args: Vec::new(),
+            }),
+            RpcBindRequest::GetTime,
+            RpcBindRequest::UADDR2TADDR("127.0.0.1.0.111".to_owned()),
+            RpcBindRequest::TADDR2UADDR(NetBuf {
+                maxlen: 16,
+                buf: vec![0, 2, 0, 111, 127, 0, 0, 1],
+            }),
+            RpcBindRequest::GETVERSADDR(rpcb()),
+            RpcBindRequest::Indirect(RmtCallArgs {
+                prog: 100005,
+                vers: 3,
+                proc: 0,
+                args: Vec::new(),
+            }),
+            RpcBindRequest::GetAddrList(rpcb()),
+            RpcBindRequest::GetStat,
+        ] {
+            if request.procedure() <= RpcBindRequest::LAST_V3_PROCEDURE {
+                round_trip(RpcRequest::V3(request.clone()));
+            }
+            round_trip(RpcRequest::V4(request));
+        }
+    }
+
+    fn decode(program: u32, version: u32, procedure: u32, payload: &[u8]) -> RequestError {
+        let body = CallBody::new(
+            program,
+            version,
+            procedure,
+            AuthFlavor::<&[u8]>::AuthNone(None),
+            AuthFlavor::AuthNone(None),
+            payload,
+        );
+        RpcRequest::from_body(&body).unwrap_err()
+    }
+
+    #[test]
+    fn rejections() {
+        let error = decode(100003, 3, 0, &[]);
+        assert!(matches!(error, RequestError::UnknownProgram(100003)));
+        assert_eq!(
+            error.accepted_status::<[u8; 0]>(),
+            AcceptedStatus::ProgramUnavailable
+        );
+
+        let error = decode(100000, 5, 0, &[]);
+        assert!(matches!(error, RequestError::UnsupportedVersion(5)));
+        assert_eq!(
+            error.accepted_status::<[u8; 0]>(),
+            AcceptedStatus::ProgramMismatch { low: 2, high: 4 }
+        );
+
+        // Procedures only version 4 has
+        for procedure in RpcBindRequest::LAST_V3_PROCEDURE + 1..=12 {
+            let error = decode(100000, 3, procedure, &[]);
+            assert!(matches!(
+                error,
+                RequestError::UnknownProcedure { version: 3, .. }
+            ));
+        }
+
+        let error = decode(100000, 2, 6, &[]);
+        assert!(matches!(
+            error,
+            RequestError::UnknownProcedure {
+                version: 2,
+                procedure: 6
+            }
+        ));
+        assert_eq!(
+            error.accepted_status::<[u8; 0]>(),
+            AcceptedStatus::ProcedureUnavailable
+        );
+
+        // A SET missing most of its RPCB
+        let error = decode(100000, 4, 1, &[0, 1, 134, 163]);
+        assert!(matches!(
+            error,
+            RequestError::GarbageArgs {
+                version: 4,
+                procedure: 1,
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "malformed arguments to version 4 procedure 1: {}",
+                std::error::Error::source(&error).unwrap()
+            )
+        );
+        assert_eq!(
+            AcceptedStatus::<[u8; 0]>::from(error),
+            AcceptedStatus::GarbageArgs
+        );
+
+        // A netid declaring more bytes than follow, which facet-xdr panicked on
+        let error = decode(
+            100000,
+            3,
+            3,
+            &[0, 1, 134, 163, 0, 0, 0, 3, 0, 0, 0, 5, b't', 0, 0, 0],
+        );
+        assert!(matches!(error, RequestError::GarbageArgs { .. }));
+    }
+
+    #[test]
+    fn limits() {
+        let mut set = rpcb();
+        set.r_owner = "x".repeat(1000);
+        let body = RpcRequest::V4(RpcBindRequest::Set(set.clone()))
+            .to_call_body()
+            .unwrap();
+        let error = RpcRequest::from_body(&body).unwrap_err();
+        assert!(matches!(
+            error,
+            RequestError::TooLong {
+                version: 4,
+                procedure: 1,
+                field: "owner",
+                len: 1000,
+                limit: 256,
+            }
+        ));
+        assert_eq!(
+            error.accepted_status::<[u8; 0]>(),
+            AcceptedStatus::GarbageArgs
+        );
+
+        let limits = Limits {
+            owner: 1000,
+            ..Limits::DEFAULT
+        };
+        assert_eq!(
+            RpcRequest::from_body_with_limits(&body, &limits).unwrap(),
+            RpcRequest::V4(RpcBindRequest::Set(set))
+        );
+
+        let limits = Limits {
+            args: 2,
+            ..Limits::DEFAULT
+        };
+        let body = RpcRequest::V2(PortMapperRequest::CallIt(CallArgs {
+            prog: 100005,
+            vers: 1,
+            proc: 0,
+            args: vec![1, 2, 3],
+        }))
+        .to_call_body()
+        .unwrap();
+        assert!(matches!(
+            RpcRequest::from_body_with_limits(&body, &limits),
+            Err(RequestError::TooLong { field: "args", .. })
+        ));
+    }
 }
@@ -0,0 +1,138 @@
+//! Decoding of call arguments with the hand written codec, which unlike facet lets the
+//! length of every string and opaque field be checked before it is copied.
+
+use facet_xdr::XdrDeserError;
+
+use crate::xdr_types::{
+    codec::{XdrBorrow, XdrCodec, XdrReader},
+    port_mapper::{CallArgs, Mapping},
+    rpcbind::{NetBuf, RPCB, RmtCallArgs, RpcbRef},
+};
+
+/// Upper bounds on the variable length fields of call arguments, past which a call is
+/// rejected with `GARBAGE_ARGS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The longest netid, in bytes.
+    pub netid: usize,
+    /// The longest universal address, and transport address as its binary form is never
+    /// longer.
+    pub universal_address: usize,
+    /// The longest owner name.
+    pub owner: usize,
+    /// The most argument bytes a call to forward may carry.
+    pub args: usize,
+}
+
+impl Limits {
+    pub const DEFAULT: Limits = Limits {
+        netid: 64,
+        // Room for a local address, whose socket path may be up to 108 bytes
+        universal_address: 128,
+        owner: 256,
+        // As much as a UDP datagram can carry
+        args: 65535,
+    };
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Why arguments were not decoded.
+pub(super) enum Invalid {
+    Xdr(XdrDeserError),
+    TooLong {
+        field: &'static str,
+        len: usize,
+        limit: usize,
+    },
+}
+
+impl From<XdrDeserError> for Invalid {
+    fn from(error: XdrDeserError) -> Self {
+        Self::Xdr(error)
+    }
+}
+
+fn bounded(field: &'static str, len: usize, limit: usize) -> Result<(), Invalid> {
+    if len > limit {
+        return Err(Invalid::TooLong { field, len, limit });
+    }
+    Ok(())
+}
+
+/// The arguments of a procedure.
+pub(super) trait Arguments: Sized {
+    fn decode(reader: &mut XdrReader, limits: &Limits) -> Result<Self, Invalid>;
+}
+
+impl Arguments for Mapping {
+    fn decode(reader: &mut XdrReader, _limits: &Limits) -> Result<Self, Invalid> {
+        Ok(<Mapping as XdrCodec>::decode(reader)?)
+    }
+}
+
+impl Arguments for RPCB {
+    fn decode(reader: &mut XdrReader, limits: &Limits) -> Result<Self, Invalid> {
+        let rpcb = RpcbRef::decode_borrowed(reader)?;
+        bounded("netid", rpcb.r_netid.len(), limits.netid)?;
+        bounded("address", rpcb.r_addr.len(), limits.universal_address)?;
+        bounded("owner", rpcb.r_owner.len(), limits.owner)?;
+        Ok(rpcb.into_owned())
+    }
+}
+
+impl Arguments for CallArgs {
+    fn decode(reader: &mut XdrReader, limits: &Limits) -> Result<Self, Invalid> {
+        let (prog, vers, proc) = (reader.u32()?, reader.u32()?, reader.u32()?);
+        let args = reader.opaque()?;
+        bounded("args", args.len(), limits.args)?;
+        Ok(Self {
+            prog,
+            vers,
+            proc,
+            args: args.to_vec(),
+        })
+    }
+}
+
+impl Arguments for RmtCallArgs {
+    fn decode(reader: &mut XdrReader, limits: &Limits) -> Result<Self, Invalid> {
+        let CallArgs {
+            prog,
+            vers,
+            proc,
+            args,
+        } = CallArgs::decode(reader, limits)?;
+        Ok(Self {
+            prog,
+            vers,
+            proc,
+            args,
+        })
+    }
+}
+
+/// The universal address given to `RPCBPROC_UADDR2TADDR`.
+impl Arguments for String {
+    fn decode(reader: &mut XdrReader, limits: &Limits) -> Result<Self, Invalid> {
+        let universal_address = reader.str()?;
+        bounded("address", universal_address.len(), limits.universal_address)?;
+        Ok(universal_address.to_owned())
+    }
+}
+
+impl Arguments for NetBuf {
+    fn decode(reader: &mut XdrReader, limits: &Limits) -> Result<Self, Invalid> {
+        let maxlen = reader.u32()?;
+        let buf = reader.opaque()?;
+        bounded("address", buf.len(), limits.universal_address)?;
+        Ok(Self {
+            maxlen,
+            buf: buf.to_vec(),
+        })
+    }
+}
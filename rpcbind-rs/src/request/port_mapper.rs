@@ -1,12 +1,13 @@
-use onc_rpc::{AcceptedStatus, CallBody};
+use facet_xdr::XdrSerError;
+use onc_rpc::CallBody;
 
-use super::deserialize_payload;
+use super::{Limits, decode_payload, serialize_payload, unknown_procedure};
 use crate::{
     RpcBindResult,
     xdr_types::port_mapper::{CallArgs, Mapping},
 };
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum PortMapperRequest {
     Null,
     Set(Mapping),
@@ -17,17 +18,39 @@ pub enum PortMapperRequest {
 }
 
 impl PortMapperRequest {
-    pub fn from_body(value: &CallBody<impl AsRef<[u8]>, impl AsRef<[u8]>>) -> RpcBindResult<Self> {
+    pub fn from_body(
+        value: &CallBody<impl AsRef<[u8]>, impl AsRef<[u8]>>,
+        limits: &Limits,
+    ) -> RpcBindResult<Self> {
         Ok(match value.procedure() {
             0 => Self::Null,
-            1 => Self::Set(deserialize_payload(value.payload())?),
-            2 => Self::Unset(deserialize_payload(value.payload())?),
-            3 => Self::GetPort(deserialize_payload(value.payload())?),
+            1 => Self::Set(decode_payload(value, limits)?),
+            2 => Self::Unset(decode_payload(value, limits)?),
+            3 => Self::GetPort(decode_payload(value, limits)?),
             4 => Self::Dump,
-            5 => Self::CallIt(deserialize_payload(value.payload())?),
-            _ => {
-                return Err(AcceptedStatus::ProcedureUnavailable);
-            }
+            5 => Self::CallIt(decode_payload(value, limits)?),
+            _ => return Err(unknown_procedure(value)),
         })
     }
+
+    pub fn procedure(&self) -> u32 {
+        match self {
+            Self::Null => 0,
+            Self::Set(_) => 1,
+            Self::Unset(_) => 2,
+            Self::GetPort(_) => 3,
+            Self::Dump => 4,
+            Self::CallIt(_) => 5,
+        }
+    }
+
+    pub fn encode_payload(&self) -> Result<Vec<u8>, XdrSerError> {
+        match self {
+            Self::Null | Self::Dump => Ok(Vec::new()),
+            Self::Set(mapping) | Self::Unset(mapping) | Self::GetPort(mapping) => {
+                serialize_payload(mapping)
+            }
+            Self::CallIt(call_args) => serialize_payload(call_args),
+        }
+    }
 }
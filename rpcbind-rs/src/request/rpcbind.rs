@@ -1,17 +1,21 @@
-use onc_rpc::{AcceptedStatus, CallBody};
+use facet_xdr::XdrSerError;
+use onc_rpc::CallBody;
 
-use super::deserialize_payload;
+use super::{Limits, decode_payload, serialize_payload, unknown_procedure};
 use crate::{
     RpcBindResult,
     xdr_types::rpcbind::{NetBuf, RPCB, RmtCallArgs},
 };
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum RpcBindRequest {
+    Null,
     Set(RPCB),
     Unset(RPCB),
     GetAddr(RPCB),
     Dump,
+    /// `RPCBPROC_BCAST`, which is `RPCBPROC_CALLIT` renamed for its use in broadcasts.
+    Broadcast(RmtCallArgs),
     GetTime,
     UADDR2TADDR(String),
     TADDR2UADDR(NetBuf),
@@ -22,36 +26,65 @@ pub enum RpcBindRequest {
 }
 
 impl RpcBindRequest {
-    pub fn from_body(value: &CallBody<impl AsRef<[u8]>, impl AsRef<[u8]>>) -> RpcBindResult<Self> {
+    /// The last procedure of version 3; the ones after it were added in version 4.
+    pub(crate) const LAST_V3_PROCEDURE: u32 = 8;
+
+    pub fn from_body(
+        value: &CallBody<impl AsRef<[u8]>, impl AsRef<[u8]>>,
+        limits: &Limits,
+    ) -> RpcBindResult<Self> {
+        if value.program_version() == 3 && value.procedure() > Self::LAST_V3_PROCEDURE {
+            return Err(unknown_procedure(value));
+        }
         Ok(match value.procedure() {
-            1 => Self::Set(deserialize_payload(value.payload())?),
-            2 => Self::Unset(deserialize_payload(value.payload())?),
-            3 => Self::GetAddr(deserialize_payload(value.payload())?),
+            0 => Self::Null,
+            1 => Self::Set(decode_payload(value, limits)?),
+            2 => Self::Unset(decode_payload(value, limits)?),
+            3 => Self::GetAddr(decode_payload(value, limits)?),
             4 => Self::Dump,
-            /*
-            //RPCBPROC_BCAST
+            5 => Self::Broadcast(decode_payload(value, limits)?),
             6 => Self::GetTime,
-            7 => Self::UADDR2TADDR(str::from_utf8(&payload)?.to_owned()),
-            8 => Self::TADDR2UADDR(NetBuf::try_from(payload)?),
-            9 => Self::GETVERSADDR(RPCB::try_from(payload)?),
-            10 => Self::Indirect(RmtCallArgs::try_from(payload)?),
-            11 => Self::GetAddrList(RPCB::try_from(payload)?),
-            */
+            7 => Self::UADDR2TADDR(decode_payload(value, limits)?),
+            8 => Self::TADDR2UADDR(decode_payload(value, limits)?),
+            9 => Self::GETVERSADDR(decode_payload(value, limits)?),
+            10 => Self::Indirect(decode_payload(value, limits)?),
+            11 => Self::GetAddrList(decode_payload(value, limits)?),
             12 => Self::GetStat,
-            _ => {
-                return Err(AcceptedStatus::ProcedureUnavailable);
-            }
+            _ => return Err(unknown_procedure(value)),
         })
     }
-}
 
-/*
- * NOTE: RPCBPROC_BCAST has the same functionality as CALLIT;
- * the new name is intended to indicate that this
- * procedure should be used for broadcast RPC, and
- * RPCBPROC_INDIRECT should be used for indirect calls.
- */
-/*
-rpcb_rmtcallres
-RPCBPROC_BCAST(rpcb_rmtcallargs) = RPCBPROC_CALLIT;
-*/
+    pub fn procedure(&self) -> u32 {
+        match self {
+            Self::Null => 0,
+            Self::Set(_) => 1,
+            Self::Unset(_) => 2,
+            Self::GetAddr(_) => 3,
+            Self::Dump => 4,
+            Self::Broadcast(_) => 5,
+            Self::GetTime => 6,
+            Self::UADDR2TADDR(_) => 7,
+            Self::TADDR2UADDR(_) => 8,
+            Self::GETVERSADDR(_) => 9,
+            Self::Indirect(_) => 10,
+            Self::GetAddrList(_) => 11,
+            Self::GetStat => 12,
+        }
+    }
+
+    pub fn encode_payload(&self) -> Result<Vec<u8>, XdrSerError> {
+        match self {
+            Self::Null | Self::Dump | Self::GetTime | Self::GetStat => Ok(Vec::new()),
+            Self::Set(rpcb)
+            | Self::Unset(rpcb)
+            | Self::GetAddr(rpcb)
+            | Self::GETVERSADDR(rpcb)
+            | Self::GetAddrList(rpcb) => serialize_payload(rpcb),
+            Self::UADDR2TADDR(universal_address) => serialize_payload(universal_address),
+            Self::TADDR2UADDR(netbuf) => serialize_payload(netbuf),
+            Self::Broadcast(rmtcallargs) | Self::Indirect(rmtcallargs) => {
+                serialize_payload(rmtcallargs)
+            }
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use facet_xdr::XdrDeserError;
+use onc_rpc::ReplyBody;
+
+mod port_mapper;
+mod rpcbind;
+
+pub use port_mapper::PortMapperResponse;
+pub use rpcbind::RpcBindResponse;
+
+use crate::{client::ClientResult, reply::accepted_payload, request::RpcRequest};
+
+/// The result of a successful call, in the version of the [`RpcRequest`] it answers.
+#[derive(Debug, PartialEq, Clone)]
+pub enum RpcResponse {
+    V2(PortMapperResponse),
+    V3(RpcBindResponse),
+    V4(RpcBindResponse),
+}
+
+impl RpcResponse {
+    /// Decodes the result `payload` of `request`, whose procedure decides its type.
+    pub fn decode(request: &RpcRequest, payload: &[u8]) -> Result<Self, XdrDeserError> {
+        Ok(match request {
+            RpcRequest::V2(request) => Self::V2(PortMapperResponse::decode(request, payload)?),
+            RpcRequest::V3(request) => Self::V3(RpcBindResponse::decode(request, payload)?),
+            RpcRequest::V4(request) => Self::V4(RpcBindResponse::decode(request, payload)?),
+        })
+    }
+
+    /// Decodes the reply to `request`, failing if the server did not accept it.
+    pub fn from_reply(
+        request: &RpcRequest,
+        reply: &ReplyBody<impl AsRef<[u8]>, impl AsRef<[u8]>>,
+    ) -> ClientResult<Self> {
+        Ok(Self::decode(request, accepted_payload(reply)?.as_ref())?)
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            Self::V2(_) => 2,
+            Self::V3(_) => 3,
+            Self::V4(_) => 4,
+        }
+    }
+
+    pub fn encode_payload(&self) -> Vec<u8> {
+        match self {
+            Self::V2(response) => response.encode_payload(),
+            Self::V3(response) | Self::V4(response) => response.encode_payload(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use onc_rpc::{AcceptedReply, AcceptedStatus, ReplyBody, auth::AuthFlavor};
+
+    use super::{PortMapperResponse, RpcBindResponse, RpcResponse};
+    use crate::{
+        client::ClientError,
+        request::{PortMapperRequest, RpcBindRequest, RpcRequest},
+        xdr_types::{
+            port_mapper::{CallArgs, CallResult, Mapping, PMapList},
+            rpcbind::{Entry, EntryList, NetBuf, RPCB, RPList, RmtCallArgs, RmtCallRes},
+        },
+    };
+
+    fn reply(status: AcceptedStatus<Vec<u8>>) -> ReplyBody<Vec<u8>, Vec<u8>> {
+        ReplyBody::Accepted(AcceptedReply::new(AuthFlavor::AuthNone(None), status))
+    }
+
+    fn round_trip(request: RpcRequest, response: RpcResponse) {
+        let payload = response.encode_payload();
+        assert_eq!(RpcResponse::decode(&request, &payload).unwrap(), response);
+        let reply = reply(AcceptedStatus::Success(payload));
+        assert_eq!(RpcResponse::from_reply(&request, &reply).unwrap(), response);
+    }
+
+    fn rpcb() -> RPCB {
+        RPCB {
+            r_prog: 100003,
+            r_vers: 3,
+            r_netid: "tcp".to_owned(),
+            r_addr: "127.0.0.1.8.1".to_owned(),
+            r_owner: "superuser".to_owned(),
+        }
+    }
+
+    fn rmtcall_args() -> RmtCallArgs {
+        RmtCallArgs {
+            prog: 100003,
+            vers: 3,
+            proc: 0,
+            args: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn port_mapper_round_trip() {
+        let mapping = Mapping {
+            prog: 100003,
+            vers: 3,
+            prot: 6,
+            port: 2049,
+        };
+        for (request, response) in [
+            (PortMapperRequest::Null, PortMapperResponse::Null),
+            (
+                PortMapperRequest::Set(mapping.clone()),
+                PortMapperResponse::Set(true),
+            ),
+            (
+                PortMapperRequest::Unset(mapping.clone()),
+                PortMapperResponse::Unset(false),
+            ),
+            (
+                PortMapperRequest::GetPort(mapping.clone()),
+                PortMapperResponse::GetPort(2049),
+            ),
+            (
+                PortMapperRequest::Dump,
+                PortMapperResponse::Dump(PMapList::from(vec![mapping])),
+            ),
+            (
+                PortMapperRequest::CallIt(CallArgs {
+                    prog: 100003,
+                    vers: 3,
+                    proc: 0,
+                    args: Vec::new(),
+                }),
+                PortMapperResponse::CallIt(CallResult {
+                    port: 2049,
+                    res: vec![1, 2, 3],
+                }),
+            ),
+        ] {
+            round_trip(RpcRequest::V2(request), RpcResponse::V2(response));
+        }
+    }
+
+    #[test]
+    fn rpcbind_round_trip() {
+        let rmtcall_res = RmtCallRes {
+            addr: "127.0.0.1.8.1".to_owned(),
+            results: vec![0, 0, 0, 1],
+        };
+        for (request, response) in [
+            (RpcBindRequest::Null, RpcBindResponse::Null),
+            (RpcBindRequest::Set(rpcb()), RpcBindResponse::Set(true)),
+            (RpcBindRequest::Unset(rpcb()), RpcBindResponse::Unset(true)),
+            (
+                RpcBindRequest::GetAddr(rpcb()),
+                RpcBindResponse::GetAddr("127.0.0.1.8.1".to_owned()),
+            ),
+            (
+                RpcBindRequest::Dump,
+                RpcBindResponse::Dump(RPList::from(vec![rpcb(), rpcb()])),
+            ),
+            (
+                RpcBindRequest::Broadcast(rmtcall_args()),
+                RpcBindResponse::Broadcast(rmtcall_res.clone()),
+            ),
+            (
+                RpcBindRequest::GetTime,
+                RpcBindResponse::GetTime(1_700_000_000),
+            ),
+            (
+                RpcBindRequest::UADDR2TADDR("127.0.0.1.0.111".to_owned()),
+                RpcBindResponse::UADDR2TADDR(NetBuf {
+                    maxlen: 16,
+                    buf: vec![0, 2, 0, 111, 127, 0, 0, 1],
+                }),
+            ),
+            (
+                RpcBindRequest::TADDR2UADDR(NetBuf {
+                    maxlen: 16,
+                    buf: vec![0, 2, 0, 111, 127, 0, 0, 1],
+                }),
+                RpcBindResponse::TADDR2UADDR("127.0.0.1.0.111".to_owned()),
+            ),
+            (
+                RpcBindRequest::GETVERSADDR(rpcb()),
+                RpcBindResponse::GETVERSADDR(String::new()),
+            ),
+            (
+                RpcBindRequest::Indirect(rmtcall_args()),
+                RpcBindResponse::Indirect(rmtcall_res),
+            ),
+            (
+                RpcBindRequest::GetAddrList(rpcb()),
+                RpcBindResponse::GetAddrList(EntryList::from(vec![Entry {
+                    r_maddr: "127.0.0.1.8.1".to_owned(),
+                    r_nc_netid: "tcp".to_owned(),
+                    r_nc_semantics: 3,
+                    r_nc_protofmly: "inet".to_owned(),
+                    r_nc_proto: "tcp".to_owned(),
+                }])),
+            ),
+        ] {
+            if request.procedure() <= RpcBindRequest::LAST_V3_PROCEDURE {
+                round_trip(
+                    RpcRequest::V3(request.clone()),
+                    RpcResponse::V3(response.clone()),
+                );
+            }
+            round_trip(RpcRequest::V4(request), RpcResponse::V4(response));
+        }
+    }
+
+    #[test]
+    fn failed_replies() {
+        let request = RpcRequest::V4(RpcBindRequest::GetAddr(rpcb()));
+        let error = RpcResponse::from_reply(&request, &reply(AcceptedStatus::ProcedureUnavailable))
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ClientError::Failed(AcceptedStatus::ProcedureUnavailable)
+        ));
+
+        // No address where one was expected
+        let reply = reply(AcceptedStatus::Success(Vec::new()));
+        let error = RpcResponse::from_reply(&request, &reply).unwrap_err();
+        assert!(matches!(error, ClientError::Decode(_)));
+    }
+
+    #[test]
+    fn truncated_replies() {
+        // An address declaring 100 bytes where only 4 were sent
+        let request = RpcRequest::V4(RpcBindRequest::GetAddr(rpcb()));
+        let payload = [0, 0, 0, 100, b'a', 0, 0, 0];
+        assert!(RpcResponse::decode(&request, &payload).is_err());
+
+        let rmtcall_res = RmtCallRes {
+            addr: "127.0.0.1.8.1".to_owned(),
+            results: vec![0, 0, 0, 1],
+        };
+        let call_result = CallResult {
+            port: 2049,
+            res: vec![0, 0, 0, 1],
+        };
+        let responses = [
+            (
+                RpcRequest::V2(PortMapperRequest::CallIt(CallArgs {
+                    prog: 100003,
+                    vers: 3,
+                    proc: 0,
+                    args: Vec::new(),
+                })),
+                RpcResponse::V2(PortMapperResponse::CallIt(call_result)),
+            ),
+            (
+                RpcRequest::V4(RpcBindRequest::Dump),
+                RpcResponse::V4(RpcBindResponse::Dump(RPList::from(vec![rpcb()]))),
+            ),
+            (
+                RpcRequest::V4(RpcBindRequest::Broadcast(rmtcall_args())),
+                RpcResponse::V4(RpcBindResponse::Broadcast(rmtcall_res)),
+            ),
+            (
+                RpcRequest::V4(RpcBindRequest::UADDR2TADDR("127.0.0.1.0.111".to_owned())),
+                RpcResponse::V4(RpcBindResponse::UADDR2TADDR(NetBuf {
+                    maxlen: 16,
+                    buf: vec![0, 2, 0, 111, 127, 0, 0, 1],
+                })),
+            ),
+        ];
+        for (request, response) in responses {
+            let payload = response.encode_payload();
+            for len in 0..payload.len() {
+                assert!(
+                    RpcResponse::decode(&request, &payload[..len]).is_err(),
+                    "{response:?} cut to {len} bytes"
+                );
+            }
+        }
+    }
+}
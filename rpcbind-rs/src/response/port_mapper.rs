@@ -0,0 +1,44 @@
+use facet_xdr::XdrDeserError;
+
+use crate::{
+    request::PortMapperRequest,
+    xdr_types::{
+        codec::XdrCodec,
+        port_mapper::{CallResult, PMapList},
+    },
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PortMapperResponse {
+    Null,
+    Set(bool),
+    Unset(bool),
+    /// The registered port, or 0 if the program is not registered.
+    GetPort(u32),
+    Dump(PMapList),
+    CallIt(CallResult),
+}
+
+impl PortMapperResponse {
+    /// Decodes the result of `request`.
+    pub fn decode(request: &PortMapperRequest, payload: &[u8]) -> Result<Self, XdrDeserError> {
+        Ok(match request {
+            PortMapperRequest::Null => Self::Null,
+            PortMapperRequest::Set(_) => Self::Set(bool::from_xdr(payload)?),
+            PortMapperRequest::Unset(_) => Self::Unset(bool::from_xdr(payload)?),
+            PortMapperRequest::GetPort(_) => Self::GetPort(u32::from_xdr(payload)?),
+            PortMapperRequest::Dump => Self::Dump(PMapList::from_xdr(payload)?),
+            PortMapperRequest::CallIt(_) => Self::CallIt(CallResult::from_xdr(payload)?),
+        })
+    }
+
+    pub fn encode_payload(&self) -> Vec<u8> {
+        match self {
+            Self::Null => Vec::new(),
+            Self::Set(success) | Self::Unset(success) => success.to_xdr(),
+            Self::GetPort(port) => port.to_xdr(),
+            Self::Dump(mappings) => mappings.to_xdr(),
+            Self::CallIt(call_result) => call_result.to_xdr(),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use facet_xdr::XdrDeserError;
+
+use crate::{
+    request::RpcBindRequest,
+    xdr_types::{
+        codec::XdrCodec,
+        rpcbind::{EntryList, NetBuf, RPList, RmtCallRes, StatByVers},
+    },
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum RpcBindResponse {
+    Null,
+    Set(bool),
+    Unset(bool),
+    /// The registered universal address, or empty if the program is not registered.
+    GetAddr(String),
+    Dump(RPList),
+    Broadcast(RmtCallRes),
+    /// Seconds since the Unix epoch.
+    GetTime(u32),
+    UADDR2TADDR(NetBuf),
+    TADDR2UADDR(String),
+    GETVERSADDR(String),
+    Indirect(RmtCallRes),
+    GetAddrList(EntryList),
+    GetStat(Box<StatByVers>),
+}
+
+impl RpcBindResponse {
+    /// Decodes the result of `request`.
+    pub fn decode(request: &RpcBindRequest, payload: &[u8]) -> Result<Self, XdrDeserError> {
+        Ok(match request {
+            RpcBindRequest::Null => Self::Null,
+            RpcBindRequest::Set(_) => Self::Set(bool::from_xdr(payload)?),
+            RpcBindRequest::Unset(_) => Self::Unset(bool::from_xdr(payload)?),
+            RpcBindRequest::GetAddr(_) => Self::GetAddr(String::from_xdr(payload)?),
+            RpcBindRequest::Dump => Self::Dump(RPList::from_xdr(payload)?),
+            RpcBindRequest::Broadcast(_) => Self::Broadcast(RmtCallRes::from_xdr(payload)?),
+            RpcBindRequest::GetTime => Self::GetTime(u32::from_xdr(payload)?),
+            RpcBindRequest::UADDR2TADDR(_) => Self::UADDR2TADDR(NetBuf::from_xdr(payload)?),
+            RpcBindRequest::TADDR2UADDR(_) => Self::TADDR2UADDR(String::from_xdr(payload)?),
+            RpcBindRequest::GETVERSADDR(_) => Self::GETVERSADDR(String::from_xdr(payload)?),
+            RpcBindRequest::Indirect(_) => Self::Indirect(RmtCallRes::from_xdr(payload)?),
+            RpcBindRequest::GetAddrList(_) => Self::GetAddrList(EntryList::from_xdr(payload)?),
+            RpcBindRequest::GetStat => Self::GetStat(Box::new(StatByVers::from_xdr(payload)?)),
+        })
+    }
+
+    pub fn encode_payload(&self) -> Vec<u8> {
+        match self {
+            Self::Null => Vec::new(),
+            Self::Set(success) | Self::Unset(success) => success.to_xdr(),
+            Self::GetAddr(universal_address)
+            | Self::TADDR2UADDR(universal_address)
+            | Self::GETVERSADDR(universal_address) => universal_address.to_xdr(),
+            Self::Dump(rpcbs) => rpcbs.to_xdr(),
+            Self::Broadcast(rmtcallres) | Self::Indirect(rmtcallres) => rmtcallres.to_xdr(),
+            Self::GetTime(time) => time.to_xdr(),
+            Self::UADDR2TADDR(netbuf) => netbuf.to_xdr(),
+            Self::GetAddrList(entries) => entries.to_xdr(),
+            Self::GetStat(stats) => stats.to_xdr(),
+        }
+    }
+}
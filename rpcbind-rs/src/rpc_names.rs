@@ -0,0 +1,120 @@
+//! The program name database in `/etc/rpc`, which maps names like `nfs` to program numbers.
+
+use std::{fs, io, path::Path};
+
+pub const DEFAULT_PATH: &str = "/etc/rpc";
+
+/// A line of the database: the official name, the program number and any aliases.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcEntry {
+    pub name: String,
+    pub number: u32,
+    pub aliases: Vec<String>,
+}
+
+impl RpcEntry {
+    /// Whether `name` is the official name or one of the aliases.
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|alias| alias == name)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RpcNames {
+    entries: Vec<RpcEntry>,
+}
+
+impl RpcNames {
+    /// Reads the database from [`DEFAULT_PATH`].
+    pub fn load() -> io::Result<Self> {
+        Self::from_path(DEFAULT_PATH)
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Parses the `name number aliases...` lines of the database.
+    ///
+    /// Comments start with `#`. Like libc's `getrpcent`, lines that do not have a name and a
+    /// valid number are skipped rather than failing the whole file.
+    pub fn parse(content: &str) -> Self {
+        let entries = content
+            .lines()
+            .filter_map(|line| {
+                let line = line.split('#').next().unwrap_or_default();
+                let mut fields = line.split_whitespace();
+                Some(RpcEntry {
+                    name: fields.next()?.to_owned(),
+                    number: fields.next()?.parse().ok()?,
+                    aliases: fields.map(str::to_owned).collect(),
+                })
+            })
+            .collect();
+        Self { entries }
+    }
+
+    pub fn entries(&self) -> &[RpcEntry] {
+        &self.entries
+    }
+
+    /// The first entry for `number`, as `getrpcbynumber` returns.
+    pub fn by_number(&self, number: u32) -> Option<&RpcEntry> {
+        self.entries.iter().find(|entry| entry.number == number)
+    }
+
+    /// The first entry with `name` as its name or an alias, as `getrpcbyname` returns.
+    pub fn by_name(&self, name: &str) -> Option<&RpcEntry> {
+        self.entries.iter().find(|entry| entry.is_named(name))
+    }
+
+    pub fn name(&self, number: u32) -> Option<&str> {
+        self.by_number(number).map(|entry| entry.name.as_str())
+    }
+
+    pub fn number(&self, name: &str) -> Option<u32> {
+        self.by_name(name).map(|entry| entry.number)
+    }
+
+    /// Resolves a program given either by number or by name, the way rpcinfo reads its
+    /// arguments.
+    pub fn resolve(&self, program: &str) -> Option<u32> {
+        program.parse().ok().or_else(|| self.number(program))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RpcNames;
+
+    const DATABASE: &str = "\
+# This file contains user readable names that can be used in place of rpc
+# program numbers.
+
+portmapper	100000	portmap sunrpc rpcbind
+nfs		100003	nfsprog
+mountd		100005	mount showmount # trailing comment
+broken		not-a-number
+ypbind		100007
+nfs_alias	100003
+";
+
+    #[test]
+    fn lookups() {
+        let names = RpcNames::parse(DATABASE);
+        assert_eq!(names.entries().len(), 5);
+        assert_eq!(names.name(100000), Some("portmapper"));
+        assert_eq!(names.name(100003), Some("nfs"));
+        assert_eq!(names.name(100004), None);
+        assert_eq!(names.number("rpcbind"), Some(100000));
+        assert_eq!(names.number("nfs_alias"), Some(100003));
+        assert_eq!(names.number("broken"), None);
+        assert_eq!(
+            names.by_name("showmount").unwrap().aliases,
+            ["mount", "showmount"]
+        );
+        assert_eq!(names.resolve("100024"), Some(100024));
+        assert_eq!(names.resolve("mount"), Some(100005));
+        assert_eq!(names.resolve("status"), None);
+    }
+}
@@ -0,0 +1,378 @@
+//! Universal addresses, the transport independent text form rpcbind stores addresses in.
+//!
+//! See [RFC 5665](https://datatracker.ietf.org/doc/html/rfc5665#section-5.2.3).
+
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::{netconfig::NetConfig, netid::Netid};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum UniversalAddressError {
+    #[error("{0:?} is not a universal address")]
+    Malformed(String),
+    #[error("{address:?} is not a {family} address")]
+    WrongFamily { address: String, family: Family },
+    #[error("netid {0} has no address family")]
+    UnknownNetid(String),
+}
+
+/// The kinds of address a netconfig protocol family can have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    Inet,
+    Inet6,
+    /// A unix socket, for the `loopback` protocol family.
+    Local,
+}
+
+impl Family {
+    /// The family of a netconfig `protofmly`, if it is one with universal addresses.
+    pub fn from_protofmly(protofmly: &str) -> Option<Self> {
+        Some(match protofmly {
+            "inet" => Family::Inet,
+            "inet6" => Family::Inet6,
+            "loopback" => Family::Local,
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Family {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Family::Inet => "inet",
+            Family::Inet6 => "inet6",
+            Family::Local => "loopback",
+        })
+    }
+}
+
+/// An address as rpcbind sends it in `r_addr`.
+///
+/// IP addresses end with the two bytes of the port, like `192.0.2.1.8.1` for port 2049, and
+/// local addresses are the socket path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UniversalAddress {
+    Inet(SocketAddrV4),
+    /// An IPv6 address, whose scope id is written as a numeric zone like `fe80::1%2.8.1`.
+    Inet6(SocketAddrV6),
+    Local(PathBuf),
+}
+
+impl UniversalAddress {
+    /// Parses `address` as registered for `netid`, checking it belongs to the netid's family.
+    ///
+    /// The family is taken from `config`, falling back to the family of well-known netids.
+    pub fn for_netid(
+        config: &NetConfig,
+        netid: &Netid,
+        address: &str,
+    ) -> Result<Self, UniversalAddressError> {
+        let family = match config.get(netid.as_str()) {
+            Some(entry) => Family::from_protofmly(&entry.protofmly),
+            None => netid.family(),
+        }
+        .ok_or_else(|| UniversalAddressError::UnknownNetid(netid.to_string()))?;
+        Self::parse_family(family, address)
+    }
+
+    /// Parses `address`, which must be of `family`.
+    pub fn parse_family(family: Family, address: &str) -> Result<Self, UniversalAddressError> {
+        let parsed: Self = address.parse()?;
+        if parsed.family() != family {
+            return Err(UniversalAddressError::WrongFamily {
+                address: address.to_owned(),
+                family,
+            });
+        }
+        Ok(parsed)
+    }
+
+    pub fn family(&self) -> Family {
+        match self {
+            UniversalAddress::Inet(_) => Family::Inet,
+            UniversalAddress::Inet6(_) => Family::Inet6,
+            UniversalAddress::Local(_) => Family::Local,
+        }
+    }
+
+    /// The port of an IP address.
+    pub fn port(&self) -> Option<u16> {
+        self.socket_addr().map(|addr| addr.port())
+    }
+
+    /// The IP address and port, or `None` for a local address.
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        match self {
+            UniversalAddress::Inet(addr) => Some((*addr).into()),
+            UniversalAddress::Inet6(addr) => Some((*addr).into()),
+            UniversalAddress::Local(_) => None,
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            UniversalAddress::Local(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl From<SocketAddr> for UniversalAddress {
+    fn from(addr: SocketAddr) -> Self {
+        match addr {
+            // The flow label is not part of the address
+            SocketAddr::V6(addr) => UniversalAddress::Inet6(SocketAddrV6::new(
+                *addr.ip(),
+                addr.port(),
+                0,
+                addr.scope_id(),
+            )),
+            SocketAddr::V4(addr) => UniversalAddress::Inet(addr),
+        }
+    }
+}
+
+impl From<SocketAddrV4> for UniversalAddress {
+    fn from(addr: SocketAddrV4) -> Self {
+        SocketAddr::V4(addr).into()
+    }
+}
+
+impl From<SocketAddrV6> for UniversalAddress {
+    fn from(addr: SocketAddrV6) -> Self {
+        SocketAddr::V6(addr).into()
+    }
+}
+
+impl TryFrom<UniversalAddress> for SocketAddr {
+    type Error = UniversalAddressError;
+
+    fn try_from(address: UniversalAddress) -> Result<Self, UniversalAddressError> {
+        address
+            .socket_addr()
+            .ok_or_else(|| UniversalAddressError::WrongFamily {
+                address: address.to_string(),
+                family: Family::Inet,
+            })
+    }
+}
+
+impl FromStr for UniversalAddress {
+    type Err = UniversalAddressError;
+
+    /// Parses an address of any family, which its form tells apart.
+    fn from_str(address: &str) -> Result<Self, UniversalAddressError> {
+        let malformed = || UniversalAddressError::Malformed(address.to_owned());
+        if address.starts_with('/') {
+            return Ok(UniversalAddress::Local(address.into()));
+        }
+
+        let mut parts = address.rsplitn(3, '.');
+        let mut port_byte = || -> Result<u8, UniversalAddressError> {
+            let part = parts.next().ok_or_else(malformed)?;
+            // Leading signs and whitespace are accepted by `parse` but are not in the grammar
+            if part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(malformed());
+            }
+            part.parse().map_err(|_| malformed())
+        };
+        let low = port_byte()?;
+        let high = port_byte()?;
+        let port = u16::from_be_bytes([high, low]);
+        let host = parts.next().ok_or_else(malformed)?;
+
+        if !host.contains(':') {
+            let ip: Ipv4Addr = host.parse().map_err(|_| malformed())?;
+            return Ok(UniversalAddress::Inet(SocketAddrV4::new(ip, port)));
+        }
+        let (ip, scope_id) = match host.split_once('%') {
+            Some((ip, zone)) => {
+                if zone.is_empty() || !zone.bytes().all(|byte| byte.is_ascii_digit()) {
+                    return Err(malformed());
+                }
+                (ip, zone.parse().map_err(|_| malformed())?)
+            }
+            None => (host, 0),
+        };
+        let ip: Ipv6Addr = ip.parse().map_err(|_| malformed())?;
+        Ok(UniversalAddress::Inet6(SocketAddrV6::new(
+            ip, port, 0, scope_id,
+        )))
+    }
+}
+
+impl fmt::Display for UniversalAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let port = |port: u16| {
+            let [high, low] = port.to_be_bytes();
+            format!("{high}.{low}")
+        };
+        match self {
+            UniversalAddress::Inet(addr) => write!(f, "{}.{}", addr.ip(), port(addr.port())),
+            UniversalAddress::Inet6(addr) if addr.scope_id() != 0 => {
+                write!(f, "{}%{}.{}", addr.ip(), addr.scope_id(), port(addr.port()))
+            }
+            UniversalAddress::Inet6(addr) => write!(f, "{}.{}", addr.ip(), port(addr.port())),
+            UniversalAddress::Local(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for UniversalAddress {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for UniversalAddress {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let address = String::deserialize(deserializer)?;
+        address.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+    use proptest::prelude::*;
+
+    use super::{Family, UniversalAddress, UniversalAddressError};
+    use crate::{netconfig::NetConfig, netid::Netid};
+
+    fn round_trip(address: &UniversalAddress) -> UniversalAddress {
+        address.to_string().parse().unwrap()
+    }
+
+    #[test]
+    fn formats() {
+        let cases = [
+            (
+                UniversalAddress::from(SocketAddr::from(([192, 0, 2, 1], 2049))),
+                "192.0.2.1.8.1",
+            ),
+            (
+                SocketAddr::from((Ipv6Addr::UNSPECIFIED, 111)).into(),
+                "::.0.111",
+            ),
+            (
+                SocketAddrV6::new("fe80::1".parse().unwrap(), 256, 0, 3).into(),
+                "fe80::1%3.1.0",
+            ),
+            (
+                UniversalAddress::Local("/var/run/rpcbind.sock".into()),
+                "/var/run/rpcbind.sock",
+            ),
+        ];
+        for (address, text) in cases {
+            assert_eq!(address.to_string(), text);
+            assert_eq!(text.parse::<UniversalAddress>().unwrap(), address);
+        }
+    }
+
+    #[test]
+    fn every_port() {
+        for port in 0..=u16::MAX {
+            let address = UniversalAddress::from(SocketAddrV4::new(Ipv4Addr::LOCALHOST, port));
+            assert_eq!(round_trip(&address).port(), Some(port));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        for text in [
+            "",
+            "1.2.3.4",
+            "1.2.3.4.5",
+            "1.2.3.4.256.1",
+            "1.2.3.4.+1.1",
+            "1.2.3.4. 1.1",
+            "1.2.3.4..1",
+            "1.2.3.4.5.1.1",
+            "::1.1",
+            "fe80::1%.0.1",
+            "fe80::1%eth0.0.1",
+            "relative/path",
+        ] {
+            assert_eq!(
+                text.parse::<UniversalAddress>(),
+                Err(UniversalAddressError::Malformed(text.to_owned())),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn keyed_by_netid() {
+        let config = NetConfig::defaults();
+        assert_eq!(
+            UniversalAddress::for_netid(&config, &Netid::Tcp6, "::1.0.111")
+                .unwrap()
+                .family(),
+            Family::Inet6
+        );
+        assert!(matches!(
+            UniversalAddress::for_netid(&config, &Netid::Tcp6, "127.0.0.1.0.111"),
+            Err(UniversalAddressError::WrongFamily {
+                family: Family::Inet6,
+                ..
+            })
+        ));
+        assert!(UniversalAddress::for_netid(&config, &Netid::Local, "/run/rpcbind.sock").is_ok());
+        // Well-known netids need no netconfig entry
+        assert!(
+            UniversalAddress::for_netid(
+                &NetConfig::parse("").unwrap(),
+                &Netid::Udp,
+                "127.0.0.1.0.1"
+            )
+            .is_ok()
+        );
+        assert_eq!(
+            UniversalAddress::for_netid(&config, &"bogus".into(), "127.0.0.1.0.1"),
+            Err(UniversalAddressError::UnknownNetid("bogus".to_owned()))
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn inet_round_trips(ip: [u8; 4], port: u16) {
+            let addr = SocketAddr::from((ip, port));
+            let address = UniversalAddress::from(addr);
+            prop_assert_eq!(round_trip(&address).socket_addr(), Some(addr));
+        }
+
+        #[test]
+        fn inet6_round_trips(ip: [u8; 16], port: u16, flow_info: u32, scope_id: u32) {
+            let addr = SocketAddrV6::new(Ipv6Addr::from(ip), port, flow_info, scope_id);
+            let address = UniversalAddress::from(addr);
+            let expected = SocketAddrV6::new(Ipv6Addr::from(ip), port, 0, scope_id);
+            prop_assert_eq!(round_trip(&address).socket_addr(), Some(expected.into()));
+        }
+
+        #[test]
+        fn local_round_trips(path in "/[^\0]*") {
+            let address = UniversalAddress::Local(path.clone().into());
+            prop_assert_eq!(address.to_string(), path);
+            prop_assert_eq!(round_trip(&address), address);
+        }
+
+        #[test]
+        fn parsing_is_canonical(text in "[0-9a-f:.%/]{0,48}") {
+            // Whatever parses formats back to text that parses to the same address
+            if let Ok(address) = text.parse::<UniversalAddress>() {
+                prop_assert_eq!(round_trip(&address), address);
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! A hand written XDR codec for the types facet cannot describe, like fixed size arrays and
+//! linked lists.
+
+use facet_xdr::XdrDeserError;
+
+/// A type with an XDR encoding.
+pub trait XdrCodec: Sized {
+    fn encode(&self, writer: &mut XdrWriter);
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError>;
+
+    fn to_xdr(&self) -> Vec<u8> {
+        let mut writer = XdrWriter::new();
+        self.encode(&mut writer);
+        writer.into_bytes()
+    }
+
+    fn from_xdr(input: &[u8]) -> Result<Self, XdrDeserError> {
+        Self::decode(&mut XdrReader::new(input))
+    }
+}
+
+/// A type decoded in place, borrowing its strings and opaque data from the input rather than
+/// copying them.
+pub trait XdrBorrow<'a>: Sized {
+    fn decode_borrowed(reader: &mut XdrReader<'a>) -> Result<Self, XdrDeserError>;
+
+    fn from_xdr_borrowed(input: &'a [u8]) -> Result<Self, XdrDeserError> {
+        Self::decode_borrowed(&mut XdrReader::new(input))
+    }
+}
+
+/// A cursor over XDR encoded input.
+pub struct XdrReader<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> XdrReader<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], XdrDeserError> {
+        let bytes = self
+            .input
+            .get(self.pos..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or(XdrDeserError::UnexpectedEof)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, XdrDeserError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, XdrDeserError> {
+        Ok(self.u32()? as i32)
+    }
+
+    pub fn bool(&mut self) -> Result<bool, XdrDeserError> {
+        match self.u32()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(XdrDeserError::InvalidBoolean {
+                position: self.pos - 4,
+            }),
+        }
+    }
+
+    /// Reads variable length opaque data.
+    pub fn opaque(&mut self) -> Result<&'a [u8], XdrDeserError> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        // Skip the padding to the next 4 byte boundary
+        self.take(padding(len))?;
+        Ok(bytes)
+    }
+
+    /// Reads a string without copying it out of the input.
+    pub fn str(&mut self) -> Result<&'a str, XdrDeserError> {
+        let position = self.pos + 4;
+        let bytes = self.opaque()?;
+        str::from_utf8(bytes).map_err(|source| XdrDeserError::InvalidString { position, source })
+    }
+
+    pub fn string(&mut self) -> Result<String, XdrDeserError> {
+        Ok(self.str()?.to_owned())
+    }
+}
+
+/// Accumulates XDR encoded output.
+#[derive(Debug, Default)]
+pub struct XdrWriter {
+    output: Vec<u8>,
+}
+
+impl XdrWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.output
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.output.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn i32(&mut self, value: i32) {
+        self.u32(value as u32);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u32(value.into());
+    }
+
+    /// Writes variable length opaque data.
+    ///
+    /// # Panics
+    ///
+    /// If `bytes` is longer than an XDR length can describe.
+    pub fn opaque(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len().try_into().expect("XDR data is at most 4GiB"));
+        self.output.extend_from_slice(bytes);
+        self.output
+            .resize(self.output.len() + padding(bytes.len()), 0);
+    }
+
+    pub fn string(&mut self, value: &str) {
+        self.opaque(value.as_bytes());
+    }
+
+    /// Writes `values` as an XDR linked list as they are produced, so a long list never has
+    /// to be collected into an [`XdrList`](super::XdrList) first.
+    pub fn list<T: XdrCodec>(&mut self, values: impl IntoIterator<Item = T>) {
+        for value in values {
+            self.list_entry(&value);
+        }
+        self.end_list();
+    }
+
+    /// Writes one entry of an XDR linked list, for entries that cannot come from an iterator.
+    /// The list is ended by [`XdrWriter::end_list`].
+    pub fn list_entry<T: XdrCodec>(&mut self, value: &T) {
+        self.bool(true);
+        value.encode(self);
+    }
+
+    pub fn end_list(&mut self) {
+        self.bool(false);
+    }
+}
+
+fn padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+impl XdrCodec for u32 {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.u32(*self);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        reader.u32()
+    }
+}
+
+impl XdrCodec for bool {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.bool(*self);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        reader.bool()
+    }
+}
+
+impl XdrCodec for i32 {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.i32(*self);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        reader.i32()
+    }
+}
+
+impl XdrCodec for String {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.string(self);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        reader.string()
+    }
+}
@@ -0,0 +1,32 @@
+//! The RFC 1833 wire layouts written out by hand, to check both codecs against something
+//! that shares no code with them.
+
+/// An unsigned or signed integer, four bytes big endian.
+pub fn word(value: impl Into<i64>) -> Vec<u8> {
+    (value.into() as u32).to_be_bytes().to_vec()
+}
+
+/// Variable length opaque data: the length, the bytes, then zeros to a multiple of four.
+pub fn opaque(bytes: &[u8]) -> Vec<u8> {
+    let mut out = word(bytes.len() as u32);
+    out.extend_from_slice(bytes);
+    while !out.len().is_multiple_of(4) {
+        out.push(0);
+    }
+    out
+}
+
+pub fn string(value: &str) -> Vec<u8> {
+    opaque(value.as_bytes())
+}
+
+/// A linked list: TRUE before each element and FALSE after the last.
+pub fn list(elements: impl IntoIterator<Item = Vec<u8>>) -> Vec<u8> {
+    let mut out = Vec::new();
+    for element in elements {
+        out.extend(word(1));
+        out.extend(element);
+    }
+    out.extend(word(0));
+    out
+}
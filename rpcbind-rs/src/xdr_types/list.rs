@@ -0,0 +1,125 @@
+use std::ops::Deref;
+
+use facet_xdr::XdrDeserError;
+
+use super::codec::{XdrCodec, XdrReader, XdrWriter};
+
+/// An XDR linked list, the `struct node { T value; node *next; }` chains rpcbind uses for
+/// every list it sends.
+///
+/// On the wire each element is preceded by a TRUE optional pointer and the list ends with
+/// FALSE, so an empty list is a single FALSE. The chain is encoded and decoded in a loop
+/// rather than by recursion, so long lists cannot overflow the stack.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct XdrList<T>(Vec<T>);
+
+impl<T> XdrList<T> {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.0.push(value);
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T> Default for XdrList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Deref for XdrList<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<T> From<Vec<T>> for XdrList<T> {
+    fn from(values: Vec<T>) -> Self {
+        Self(values)
+    }
+}
+
+impl<T> FromIterator<T> for XdrList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(values: I) -> Self {
+        Self(values.into_iter().collect())
+    }
+}
+
+impl<T> IntoIterator for XdrList<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a XdrList<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<T: XdrCodec> XdrCodec for XdrList<T> {
+    fn encode(&self, writer: &mut XdrWriter) {
+        for value in &self.0 {
+            writer.bool(true);
+            value.encode(writer);
+        }
+        writer.bool(false);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        let mut values = Vec::new();
+        while reader.bool()? {
+            values.push(T::decode(reader)?);
+        }
+        Ok(Self(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::XdrList;
+    use crate::xdr_types::codec::{XdrCodec, XdrWriter};
+
+    #[test]
+    fn encoding() {
+        assert_eq!(XdrList::<u32>::new().to_xdr(), [0, 0, 0, 0]);
+        let list: XdrList<u32> = [7, 8].into_iter().collect();
+        let bytes = list.to_xdr();
+        assert_eq!(
+            bytes,
+            [0, 0, 0, 1, 0, 0, 0, 7, 0, 0, 0, 1, 0, 0, 0, 8, 0, 0, 0, 0]
+        );
+        assert_eq!(XdrList::from_xdr(&bytes).unwrap(), list);
+        assert!(XdrList::<u32>::from_xdr(&bytes[..16]).is_err());
+
+        let mut writer = XdrWriter::new();
+        writer.list([7u32, 8]);
+        assert_eq!(writer.into_bytes(), bytes);
+    }
+
+    #[test]
+    fn long_lists_do_not_recurse() {
+        let list: XdrList<String> = (0..1_000_000).map(|_| String::new()).collect();
+        let decoded = XdrList::<String>::from_xdr(&list.to_xdr()).unwrap();
+        assert_eq!(decoded.len(), 1_000_000);
+    }
+}
@@ -1,4 +1,13 @@
-#[derive(Debug, PartialEq, facet::Facet)]
+use facet_xdr::XdrDeserError;
+
+use super::{
+    XdrList,
+    codec::{XdrCodec, XdrReader, XdrWriter},
+};
+use crate::netid::Netid;
+
+#[derive(Debug, PartialEq, Clone, facet::Facet)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mapping {
     pub prog: u32,
     pub vers: u32,
@@ -6,13 +15,36 @@ pub struct Mapping {
     pub port: u32,
 }
 
-#[derive(Debug, PartialEq, facet::Facet)]
-pub struct PMapList {
-    pub map: Mapping,
-    pub next: Vec<Box<PMapList>>,
+impl Mapping {
+    /// The netid the mapping is registered under, if its protocol is TCP or UDP.
+    pub fn netid(&self) -> Option<Netid> {
+        Netid::from_protocol(self.prot)
+    }
+}
+
+impl XdrCodec for Mapping {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.u32(self.prog);
+        writer.u32(self.vers);
+        writer.u32(self.prot);
+        writer.u32(self.port);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        Ok(Self {
+            prog: reader.u32()?,
+            vers: reader.u32()?,
+            prot: reader.u32()?,
+            port: reader.u32()?,
+        })
+    }
 }
 
-#[derive(Debug, PartialEq, facet::Facet)]
+/// The reply to `PMAPPROC_DUMP`.
+pub type PMapList = XdrList<Mapping>;
+
+#[derive(Debug, PartialEq, Clone, facet::Facet)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CallArgs {
     pub prog: u32,
     pub vers: u32,
@@ -20,8 +52,96 @@ pub struct CallArgs {
     pub args: Vec<u8>,
 }
 
-#[derive(Debug, PartialEq, facet::Facet)]
+#[derive(Debug, PartialEq, Clone, facet::Facet)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CallResult {
     pub port: u32,
     pub res: Vec<u8>,
 }
+
+impl XdrCodec for CallResult {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.u32(self.port);
+        writer.opaque(&self.res);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        Ok(Self {
+            port: reader.u32()?,
+            res: reader.opaque()?.to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::{CallArgs, CallResult, Mapping, PMapList};
+    use crate::xdr_types::{
+        codec::XdrCodec,
+        layout::{list, opaque, word},
+    };
+
+    fn mapping() -> impl Strategy<Value = Mapping> {
+        any::<[u32; 4]>().prop_map(|[prog, vers, prot, port]| Mapping {
+            prog,
+            vers,
+            prot,
+            port,
+        })
+    }
+
+    fn mapping_layout(mapping: &Mapping) -> Vec<u8> {
+        [mapping.prog, mapping.vers, mapping.prot, mapping.port]
+            .into_iter()
+            .flat_map(word)
+            .collect()
+    }
+
+    #[test]
+    fn empty_list_is_false() {
+        assert_eq!(PMapList::new().to_xdr(), [0, 0, 0, 0]);
+        assert_eq!(PMapList::from_xdr(&[0, 0, 0, 0]).unwrap(), PMapList::new());
+    }
+
+    proptest! {
+        #[test]
+        fn mapping_layouts(mapping in mapping()) {
+            let bytes = mapping_layout(&mapping);
+            prop_assert_eq!(mapping.to_xdr(), bytes.clone());
+            prop_assert_eq!(facet_xdr::to_vec(&mapping).unwrap(), bytes.clone());
+            prop_assert_eq!(Mapping::from_xdr(&bytes).unwrap(), mapping.clone());
+            prop_assert_eq!(facet_xdr::deserialize::<Mapping>(&bytes).unwrap(), mapping);
+        }
+
+        #[test]
+        fn pmaplist_layouts(mappings in vec(mapping(), 0..8)) {
+            let bytes = list(mappings.iter().map(mapping_layout));
+            let mappings = PMapList::from(mappings);
+            prop_assert_eq!(mappings.to_xdr(), bytes.clone());
+            prop_assert_eq!(PMapList::from_xdr(&bytes).unwrap(), mappings);
+        }
+
+        #[test]
+        fn call_args_layouts(header: [u32; 3], args in vec(any::<u8>(), 0..64)) {
+            let [prog, vers, proc] = header;
+            let call_args = CallArgs { prog, vers, proc, args };
+            let mut bytes: Vec<u8> = header.into_iter().flat_map(word).collect();
+            bytes.extend(opaque(&call_args.args));
+            prop_assert_eq!(facet_xdr::to_vec(&call_args).unwrap(), bytes.clone());
+            prop_assert_eq!(facet_xdr::deserialize::<CallArgs>(&bytes).unwrap(), call_args);
+        }
+
+        #[test]
+        fn call_result_layouts(port: u32, res in vec(any::<u8>(), 0..64)) {
+            let call_result = CallResult { port, res };
+            let mut bytes = word(port);
+            bytes.extend(opaque(&call_result.res));
+            prop_assert_eq!(facet_xdr::to_vec(&call_result).unwrap(), bytes.clone());
+            prop_assert_eq!(call_result.to_xdr(), bytes.clone());
+            prop_assert_eq!(facet_xdr::deserialize::<CallResult>(&bytes).unwrap(), call_result.clone());
+            prop_assert_eq!(CallResult::from_xdr(&bytes).unwrap(), call_result);
+        }
+    }
+}
@@ -1,3 +1,11 @@
+use facet_xdr::XdrDeserError;
+
+use super::{
+    XdrList,
+    codec::{XdrBorrow, XdrCodec, XdrReader, XdrWriter},
+};
+use crate::netid::Netid;
+
 pub const STAT_HIGHPROC: u32 = 13;
 pub const VERS_2_STAT: u32 = 0;
 pub const VERS_3_STAT: u32 = 1;
@@ -8,19 +16,15 @@ pub const HIGHPROC_2: u32 = 5;
 pub const HIGHPROC_3: u32 = 8;
 pub const HIGHPROC_4: u32 = 12;
 
-#[derive(Debug, PartialEq, facet::Facet)]
+#[derive(Debug, PartialEq, Clone, facet::Facet)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NetBuf {
     pub maxlen: u32,
     pub buf: Vec<u8>,
 }
 
 #[derive(Debug, PartialEq, Clone, facet::Facet)]
-pub struct RPList {
-    pub rpcb_map: RPCB,
-    pub rpcb_next: Vec<Box<RPList>>,
-}
-
-#[derive(Debug, PartialEq, Clone, facet::Facet)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RPCB {
     pub r_prog: u32,
     pub r_vers: u32,
@@ -29,7 +33,73 @@ pub struct RPCB {
     pub r_owner: String,
 }
 
-#[derive(Debug, PartialEq)]
+/// An [`RPCB`] borrowing its strings from the buffer it was decoded from, so decoding the
+/// arguments of a SET, UNSET or GETADDR does not allocate.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RpcbRef<'a> {
+    pub r_prog: u32,
+    pub r_vers: u32,
+    pub r_netid: &'a str,
+    pub r_addr: &'a str,
+    pub r_owner: &'a str,
+}
+
+impl RpcbRef<'_> {
+    pub fn netid(&self) -> Netid {
+        self.r_netid.into()
+    }
+
+    pub fn encode(&self, writer: &mut XdrWriter) {
+        writer.u32(self.r_prog);
+        writer.u32(self.r_vers);
+        writer.string(self.r_netid);
+        writer.string(self.r_addr);
+        writer.string(self.r_owner);
+    }
+
+    pub fn to_xdr(&self) -> Vec<u8> {
+        let mut writer = XdrWriter::new();
+        self.encode(&mut writer);
+        writer.into_bytes()
+    }
+
+    pub fn into_owned(self) -> RPCB {
+        RPCB {
+            r_prog: self.r_prog,
+            r_vers: self.r_vers,
+            r_netid: self.r_netid.to_owned(),
+            r_addr: self.r_addr.to_owned(),
+            r_owner: self.r_owner.to_owned(),
+        }
+    }
+}
+
+impl<'a> From<&'a RPCB> for RpcbRef<'a> {
+    fn from(rpcb: &'a RPCB) -> Self {
+        Self {
+            r_prog: rpcb.r_prog,
+            r_vers: rpcb.r_vers,
+            r_netid: &rpcb.r_netid,
+            r_addr: &rpcb.r_addr,
+            r_owner: &rpcb.r_owner,
+        }
+    }
+}
+
+impl<'a> XdrBorrow<'a> for RpcbRef<'a> {
+    fn decode_borrowed(reader: &mut XdrReader<'a>) -> Result<Self, XdrDeserError> {
+        Ok(Self {
+            r_prog: reader.u32()?,
+            r_vers: reader.u32()?,
+            r_netid: reader.str()?,
+            r_addr: reader.str()?,
+            r_owner: reader.str()?,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, facet::Facet)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Entry {
     pub r_maddr: String,
     pub r_nc_netid: String,
@@ -37,12 +107,15 @@ pub struct Entry {
     pub r_nc_protofmly: String,
     pub r_nc_proto: String,
 }
-#[derive(Debug, PartialEq)]
-pub struct EntryList {
-    pub rpcb_entry_map: Entry,
-    pub rpcb_entry_next: Vec<Box<EntryList>>,
-}
-#[derive(Debug, PartialEq, facet::Facet)]
+
+/// The reply to `RPCBPROC_DUMP`.
+pub type RPList = XdrList<RPCB>;
+
+/// The reply to `RPCBPROC_GETADDRLIST`.
+pub type EntryList = XdrList<Entry>;
+
+#[derive(Debug, PartialEq, Clone, facet::Facet)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RmtCallArgs {
     pub prog: u32,
     pub vers: u32,
@@ -50,37 +123,47 @@ pub struct RmtCallArgs {
     pub args: Vec<u8>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, facet::Facet)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RmtCallRes {
     pub addr: String,
     pub results: Vec<u8>,
 }
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Stat {
     pub info: Proc,
     pub setinfo: i32,
     pub unsetinfo: i32,
-    pub addrinfo: Option<Box<AddrList>>,
-    pub rmtinfo: Option<Box<RmtCallList>>,
+    pub addrinfo: AddrList,
+    pub rmtinfo: RmtCallList,
 }
-#[derive(Debug, PartialEq)]
+/// The reply to `RPCBPROC_GETSTAT`, which facet cannot describe as it has fixed size arrays.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StatByVers(pub [Stat; VERS_STAT as usize]);
 
-#[derive(Debug, PartialEq)]
-pub struct AddrList {
+/// Address lookups of one program, `rpcbs_addr`.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RpcbsAddr {
     pub prog: u32,
     pub vers: u32,
     pub success: i32,
     pub failure: i32,
     pub netid: String,
-    pub next: Vec<Box<AddrList>>,
 }
 
-#[derive(Debug, PartialEq)]
+pub type AddrList = XdrList<RpcbsAddr>;
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Proc(pub [i32; STAT_HIGHPROC as usize]);
 
-#[derive(Debug, PartialEq)]
-pub struct RmtCallList {
+/// Remote calls to one procedure, `rpcbs_rmtcall`.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RpcbsRmtCall {
     pub prog: u32,
     pub vers: u32,
     pub proc: u32,
@@ -88,5 +171,392 @@ pub struct RmtCallList {
     pub failure: i32,
     pub indirect: i32,
     pub netid: String,
-    pub next: Vec<Box<RmtCallList>>,
+}
+
+pub type RmtCallList = XdrList<RpcbsRmtCall>;
+
+impl RPCB {
+    pub fn netid(&self) -> Netid {
+        self.r_netid.as_str().into()
+    }
+}
+
+impl XdrCodec for RPCB {
+    fn encode(&self, writer: &mut XdrWriter) {
+        RpcbRef::from(self).encode(writer);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        Ok(Self {
+            r_prog: reader.u32()?,
+            r_vers: reader.u32()?,
+            r_netid: reader.string()?,
+            r_addr: reader.string()?,
+            r_owner: reader.string()?,
+        })
+    }
+}
+
+impl XdrCodec for NetBuf {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.u32(self.maxlen);
+        writer.opaque(&self.buf);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        Ok(Self {
+            maxlen: reader.u32()?,
+            buf: reader.opaque()?.to_vec(),
+        })
+    }
+}
+
+impl XdrCodec for RmtCallRes {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.string(&self.addr);
+        writer.opaque(&self.results);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        Ok(Self {
+            addr: reader.string()?,
+            results: reader.opaque()?.to_vec(),
+        })
+    }
+}
+
+impl XdrCodec for Entry {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.string(&self.r_maddr);
+        writer.string(&self.r_nc_netid);
+        writer.u32(self.r_nc_semantics);
+        writer.string(&self.r_nc_protofmly);
+        writer.string(&self.r_nc_proto);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        Ok(Self {
+            r_maddr: reader.string()?,
+            r_nc_netid: reader.string()?,
+            r_nc_semantics: reader.u32()?,
+            r_nc_protofmly: reader.string()?,
+            r_nc_proto: reader.string()?,
+        })
+    }
+}
+
+impl XdrCodec for StatByVers {
+    fn encode(&self, writer: &mut XdrWriter) {
+        for stat in &self.0 {
+            stat.encode(writer);
+        }
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        Ok(Self([
+            Stat::decode(reader)?,
+            Stat::decode(reader)?,
+            Stat::decode(reader)?,
+        ]))
+    }
+}
+
+impl XdrCodec for Stat {
+    fn encode(&self, writer: &mut XdrWriter) {
+        for count in self.info.0 {
+            writer.i32(count);
+        }
+        writer.i32(self.setinfo);
+        writer.i32(self.unsetinfo);
+        self.addrinfo.encode(writer);
+        self.rmtinfo.encode(writer);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        let mut info = [0; STAT_HIGHPROC as usize];
+        for count in &mut info {
+            *count = reader.i32()?;
+        }
+        Ok(Self {
+            info: Proc(info),
+            setinfo: reader.i32()?,
+            unsetinfo: reader.i32()?,
+            addrinfo: AddrList::decode(reader)?,
+            rmtinfo: RmtCallList::decode(reader)?,
+        })
+    }
+}
+
+impl XdrCodec for RpcbsAddr {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.u32(self.prog);
+        writer.u32(self.vers);
+        writer.i32(self.success);
+        writer.i32(self.failure);
+        writer.string(&self.netid);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        Ok(Self {
+            prog: reader.u32()?,
+            vers: reader.u32()?,
+            success: reader.i32()?,
+            failure: reader.i32()?,
+            netid: reader.string()?,
+        })
+    }
+}
+
+impl XdrCodec for RpcbsRmtCall {
+    fn encode(&self, writer: &mut XdrWriter) {
+        writer.u32(self.prog);
+        writer.u32(self.vers);
+        writer.u32(self.proc);
+        writer.i32(self.success);
+        writer.i32(self.failure);
+        writer.i32(self.indirect);
+        writer.string(&self.netid);
+    }
+
+    fn decode(reader: &mut XdrReader) -> Result<Self, XdrDeserError> {
+        Ok(Self {
+            prog: reader.u32()?,
+            vers: reader.u32()?,
+            proc: reader.u32()?,
+            success: reader.i32()?,
+            failure: reader.i32()?,
+            indirect: reader.i32()?,
+            netid: reader.string()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::{
+        AddrList, Entry, EntryList, NetBuf, Proc, RPCB, RPList, RmtCallArgs, RmtCallList,
+        RmtCallRes, RpcbRef, RpcbsAddr, RpcbsRmtCall, STAT_HIGHPROC, Stat, StatByVers,
+    };
+    use crate::xdr_types::{
+        codec::{XdrBorrow, XdrCodec},
+        layout::{list, opaque, string, word},
+    };
+
+    // Any characters, so multi-byte UTF-8 and every padding length are covered
+    const TEXT: &str = ".{0,12}";
+
+    fn rpcb() -> impl Strategy<Value = RPCB> {
+        (any::<[u32; 2]>(), TEXT, TEXT, TEXT).prop_map(|([r_prog, r_vers], netid, addr, owner)| {
+            RPCB {
+                r_prog,
+                r_vers,
+                r_netid: netid,
+                r_addr: addr,
+                r_owner: owner,
+            }
+        })
+    }
+
+    fn rpcb_layout(rpcb: &RPCB) -> Vec<u8> {
+        [
+            word(rpcb.r_prog),
+            word(rpcb.r_vers),
+            string(&rpcb.r_netid),
+            string(&rpcb.r_addr),
+            string(&rpcb.r_owner),
+        ]
+        .concat()
+    }
+
+    fn entry() -> impl Strategy<Value = Entry> {
+        (TEXT, TEXT, any::<u32>(), TEXT, TEXT).prop_map(
+            |(r_maddr, r_nc_netid, r_nc_semantics, r_nc_protofmly, r_nc_proto)| Entry {
+                r_maddr,
+                r_nc_netid,
+                r_nc_semantics,
+                r_nc_protofmly,
+                r_nc_proto,
+            },
+        )
+    }
+
+    fn entry_layout(entry: &Entry) -> Vec<u8> {
+        [
+            string(&entry.r_maddr),
+            string(&entry.r_nc_netid),
+            word(entry.r_nc_semantics),
+            string(&entry.r_nc_protofmly),
+            string(&entry.r_nc_proto),
+        ]
+        .concat()
+    }
+
+    fn stat() -> impl Strategy<Value = Stat> {
+        let addr = (any::<(u32, u32, i32, i32)>(), TEXT).prop_map(
+            |((prog, vers, success, failure), netid)| RpcbsAddr {
+                prog,
+                vers,
+                success,
+                failure,
+                netid,
+            },
+        );
+        let rmtcall = (any::<(u32, u32, u32, i32, i32, i32)>(), TEXT).prop_map(
+            |((prog, vers, proc, success, failure, indirect), netid)| RpcbsRmtCall {
+                prog,
+                vers,
+                proc,
+                success,
+                failure,
+                indirect,
+                netid,
+            },
+        );
+        (
+            any::<[i32; STAT_HIGHPROC as usize]>(),
+            any::<[i32; 2]>(),
+            vec(addr, 0..4),
+            vec(rmtcall, 0..4),
+        )
+            .prop_map(|(info, [setinfo, unsetinfo], addrinfo, rmtinfo)| Stat {
+                info: Proc(info),
+                setinfo,
+                unsetinfo,
+                addrinfo: AddrList::from(addrinfo),
+                rmtinfo: RmtCallList::from(rmtinfo),
+            })
+    }
+
+    fn stat_layout(stat: &Stat) -> Vec<u8> {
+        let mut bytes: Vec<u8> = stat.info.0.into_iter().flat_map(word).collect();
+        bytes.extend(word(stat.setinfo));
+        bytes.extend(word(stat.unsetinfo));
+        bytes.extend(list(stat.addrinfo.iter().map(|addr| {
+            [
+                word(addr.prog),
+                word(addr.vers),
+                word(addr.success),
+                word(addr.failure),
+                string(&addr.netid),
+            ]
+            .concat()
+        })));
+        bytes.extend(list(stat.rmtinfo.iter().map(|call| {
+            [
+                word(call.prog),
+                word(call.vers),
+                word(call.proc),
+                word(call.success),
+                word(call.failure),
+                word(call.indirect),
+                string(&call.netid),
+            ]
+            .concat()
+        })));
+        bytes
+    }
+
+    #[test]
+    fn borrowed_rpcb() {
+        let rpcb = RPCB {
+            r_prog: 100003,
+            r_vers: 3,
+            r_netid: "tcp6".to_owned(),
+            r_addr: "::1.8.1".to_owned(),
+            r_owner: "superuser".to_owned(),
+        };
+        let bytes = facet_xdr::to_vec(&rpcb).unwrap();
+        assert_eq!(rpcb.to_xdr(), bytes);
+
+        let borrowed = RpcbRef::from_xdr_borrowed(&bytes).unwrap();
+        assert_eq!(borrowed, RpcbRef::from(&rpcb));
+        assert_eq!(borrowed.r_addr, "::1.8.1");
+        assert_eq!(borrowed.to_xdr(), bytes);
+        assert_eq!(borrowed.into_owned(), rpcb);
+
+        for len in 0..bytes.len() {
+            assert!(RpcbRef::from_xdr_borrowed(&bytes[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn empty_lists_are_false() {
+        assert_eq!(RPList::new().to_xdr(), [0, 0, 0, 0]);
+        assert_eq!(EntryList::new().to_xdr(), [0, 0, 0, 0]);
+        assert_eq!(RPList::from_xdr(&[0, 0, 0, 0]).unwrap(), RPList::new());
+        assert_eq!(
+            EntryList::from_xdr(&[0, 0, 0, 0]).unwrap(),
+            EntryList::new()
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn rpcb_layouts(rpcb in rpcb()) {
+            let bytes = rpcb_layout(&rpcb);
+            prop_assert_eq!(rpcb.to_xdr(), bytes.clone());
+            prop_assert_eq!(facet_xdr::to_vec(&rpcb).unwrap(), bytes.clone());
+            prop_assert_eq!(RpcbRef::from(&rpcb).to_xdr(), bytes.clone());
+            prop_assert_eq!(RPCB::from_xdr(&bytes).unwrap(), rpcb.clone());
+            prop_assert_eq!(RpcbRef::from_xdr_borrowed(&bytes).unwrap(), RpcbRef::from(&rpcb));
+            prop_assert_eq!(facet_xdr::deserialize::<RPCB>(&bytes).unwrap(), rpcb);
+        }
+
+        #[test]
+        fn rplist_layouts(rpcbs in vec(rpcb(), 0..8)) {
+            let bytes = list(rpcbs.iter().map(rpcb_layout));
+            let rpcbs = RPList::from(rpcbs);
+            prop_assert_eq!(rpcbs.to_xdr(), bytes.clone());
+            prop_assert_eq!(RPList::from_xdr(&bytes).unwrap(), rpcbs);
+        }
+
+        #[test]
+        fn entry_list_layouts(entries in vec(entry(), 0..8)) {
+            if let Some(entry) = entries.first() {
+                let bytes = entry_layout(entry);
+                prop_assert_eq!(facet_xdr::to_vec(entry).unwrap(), bytes.clone());
+                prop_assert_eq!(facet_xdr::deserialize::<Entry>(&bytes).unwrap(), entry.clone());
+            }
+            let bytes = list(entries.iter().map(entry_layout));
+            let entries = EntryList::from(entries);
+            prop_assert_eq!(entries.to_xdr(), bytes.clone());
+            prop_assert_eq!(EntryList::from_xdr(&bytes).unwrap(), entries);
+        }
+
+        #[test]
+        fn netbuf_layouts(maxlen: u32, buf in vec(any::<u8>(), 0..64)) {
+            let netbuf = NetBuf { maxlen, buf };
+            let bytes = [word(maxlen), opaque(&netbuf.buf)].concat();
+            prop_assert_eq!(facet_xdr::to_vec(&netbuf).unwrap(), bytes.clone());
+            prop_assert_eq!(netbuf.to_xdr(), bytes.clone());
+            prop_assert_eq!(facet_xdr::deserialize::<NetBuf>(&bytes).unwrap(), netbuf.clone());
+            prop_assert_eq!(NetBuf::from_xdr(&bytes).unwrap(), netbuf);
+        }
+
+        #[test]
+        fn rmtcall_layouts(header: [u32; 3], args in vec(any::<u8>(), 0..64), addr in TEXT) {
+            let [prog, vers, proc] = header;
+            let call_args = RmtCallArgs { prog, vers, proc, args: args.clone() };
+            let bytes = [word(prog), word(vers), word(proc), opaque(&args)].concat();
+            prop_assert_eq!(facet_xdr::to_vec(&call_args).unwrap(), bytes.clone());
+            prop_assert_eq!(facet_xdr::deserialize::<RmtCallArgs>(&bytes).unwrap(), call_args);
+
+            let call_res = RmtCallRes { addr, results: args };
+            let bytes = [string(&call_res.addr), opaque(&call_res.results)].concat();
+            prop_assert_eq!(facet_xdr::to_vec(&call_res).unwrap(), bytes.clone());
+            prop_assert_eq!(call_res.to_xdr(), bytes.clone());
+            prop_assert_eq!(facet_xdr::deserialize::<RmtCallRes>(&bytes).unwrap(), call_res.clone());
+            prop_assert_eq!(RmtCallRes::from_xdr(&bytes).unwrap(), call_res);
+        }
+
+        #[test]
+        fn stat_layouts(stats in [stat(), stat(), stat()]) {
+            let bytes: Vec<u8> = stats.iter().flat_map(stat_layout).collect();
+            let stats = StatByVers(stats);
+            prop_assert_eq!(stats.to_xdr(), bytes.clone());
+            prop_assert_eq!(StatByVers::from_xdr(&bytes).unwrap(), stats);
+        }
+    }
 }
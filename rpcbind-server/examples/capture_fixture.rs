@@ -0,0 +1,129 @@
+//! Records a wire format fixture from the calls a libtirpc client makes to an rpcbind.
+//!
+//! Usage: `capture_fixture CLIENT SERVER FIXTURE SOURCE`, where `CLIENT` is
+//! `tests/capture/capture.c` built by `tests/capture/capture.sh`, and `SERVER` the address of
+//! the rpcbind answering it, described by `SOURCE` in the fixture. The records sent each way on
+//! the client's connection are relayed, each as a single fragment, and printed in the format of
+//! `tests/fixtures`, each call described by what the client printed before making it.
+
+use std::{
+    env,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Result, anyhow, bail, ensure};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+const LAST_FRAGMENT: u32 = 1 << 31;
+
+/// Reads the fragments of a record, returning them as one fragment with its header, or `None`
+/// at the end of the stream.
+async fn read_record(stream: &mut (impl AsyncRead + Unpin)) -> Result<Option<Vec<u8>>> {
+    let mut record = vec![0u8; 4];
+    loop {
+        let mut header = [0u8; 4];
+        match stream.read_exact(&mut header).await {
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && record.len() == 4 => {
+                return Ok(None);
+            }
+            result => result?,
+        };
+        let header = u32::from_be_bytes(header);
+        let start = record.len();
+        record.resize(start + (header & !LAST_FRAGMENT) as usize, 0);
+        stream.read_exact(&mut record[start..]).await?;
+        if header & LAST_FRAGMENT != 0 {
+            break;
+        }
+    }
+    let len = (record.len() - 4) as u32;
+    record[..4].copy_from_slice(&(LAST_FRAGMENT | len).to_be_bytes());
+    Ok(Some(record))
+}
+
+fn words(record: &[u8]) -> String {
+    record
+        .chunks(4)
+        .map(|word| format!("{:08x}", u32::from_be_bytes(word.try_into().unwrap())))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Wraps `text` into comment lines of at most 96 characters.
+fn comment(text: &str) -> String {
+    let mut lines = vec![String::from("#")];
+    for word in text.split(' ') {
+        let line = lines.last_mut().unwrap();
+        if line.len() + 1 + word.len() > 96 {
+            lines.push(format!("# {word}"));
+        } else {
+            line.push(' ');
+            line.push_str(word);
+        }
+    }
+    lines.join("\n")
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<()> {
+    let [_, client, server, fixture, source] = env::args()
+        .collect::<Vec<_>>()
+        .try_into()
+        .map_err(|_| anyhow!("usage: capture_fixture CLIENT SERVER FIXTURE SOURCE"))?;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port().to_string();
+    let child = Command::new(&client)
+        .args(["127.0.0.1", &port, &fixture])
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("running {client}"))?;
+
+    let (mut caller, _) = listener.accept().await?;
+    let mut rpcbind = TcpStream::connect(&server)
+        .await
+        .with_context(|| format!("connecting to {server}"))?;
+    let mut exchanges = Vec::new();
+    while let Some(call) = read_record(&mut caller).await? {
+        rpcbind.write_all(&call).await?;
+        let reply = read_record(&mut rpcbind)
+            .await?
+            .with_context(|| format!("{server} closed the connection"))?;
+        caller.write_all(&reply).await?;
+        exchanges.push((call, reply));
+    }
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("{client} failed: {}", output.status);
+    }
+    let stdout = String::from_utf8(output.stdout)?;
+    let mut lines = stdout.lines();
+    let title = lines.next().context("no fixture title")?;
+    let descriptions: Vec<_> = lines.collect();
+    ensure!(
+        descriptions.len() == exchanges.len(),
+        "{} calls described, {} made",
+        descriptions.len(),
+        exchanges.len()
+    );
+
+    println!(
+        "{}",
+        comment(&format!(
+            "Wire format fixture: each `>` line is a call record sent on one connection, each \
+             `<` line the reply record expected for it, as 32-bit big endian words including \
+             the record marking header. Captured by tests/capture/capture.sh: the calls were \
+             made with libtirpc's clnt_call(), which checked each result, and answered by \
+             {source} starting from an empty registry."
+        ))
+    );
+    println!("#\n# {title}");
+    for (description, (call, reply)) in descriptions.iter().zip(&exchanges) {
+        println!("\n# {description}\n> {}\n< {}", words(call), words(reply));
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+use anyhow::{Result, anyhow, bail};
+use rpcbind_rs::request::Limits;
+
+/// Options given to the server on the command line.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Persist registrations to this file instead of only keeping them in memory.
+    pub state_file: Option<PathBuf>,
+    /// Unix socket on which registry changes are streamed as JSON lines.
+    pub control_socket: Option<PathBuf>,
+    /// Program name database used in logs, instead of `/etc/rpc`.
+    pub rpc_file: Option<PathBuf>,
+    /// Path of the socket serving the `local` and `unix` netids, instead of the default.
+    pub local_socket: Option<PathBuf>,
+    /// Unix socket on which every registration is dumped as JSON lines, with its lease.
+    pub admin_socket: Option<PathBuf>,
+    /// Let the owner of a registration replace it with another SET, which rpcbind refuses.
+    pub owner_replacement: bool,
+    /// Bounds on the lengths of fields in call arguments.
+    pub limits: Limits,
+    /// How long a registration with an owner lasts unless the owner sets it again. Without
+    /// one, registrations last until they are unset.
+    pub lease_ttl: Option<Duration>,
+    /// How often to look in `/proc/net` for registrations whose port nothing is bound to.
+    pub stale_check: Option<Duration>,
+    /// Remove the registrations the stale check finds, instead of only reporting them.
+    pub remove_stale: bool,
+}
+
+impl Config {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut config = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| anyhow!("{arg} requires a value"));
+            let mut length = || parse(&arg, value()?, "a length");
+            match arg.as_str() {
+                "--state-file" => config.state_file = Some(value()?.into()),
+                "--control-socket" => config.control_socket = Some(value()?.into()),
+                "--rpc-file" => config.rpc_file = Some(value()?.into()),
+                "--local-socket" => config.local_socket = Some(value()?.into()),
+                "--admin-socket" => config.admin_socket = Some(value()?.into()),
+                "--allow-owner-replace" => config.owner_replacement = true,
+                "--max-netid-len" => config.limits.netid = length()?,
+                "--max-address-len" => config.limits.universal_address = length()?,
+                "--max-owner-len" => config.limits.owner = length()?,
+                "--max-args-len" => config.limits.args = length()?,
+                "--lease-ttl" => {
+                    config.lease_ttl = {
+                        let seconds = parse(&arg, value()?, "a number of seconds")?;
+                        Some(Duration::from_secs(seconds))
+                    }
+                }
+                "--stale-check" => {
+                    let seconds = parse(&arg, value()?, "a number of seconds")?;
+                    if seconds == 0 {
+                        bail!("{arg} requires a number of seconds above 0");
+                    }
+                    config.stale_check = Some(Duration::from_secs(seconds))
+                }
+                "--remove-stale" => config.remove_stale = true,
+                _ => bail!("Unknown argument {arg}"),
+            }
+        }
+        if config.remove_stale && config.stale_check.is_none() {
+            bail!("--remove-stale requires --stale-check");
+        }
+        Ok(config)
+    }
+}
+
+fn parse<T: FromStr>(arg: &str, value: String, kind: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("{arg} requires {kind}, not {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Config;
+
+    fn from_args(args: &[&str]) -> anyhow::Result<Config> {
+        Config::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn stale_check_options() {
+        let config = from_args(&["--stale-check", "30", "--remove-stale"]).unwrap();
+        assert_eq!(config.stale_check, Some(Duration::from_secs(30)));
+        assert!(config.remove_stale);
+
+        let error = from_args(&["--stale-check", "0"]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "--stale-check requires a number of seconds above 0"
+        );
+        let error = from_args(&["--remove-stale"]).unwrap_err();
+        assert_eq!(error.to_string(), "--remove-stale requires --stale-check");
+    }
+}
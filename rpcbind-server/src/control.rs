@@ -0,0 +1,210 @@
+use std::{path::Path, sync::Arc, time::Instant};
+
+use anyhow::Result;
+use serde::Serialize;
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    net::UnixListener,
+};
+
+use crate::{
+    listener::remove_stale_socket,
+    registry::{Registry, RegistryEvent},
+    state::{ProgramDescription, ProgramKey},
+};
+
+/// Streams registry changes to every client of the unix socket at `path`.
+///
+/// Each event is written as a single line of JSON. A new client first receives an `added`
+/// event for every existing registration, so it never has to query the current state separately.
+/// A client that reads too slowly to keep up is disconnected, and starts again from a snapshot
+/// when it reconnects.
+pub async fn serve(path: &Path, registry: Arc<dyn Registry>) -> Result<()> {
+    let listener = bind(path)?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            if let Err(e) = stream_events(stream, registry.as_ref()).await {
+                eprintln!("Error streaming registry events {e:?}");
+            }
+        });
+    }
+}
+
+/// Dumps every registration to each client of the unix socket at `path`, then closes the
+/// connection.
+///
+/// Each registration is written as a single line of JSON, with `ttl` holding the whole seconds
+/// left on its lease, or `null` if it lasts until it is unset.
+pub async fn serve_dump(path: &Path, registry: Arc<dyn Registry>) -> Result<()> {
+    let listener = bind(path)?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            if let Err(e) = dump_leases(stream, registry.as_ref()).await {
+                eprintln!("Error dumping registrations {e:?}");
+            }
+        });
+    }
+}
+
+fn bind(path: &Path) -> Result<UnixListener> {
+    remove_stale_socket(path)?;
+    Ok(UnixListener::bind(path)?)
+}
+
+#[derive(Serialize)]
+struct Lease {
+    key: ProgramKey,
+    description: ProgramDescription,
+    ttl: Option<u64>,
+}
+
+async fn dump_leases(mut stream: impl AsyncWrite + Unpin, registry: &dyn Registry) -> Result<()> {
+    for (key, description, remaining) in registry.dump_leases(Instant::now()) {
+        let lease = Lease {
+            key,
+            description,
+            ttl: remaining.map(|remaining| remaining.as_secs()),
+        };
+        write_line(&mut stream, &lease).await?;
+    }
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn stream_events(mut stream: impl AsyncWrite + Unpin, registry: &dyn Registry) -> Result<()> {
+    // Subscribe before taking the snapshot so nothing in between is missed
+    let mut events = registry.watch();
+    for (key, description) in registry.dump() {
+        write_line(&mut stream, &RegistryEvent::Added { key, description }).await?;
+    }
+
+    while let Some(event) = events.recv().await {
+        write_line(&mut stream, &event).await?;
+    }
+    Ok(())
+}
+
+async fn write_line(stream: &mut (impl AsyncWrite + Unpin), value: &impl Serialize) -> Result<()> {
+    let mut line = serde_json::to_vec(value)?;
+    line.push(b'\n');
+    stream.write_all(&line).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use rpcbind_rs::netid::Netid;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    use super::{bind, dump_leases, stream_events};
+    use crate::{
+        registry::{InMemoryRegistry, Registry},
+        state::{ProgramDescription, ProgramKey},
+    };
+
+    fn key(version: u32) -> ProgramKey {
+        ProgramKey {
+            program: 100003,
+            version,
+            net_id: Netid::Tcp,
+        }
+    }
+
+    fn description(addr: &str) -> ProgramDescription {
+        ProgramDescription {
+            addr: addr.parse().unwrap(),
+            owner: Some("nfs".to_owned()),
+        }
+    }
+
+    #[tokio::test]
+    async fn streams_snapshot_then_changes() {
+        let registry = InMemoryRegistry::new().with_owner_replacement(true);
+        registry.set(key(3), description("127.0.0.1.8.1"));
+
+        let (client, server) = tokio::io::duplex(4096);
+        let mut lines = BufReader::new(client).lines();
+        let streamer = stream_events(server, &registry);
+        let reader = async {
+            let mut received = Vec::new();
+            for _ in 0..4 {
+                received.push(lines.next_line().await.unwrap().unwrap());
+            }
+            received
+        };
+        let changes = async {
+            tokio::task::yield_now().await;
+            registry.set(key(4), description("127.0.0.1.8.1"));
+            registry.set(key(4), description("127.0.0.1.8.2"));
+            registry.unset(100003, 3, Some(&Netid::Tcp));
+        };
+
+        let received = tokio::select! {
+            result = streamer => panic!("streaming stopped early {result:?}"),
+            (received, ()) = async { tokio::join!(reader, changes) } => received,
+        };
+        assert_eq!(
+            received,
+            [
+                r#"{"event":"added","key":{"program":100003,"version":3,"net_id":"tcp"},"description":{"addr":"127.0.0.1.8.1","owner":"nfs"}}"#,
+                r#"{"event":"added","key":{"program":100003,"version":4,"net_id":"tcp"},"description":{"addr":"127.0.0.1.8.1","owner":"nfs"}}"#,
+                r#"{"event":"changed","key":{"program":100003,"version":4,"net_id":"tcp"},"old":{"addr":"127.0.0.1.8.1","owner":"nfs"},"new":{"addr":"127.0.0.1.8.2","owner":"nfs"}}"#,
+                r#"{"event":"removed","key":{"program":100003,"version":3,"net_id":"tcp"},"description":{"addr":"127.0.0.1.8.1","owner":"nfs"}}"#,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn dumps_leases() {
+        let registry = InMemoryRegistry::new();
+        registry.set(key(3), description("127.0.0.1.8.1"));
+        registry.set_leased(
+            key(4),
+            description("127.0.0.1.8.1"),
+            Duration::from_secs(30),
+        );
+
+        let mut dump = Vec::new();
+        dump_leases(&mut dump, &registry).await.unwrap();
+        let mut lines: Vec<_> = String::from_utf8(dump)
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect();
+        lines.sort();
+        // The lease may have ticked below 30 seconds while dumping
+        let leased = lines[1].replace(r#""ttl":30"#, r#""ttl":29"#);
+        assert_eq!(
+            [lines[0].as_str(), leased.as_str()],
+            [
+                r#"{"key":{"program":100003,"version":3,"net_id":"tcp"},"description":{"addr":"127.0.0.1.8.1","owner":"nfs"},"ttl":null}"#,
+                r#"{"key":{"program":100003,"version":4,"net_id":"tcp"},"description":{"addr":"127.0.0.1.8.1","owner":"nfs"},"ttl":29}"#,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn only_stale_sockets_are_replaced() {
+        let path = std::env::temp_dir().join(format!("rpcbind-control-{}", std::process::id()));
+
+        fs::write(&path, "not a socket").unwrap();
+        assert!(bind(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "not a socket");
+        fs::remove_file(&path).unwrap();
+
+        let listener = bind(&path).unwrap();
+        let error = bind(&path).unwrap_err();
+        assert!(error.to_string().contains("in use"), "{error}");
+
+        // Left behind once nothing listens on it
+        drop(listener);
+        let _listener = bind(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt::{Debug, Display};
 use onc_rpc::{
     AcceptedReply, AcceptedStatus, AuthError, RejectedReply, ReplyBody, auth::AuthFlavor,
 };
+use rpcbind_rs::request::RequestError;
 use thiserror::Error;
 
 pub type RPCResult<T> = Result<T, RPCError>;
@@ -27,6 +28,12 @@ impl<P: AsRef<[u8]>> From<AcceptedStatus<P>> for RPCError {
     }
 }
 
+impl From<RequestError> for RPCError {
+    fn from(error: RequestError) -> Self {
+        error.accepted_status::<[u8; 0]>().into()
+    }
+}
+
 impl Display for RPCError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         Debug::fmt(&self, f)
@@ -49,7 +56,8 @@ fn convert_accepted_status<P: AsRef<[u8]>>(
     from: &AcceptedStatus<impl AsRef<[u8]>>,
 ) -> AcceptedStatus<P> {
     match from {
-        AcceptedStatus::Success(_) => panic!("Converting a non_error Accepted Status"),
+        // A success is never an error, but if one is made of it the call still fails cleanly
+        AcceptedStatus::Success(_) => AcceptedStatus::SystemError,
         AcceptedStatus::ProgramUnavailable => AcceptedStatus::ProgramUnavailable,
         AcceptedStatus::ProgramMismatch { low, high } => AcceptedStatus::ProgramMismatch {
             low: *low,
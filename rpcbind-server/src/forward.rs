@@ -0,0 +1,374 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    hash::{BuildHasher, Hasher, RandomState},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use bytes::Bytes;
+use onc_rpc::{
+    AcceptedReply, AcceptedStatus, CallBody, MessageType, ReplyBody, RpcMessage, auth::AuthFlavor,
+};
+use parking_lot::Mutex;
+use rpcbind_rs::{
+    netid::Netid,
+    request::{PortMapperRequest, RpcBindRequest, RpcRequest},
+    xdr_types::{
+        codec::XdrCodec,
+        port_mapper::CallResult,
+        rpcbind::{RmtCallArgs, RmtCallRes},
+    },
+};
+use tokio::{
+    net::UdpSocket,
+    sync::{Semaphore, oneshot},
+    time::timeout,
+};
+
+use crate::{
+    MAX_DATAGRAM_LEN, MSG_HEADER_LEN, PROGRAM_ID, datagram_record, limits::limits,
+    registry::Registry, state::ProgramKey, stats::stats,
+};
+
+/// How long a program has to answer a forwarded call before the caller is left without reply.
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The procedures that ask rpcbind to call another program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Procedure {
+    /// `PMAPPROC_CALLIT`, whose result gives the port of the program.
+    CallIt,
+    /// `RPCBPROC_BCAST`, whose result gives the universal address of the program.
+    Broadcast,
+    /// `RPCBPROC_INDIRECT`, which is answered with an error when the call cannot be forwarded.
+    Indirect,
+}
+
+/// A call to forward to a registered program, on behalf of the caller of `xid`.
+#[derive(Debug)]
+pub struct Call {
+    xid: u32,
+    /// The version of rpcbind the call was made to.
+    version: u32,
+    procedure: Procedure,
+    credentials: AuthFlavor<Bytes>,
+    args: RmtCallArgs,
+}
+
+impl Call {
+    /// The call to forward in `message`, or `None` if it is any other call.
+    ///
+    /// Forwarding calls with malformed arguments are left to be answered like any other call.
+    pub fn from_message(message: &RpcMessage<Bytes, Bytes>) -> Option<Self> {
+        let body = message.call_body()?;
+        let forwards = matches!(
+            (body.program_version(), body.procedure()),
+            (2..=4, 5) | (4, 10)
+        );
+        if body.program() != PROGRAM_ID || !forwards {
+            return None;
+        }
+        let (procedure, args) = match RpcRequest::from_body_with_limits(body, limits()).ok()? {
+            RpcRequest::V2(PortMapperRequest::CallIt(args)) => (
+                Procedure::CallIt,
+                RmtCallArgs {
+                    prog: args.prog,
+                    vers: args.vers,
+                    proc: args.proc,
+                    args: args.args,
+                },
+            ),
+            RpcRequest::V3(RpcBindRequest::Broadcast(args))
+            | RpcRequest::V4(RpcBindRequest::Broadcast(args)) => (Procedure::Broadcast, args),
+            RpcRequest::V4(RpcBindRequest::Indirect(args)) => (Procedure::Indirect, args),
+            _ => return None,
+        };
+        // Like rpcbind, only AUTH_UNIX credentials are passed on to the program
+        let credentials = match body.auth_credentials() {
+            credentials @ AuthFlavor::AuthUnix(_) => credentials.clone(),
+            _ => AuthFlavor::AuthNone(None),
+        };
+        Some(Self {
+            xid: message.xid(),
+            version: body.program_version(),
+            procedure,
+            credentials,
+            args,
+        })
+    }
+
+    /// Serialises the call to the program as a datagram, without record marking.
+    pub fn datagram(&self, xid: u32) -> Result<Vec<u8>> {
+        let body = CallBody::new(
+            self.args.prog,
+            self.args.vers,
+            self.args.proc,
+            self.credentials.clone(),
+            AuthFlavor::AuthNone(None),
+            self.args.args.as_slice(),
+        );
+        let mut record = RpcMessage::new(xid, MessageType::Call(body)).serialise()?;
+        record.drain(..MSG_HEADER_LEN);
+        Ok(record)
+    }
+
+    /// The payload answering the caller, wrapping the `results` the program at `target` gave.
+    fn results(&self, target: &Target, results: Vec<u8>) -> Vec<u8> {
+        match self.procedure {
+            Procedure::CallIt => CallResult {
+                port: target.addr.port().into(),
+                res: results,
+            }
+            .to_xdr(),
+            Procedure::Broadcast | Procedure::Indirect => RmtCallRes {
+                addr: target.universal_address.clone(),
+                results,
+            }
+            .to_xdr(),
+        }
+    }
+}
+
+/// Where a forwarded call goes.
+#[derive(Debug)]
+struct Target {
+    addr: SocketAddr,
+    /// The address the program registered, which `RPCBPROC_BCAST` and `RPCBPROC_INDIRECT`
+    /// answer with.
+    universal_address: String,
+}
+
+/// The UDP transport of `caller`'s family, which calls are forwarded on.
+fn caller_netid(caller: SocketAddr) -> Netid {
+    match caller.ip().to_canonical() {
+        IpAddr::V4(_) => Netid::Udp,
+        IpAddr::V6(_) => Netid::Udp6,
+    }
+}
+
+/// Finds the UDP registration `call` is for, in the family of `caller`.
+///
+/// Fails with the status `RPCBPROC_INDIRECT` is answered with when there is none.
+fn target(
+    registry: &dyn Registry,
+    call: &Call,
+    caller: SocketAddr,
+) -> Result<Target, AcceptedStatus<Vec<u8>>> {
+    let RmtCallArgs { prog, vers, .. } = call.args;
+    // Calls through rpcbind to itself could get around checks on who may SET and UNSET
+    if prog == PROGRAM_ID {
+        return Err(AcceptedStatus::ProcedureUnavailable);
+    }
+    let net_id = caller_netid(caller);
+    let key = ProgramKey {
+        program: prog,
+        version: vers,
+        net_id: net_id.clone(),
+    };
+    let Some(description) = registry.lookup(&key) else {
+        let versions: Vec<u32> = registry
+            .dump()
+            .into_iter()
+            .filter(|(key, _)| key.program == prog && key.net_id == net_id)
+            .map(|(key, _)| key.version)
+            .collect();
+        return Err(match (versions.iter().min(), versions.iter().max()) {
+            (Some(&low), Some(&high)) => AcceptedStatus::ProgramMismatch { low, high },
+            _ => AcceptedStatus::ProgramUnavailable,
+        });
+    };
+    let mut addr = description
+        .addr
+        .socket_addr()
+        .ok_or(AcceptedStatus::ProgramUnavailable)?;
+    // Portmapper registrations are on every interface, which this host reaches over loopback
+    if addr.ip().is_unspecified() {
+        addr.set_ip(match addr {
+            SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        });
+    }
+    Ok(Target {
+        addr,
+        universal_address: description.addr.to_string(),
+    })
+}
+
+/// Like rpcbind's `NFORWARD`, how many calls can be forwarded at once. Calls beyond it are
+/// dropped, so a flood of them holds neither memory nor sockets for long.
+const MAX_FORWARDS: usize = 64;
+
+/// Forwards calls to programs from one socket per family, pairing their replies with the calls
+/// by xid.
+#[derive(Debug)]
+pub struct Forwarder {
+    v4: UdpSocket,
+    /// `None` if the host has no IPv6.
+    v6: Option<UdpSocket>,
+    pending: Mutex<HashMap<u32, Pending>>,
+    slots: Arc<Semaphore>,
+    xid: AtomicU32,
+}
+
+/// A call in flight to a program.
+#[derive(Debug)]
+struct Pending {
+    /// Where the call went, which its reply must come from.
+    target: SocketAddr,
+    /// Takes the results of a successful reply, or `None` for any other.
+    results: oneshot::Sender<Option<Vec<u8>>>,
+}
+
+impl Forwarder {
+    /// Binds a socket to forward calls from in each family the host has.
+    pub async fn bind() -> Result<Self> {
+        let v4 = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+        let v6 = UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0)).await.ok();
+        // A random first xid keeps replies from being forged without seeing the calls
+        let seed = RandomState::new().build_hasher().finish() as u32;
+        Ok(Self {
+            v4,
+            v6,
+            pending: Mutex::new(HashMap::new()),
+            slots: Arc::new(Semaphore::new(MAX_FORWARDS)),
+            xid: AtomicU32::new(seed),
+        })
+    }
+
+    /// Hands every reply from a program to the call waiting for it, until a socket fails.
+    pub async fn run(&self) -> Result<()> {
+        match &self.v6 {
+            Some(v6) => tokio::try_join!(self.receive(&self.v4), self.receive(v6)).map(|_| ()),
+            None => self.receive(&self.v4).await,
+        }
+    }
+
+    async fn receive(&self, socket: &UdpSocket) -> Result<()> {
+        let mut datagram = vec![0u8; MAX_DATAGRAM_LEN];
+        loop {
+            let (len, from) = socket.recv_from(&mut datagram).await?;
+            // Anything but a reply from where a call in flight went is ignored
+            let Some((xid, results)) = read_reply(&datagram[..len]) else {
+                continue;
+            };
+            let mut pending = self.pending.lock();
+            let Entry::Occupied(entry) = pending.entry(xid) else {
+                continue;
+            };
+            if entry.get().target != from {
+                continue;
+            }
+            // The call may have just timed out, and no longer be waiting
+            let _ = entry.remove().results.send(results);
+        }
+    }
+
+    /// Calls the program at `target`, returning its results if it answers successfully in time.
+    async fn call_program(&self, call: &Call, target: &Target) -> Result<Option<Vec<u8>>> {
+        let socket = match target.addr {
+            SocketAddr::V4(_) => &self.v4,
+            SocketAddr::V6(_) => self
+                .v6
+                .as_ref()
+                .ok_or_else(|| anyhow!("no IPv6 socket to forward from"))?,
+        };
+        let xid = self.xid.fetch_add(1, Ordering::Relaxed);
+        let (results, receiver) = oneshot::channel();
+        let pending = Pending {
+            target: target.addr,
+            results,
+        };
+        self.pending.lock().insert(xid, pending);
+        let results = async {
+            socket.send_to(&call.datagram(xid)?, target.addr).await?;
+            Ok(timeout(FORWARD_TIMEOUT, receiver)
+                .await
+                .ok()
+                .and_then(Result::ok)
+                .flatten())
+        }
+        .await;
+        self.pending.lock().remove(&xid);
+        results
+    }
+}
+
+/// Reads a datagram from a program as a reply, returning its xid with the results if the call
+/// succeeded, or `None` if it is not a reply.
+pub fn read_reply(datagram: &[u8]) -> Option<(u32, Option<Vec<u8>>)> {
+    let message = RpcMessage::try_from(datagram_record(datagram).ok()?).ok()?;
+    let results = match message.reply_body()? {
+        ReplyBody::Accepted(reply) => match reply.status() {
+            AcceptedStatus::Success(results) => Some(results.to_vec()),
+            _ => None,
+        },
+        ReplyBody::Denied(_) => None,
+    };
+    Some((message.xid(), results))
+}
+
+/// Forwards `call` from `caller` in the background, answering it from `socket` once the program
+/// does.
+///
+/// Like rpcbind, broadcasts are not answered when the call cannot be forwarded or the program
+/// fails, so that only servers running the program reply. Calls arriving while
+/// [`MAX_FORWARDS`] others are in flight are dropped without an answer.
+pub fn start(
+    registry: &dyn Registry,
+    forwarder: &Arc<Forwarder>,
+    socket: Arc<UdpSocket>,
+    caller: SocketAddr,
+    call: Call,
+) {
+    let Ok(slot) = forwarder.slots.clone().try_acquire_owned() else {
+        return;
+    };
+    let target = target(registry, &call, caller);
+    stats().remote_call(
+        call.version,
+        &call.args,
+        &caller_netid(caller),
+        call.procedure == Procedure::Indirect,
+        target.is_ok(),
+    );
+    let forwarder = forwarder.clone();
+    tokio::spawn(async move {
+        if let Err(e) = forward(&forwarder, &socket, caller, call, target).await {
+            eprintln!("Error forwarding a call from {caller}: {e:?}");
+        }
+        drop(slot);
+    });
+}
+
+async fn forward(
+    forwarder: &Forwarder,
+    socket: &UdpSocket,
+    caller: SocketAddr,
+    call: Call,
+    target: Result<Target, AcceptedStatus<Vec<u8>>>,
+) -> Result<()> {
+    let status = match target {
+        Ok(target) => match forwarder.call_program(&call, &target).await? {
+            Some(results) => AcceptedStatus::Success(call.results(&target, results)),
+            None => return Ok(()),
+        },
+        Err(status) if call.procedure == Procedure::Indirect => status,
+        Err(_) => return Ok(()),
+    };
+    let reply = RpcMessage::new(
+        call.xid,
+        MessageType::Reply(ReplyBody::Accepted(AcceptedReply::new(
+            AuthFlavor::<Vec<u8>>::AuthNone(None),
+            status,
+        ))),
+    );
+    socket
+        .send_to(&reply.serialise()?[MSG_HEADER_LEN..], caller)
+        .await?;
+    Ok(())
+}
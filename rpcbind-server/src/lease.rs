@@ -0,0 +1,19 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::registry::Registry;
+
+/// How often the sweeper looks for registrations whose lease ran out.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Removes registrations from `registry` as their leases run out, forever.
+pub async fn sweep(registry: Arc<dyn Registry>) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        registry.expire(Instant::now());
+    }
+}
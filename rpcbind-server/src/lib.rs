@@ -0,0 +1,502 @@
+use std::{path::Path, sync::Arc};
+
+use anyhow::{Result, anyhow, bail};
+use bytes::{BufMut, Bytes, BytesMut};
+use onc_rpc::{
+    AcceptedReply, AcceptedStatus, CallBody, MessageType, ReplyBody, RpcMessage, auth::AuthFlavor,
+};
+use rpcbind_rs::{netconfig::NetConfig, netid::Netid, request::RpcRequest, rpc_names::RpcNames};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, UdpSocket, UnixListener},
+    task::JoinSet,
+};
+
+use crate::{
+    config::Config,
+    error::RPCResult,
+    limits::limits,
+    listener::{DEFAULT_LOCAL_SOCKET, Endpoint, Listener},
+    netconfig::net_config,
+    process_request::process_request,
+    registry::{FileRegistry, InMemoryRegistry, Registry, RegistryEvent},
+    state::{ProgramDescription, ProgramKey},
+    stats::stats,
+};
+
+pub mod config;
+pub mod control;
+mod error;
+pub mod forward;
+pub mod lease;
+mod limits;
+mod listener;
+mod netconfig;
+mod process_request;
+pub mod registry;
+mod stale;
+pub mod state;
+mod stats;
+
+const RPCBIND_PORT: u16 = 111;
+const PROGRAM_ID: u32 = 100000;
+
+/// Registers rpcbind under every netid it listens on, so clients can find each transport.
+fn register_self(registry: &dyn Registry, endpoints: &[Endpoint]) {
+    for endpoint in endpoints {
+        for net_id in &endpoint.net_ids {
+            for version in endpoint.versions.clone() {
+                registry.set(
+                    ProgramKey {
+                        program: PROGRAM_ID,
+                        version,
+                        net_id: net_id.clone(),
+                    },
+                    ProgramDescription {
+                        addr: endpoint.universal_address.clone(),
+                        owner: Some("rpcbind-rs".to_owned()),
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Serves rpcbind on every transport until a listener fails.
+pub async fn run(config: Config) -> Result<()> {
+    netconfig::init(NetConfig::load()?);
+    limits::init(config.limits);
+    let registry: Arc<dyn Registry> = match &config.state_file {
+        Some(path) => Arc::new(
+            FileRegistry::open(path)?
+                .with_owner_replacement(config.owner_replacement)
+                .with_lease_ttl(config.lease_ttl),
+        ),
+        None => Arc::new(
+            InMemoryRegistry::new()
+                .with_owner_replacement(config.owner_replacement)
+                .with_lease_ttl(config.lease_ttl),
+        ),
+    };
+    let names = match &config.rpc_file {
+        Some(path) => RpcNames::from_path(path)?,
+        // Logs fall back to program numbers if the system has no database
+        None => RpcNames::load().unwrap_or_default(),
+    };
+    tokio::spawn(log_changes(registry.clone(), names));
+    if registry.lease_ttl().is_some() {
+        tokio::spawn(lease::sweep(registry.clone()));
+    }
+    if let Some(interval) = config.stale_check {
+        tokio::spawn(stale::check(
+            registry.clone(),
+            interval,
+            config.remove_stale,
+        ));
+    }
+
+    let local_socket = config
+        .local_socket
+        .as_deref()
+        .unwrap_or(Path::new(DEFAULT_LOCAL_SOCKET));
+    let endpoints = listener::bind_all(net_config(), RPCBIND_PORT, local_socket).await?;
+    register_self(registry.as_ref(), &endpoints);
+
+    if let Some(path) = config.control_socket {
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            if let Err(e) = control::serve(&path, registry).await {
+                eprintln!("Error serving control socket {e:?}");
+            }
+        });
+    }
+
+    if let Some(path) = config.admin_socket {
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            if let Err(e) = control::serve_dump(&path, registry).await {
+                eprintln!("Error serving admin socket {e:?}");
+            }
+        });
+    }
+
+    let mut servers = JoinSet::new();
+    for endpoint in endpoints {
+        let registry = registry.clone();
+        servers.spawn(async move {
+            let result = match endpoint.listener {
+                Listener::Tcp(listener) => serve_tcp(listener, registry).await,
+                Listener::Udp(socket) => serve_udp(socket, registry.as_ref()).await,
+                Listener::Local(listener) => serve_local(listener, registry).await,
+            };
+            (endpoint.net_ids, result)
+        });
+    }
+    while let Some(joined) = servers.join_next().await {
+        let (net_ids, result) = joined?;
+        if let Err(e) = result {
+            let net_ids: Vec<_> = net_ids.iter().map(Netid::as_str).collect();
+            eprintln!("Stopped serving {}: {e:?}", net_ids.join(", "));
+        }
+    }
+    bail!("Every listener stopped")
+}
+
+/// Logs every registration change, naming programs the way rpcinfo does.
+async fn log_changes(registry: Arc<dyn Registry>, names: RpcNames) {
+    loop {
+        let mut events = registry.watch();
+        while let Some(event) = events.recv().await {
+            log_change(&event, &names);
+        }
+        eprintln!("Fell behind on registry changes, some were not logged");
+    }
+}
+
+fn log_change(event: &RegistryEvent, names: &RpcNames) {
+    let (action, key, description) = match event {
+        RegistryEvent::Added { key, description } => ("Registered", key, description),
+        RegistryEvent::Removed { key, description } => ("Unregistered", key, description),
+        RegistryEvent::Expired { key, description } => ("Expired", key, description),
+        RegistryEvent::Changed { key, new, .. } => ("Updated", key, new),
+    };
+    let program = match names.name(key.program) {
+        Some(name) => format!("{name} ({})", key.program),
+        None => key.program.to_string(),
+    };
+    println!(
+        "{action} {program} version {} on {} at {}",
+        key.version, key.net_id, description.addr
+    );
+}
+
+async fn serve_tcp(listener: TcpListener, registry: Arc<dyn Registry>) -> Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(stream, registry.as_ref()).await {
+                eprintln!("Error handling client {e:?}");
+            }
+        });
+    }
+}
+
+async fn serve_local(listener: UnixListener, registry: Arc<dyn Registry>) -> Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(stream, registry.as_ref()).await {
+                eprintln!("Error handling client {e:?}");
+            }
+        });
+    }
+}
+
+/// The largest datagram a UDP request can be.
+const MAX_DATAGRAM_LEN: usize = 65535;
+
+/// Answers each datagram with one reply, which like the request has no record marking.
+///
+/// Calls asking for another program to be called are forwarded to it, and answered once it
+/// replies.
+pub async fn serve_udp(socket: UdpSocket, registry: &dyn Registry) -> Result<()> {
+    let forwarder = Arc::new(forward::Forwarder::bind().await?);
+    tokio::select! {
+        result = answer_datagrams(Arc::new(socket), registry, &forwarder) => result,
+        result = forwarder.run() => result,
+    }
+}
+
+async fn answer_datagrams(
+    socket: Arc<UdpSocket>,
+    registry: &dyn Registry,
+    forwarder: &Arc<forward::Forwarder>,
+) -> Result<()> {
+    let mut datagram = vec![0u8; MAX_DATAGRAM_LEN];
+    loop {
+        let (len, peer) = socket.recv_from(&mut datagram).await?;
+        match read_datagram(registry, &datagram[..len]) {
+            Ok(Datagram::Reply(reply)) => {
+                if let Err(e) = socket.send_to(&reply, peer).await {
+                    eprintln!("Error replying to {peer}: {e:?}");
+                }
+            }
+            Ok(Datagram::Forward(call)) => {
+                forward::start(registry, forwarder, socket.clone(), peer, call)
+            }
+            Err(e) => eprintln!("Error handling datagram from {peer}: {e:?}"),
+        }
+    }
+}
+
+/// How [`serve_udp`] answers a datagram.
+#[derive(Debug)]
+pub enum Datagram {
+    /// Answered at once with this reply, which has no record marking.
+    Reply(Vec<u8>),
+    /// Answered once the program this call is for does.
+    Forward(forward::Call),
+}
+
+/// Reads a call sent over UDP, answering it unless it is to be forwarded.
+pub fn read_datagram(registry: &dyn Registry, datagram: &[u8]) -> Result<Datagram> {
+    let message = decode_call(datagram_record(datagram)?)?;
+    Ok(match forward::Call::from_message(&message) {
+        Some(call) => Datagram::Forward(call),
+        None => {
+            let mut reply = reply(registry, &message)?;
+            reply.drain(..MSG_HEADER_LEN);
+            Datagram::Reply(reply)
+        }
+    })
+}
+
+/// Adds record marking to a datagram, which is a whole message in one fragment.
+fn datagram_record(datagram: &[u8]) -> Result<Bytes> {
+    let len = u32::try_from(datagram.len())
+        .ok()
+        .filter(|len| len & LAST_FRAGMENT == 0)
+        .ok_or_else(|| anyhow!("datagram is too long"))?;
+    let mut record = BytesMut::with_capacity(MSG_HEADER_LEN + datagram.len());
+    record.put_u32(LAST_FRAGMENT | len);
+    record.put_slice(datagram);
+    Ok(record.freeze())
+}
+
+const MSG_HEADER_LEN: usize = 4;
+/// Set in a record marking header on the last fragment of a record.
+const LAST_FRAGMENT: u32 = 1 << 31;
+/// Room in a record for the message header and credentials, besides the call arguments.
+const MAX_CALL_OVERHEAD: usize = 4096;
+
+/// Answers every record sent on a connection until the client closes it.
+pub async fn handle_client(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    registry: &dyn Registry,
+) -> Result<()> {
+    while let Some(record) = read_record(&mut stream).await? {
+        let reply = handle_message(registry, record)?;
+        stream.write_all(&reply).await?;
+    }
+    Ok(())
+}
+
+/// Reads the fragments of the next record, returning them as one fragment with its header.
+///
+/// Returns `None` if the stream ends before a new record starts. Records longer than any call
+/// within the argument limits are refused before their fragments are buffered.
+async fn read_record(stream: &mut (impl AsyncRead + Unpin)) -> Result<Option<Bytes>> {
+    let max_len = limits().args.saturating_add(MAX_CALL_OVERHEAD);
+    let mut record = BytesMut::from([0u8; MSG_HEADER_LEN].as_slice());
+    loop {
+        let mut header = [0u8; MSG_HEADER_LEN];
+        match stream.read_exact(&mut header).await {
+            Err(e)
+                if e.kind() == std::io::ErrorKind::UnexpectedEof
+                    && record.len() == MSG_HEADER_LEN =>
+            {
+                return Ok(None);
+            }
+            result => result?,
+        };
+        let header = u32::from_be_bytes(header);
+        let start = record.len();
+        let len = start - MSG_HEADER_LEN + (header & !LAST_FRAGMENT) as usize;
+        if len > max_len {
+            bail!("record of at least {len} bytes is over the limit of {max_len}");
+        }
+        record.resize(MSG_HEADER_LEN + len, 0);
+        stream.read_exact(&mut record[start..]).await?;
+        if header & LAST_FRAGMENT != 0 {
+            break;
+        }
+    }
+    let len =
+        u32::try_from(record.len() - MSG_HEADER_LEN).map_err(|_| anyhow!("record is too long"))?;
+    record[..MSG_HEADER_LEN].copy_from_slice(&(LAST_FRAGMENT | len).to_be_bytes());
+    Ok(Some(record.freeze()))
+}
+
+/// Answers a call given as a record, returning the reply as a record.
+///
+/// Calls are only forwarded over UDP, so on other transports the procedures that forward are
+/// unavailable.
+fn handle_message(registry: &dyn Registry, record: Bytes) -> Result<Vec<u8>> {
+    reply(registry, &decode_call(record)?)
+}
+
+/// Decodes a record, counting it towards `RPCBPROC_GETSTAT` if it is a call to rpcbind.
+fn decode_call(record: Bytes) -> Result<RpcMessage<Bytes, Bytes>> {
+    let message = RpcMessage::try_from(record)
+        .map_err(|e| anyhow!("Got an error when decoding message {e:?}"))?;
+    if let Some(body) = message.call_body()
+        && body.program() == PROGRAM_ID
+    {
+        stats().call(body.program_version(), body.procedure());
+    }
+    Ok(message)
+}
+
+/// Answers `message`, which is a call, returning the reply as a record.
+fn reply(registry: &dyn Registry, message: &RpcMessage<Bytes, Bytes>) -> Result<Vec<u8>> {
+    let xid = message.xid();
+    let rpc_request = message
+        .call_body()
+        .ok_or_else(|| anyhow!("Server got response packet"))?;
+    let body = match handle_request(registry, rpc_request) {
+        Ok(status) => ReplyBody::Accepted(AcceptedReply::new(
+            AuthFlavor::<Vec<u8>>::AuthNone(None),
+            status,
+        )),
+        Err(e) => e.into(),
+    };
+
+    let reply = RpcMessage::new(xid, MessageType::Reply(body));
+    Ok(reply.serialise()?)
+}
+
+fn handle_request(
+    registry: &dyn Registry,
+    body: &CallBody<impl AsRef<[u8]>, impl AsRef<[u8]>>,
+) -> RPCResult<AcceptedStatus<Vec<u8>>> {
+    let request = RpcRequest::from_body_with_limits(body, limits())
+        .inspect_err(|e| eprintln!("Rejecting request: {e}"))?;
+    let response = process_request(registry, &request)?;
+    Ok(AcceptedStatus::Success(response.encode_payload(registry)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, sync::Arc};
+
+    use onc_rpc::{AcceptedStatus, CallBody, MessageType, ReplyBody, RpcMessage, auth::AuthFlavor};
+    use rpcbind_rs::{
+        client::{Protocol, RpcBindClient, RpcBindVersion},
+        request::{PortMapperRequest, RpcRequest},
+        xdr_types::port_mapper::CallArgs,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::UdpSocket,
+    };
+
+    use super::{LAST_FRAGMENT, MSG_HEADER_LEN, handle_client, handle_message, serve_udp};
+    use crate::registry::{InMemoryRegistry, Registry};
+
+    fn null_call(xid: u32) -> Vec<u8> {
+        let body = CallBody::new(
+            100000,
+            4,
+            0,
+            AuthFlavor::<Vec<u8>>::AuthNone(None),
+            AuthFlavor::AuthNone(None),
+            Vec::new(),
+        );
+        let mut record = RpcMessage::new(xid, MessageType::Call(body))
+            .serialise()
+            .unwrap();
+        // Drop the record header, leaving the bare message
+        record.drain(..MSG_HEADER_LEN);
+        record
+    }
+
+    #[tokio::test]
+    async fn answers_every_record_on_a_connection() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let registry = InMemoryRegistry::new();
+        let server = async { handle_client(server, &registry).await.unwrap() };
+
+        let client = async {
+            // The first call is split across two fragments
+            let message = null_call(1);
+            let (first, second) = message.split_at(10);
+            for (fragment, last) in [(first, 0), (second, LAST_FRAGMENT)] {
+                client
+                    .write_all(&(last | fragment.len() as u32).to_be_bytes())
+                    .await
+                    .unwrap();
+                client.write_all(fragment).await.unwrap();
+            }
+            let message = null_call(2);
+            client
+                .write_all(&(LAST_FRAGMENT | message.len() as u32).to_be_bytes())
+                .await
+                .unwrap();
+            client.write_all(&message).await.unwrap();
+            client.shutdown().await.unwrap();
+
+            let mut replies = Vec::new();
+            client.read_to_end(&mut replies).await.unwrap();
+            let mut xids = Vec::new();
+            while !replies.is_empty() {
+                let len = u32::from_be_bytes(replies[..4].try_into().unwrap()) & !LAST_FRAGMENT;
+                let record: Vec<u8> = replies.drain(..MSG_HEADER_LEN + len as usize).collect();
+                let reply = RpcMessage::try_from(record.as_slice()).unwrap();
+                xids.push(reply.xid());
+            }
+            assert_eq!(xids, [1, 2]);
+        };
+        tokio::join!(server, client);
+    }
+
+    #[tokio::test]
+    async fn serves_udp() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr: SocketAddr = socket.local_addr().unwrap();
+        let registry: Arc<dyn Registry> = Arc::new(InMemoryRegistry::new());
+        let server = tokio::spawn({
+            let registry = registry.clone();
+            async move { serve_udp(socket, registry.as_ref()).await }
+        });
+
+        let mut client = RpcBindClient::connect(addr, Protocol::Udp, RpcBindVersion::V4)
+            .await
+            .unwrap();
+        let rpcb = rpcbind_rs::xdr_types::rpcbind::RPCB {
+            r_prog: 100003,
+            r_vers: 3,
+            r_netid: "udp".to_owned(),
+            r_addr: "127.0.0.1.8.1".to_owned(),
+            r_owner: "nfs".to_owned(),
+        };
+        assert!(client.set(rpcb.clone()).await.unwrap());
+        assert_eq!(client.get_addr(rpcb).await.unwrap(), "127.0.0.1.8.1");
+        server.abort();
+    }
+
+    #[test]
+    fn callit_is_unavailable() {
+        // Used to hit a todo!() and take the server down
+        let request = RpcRequest::V2(PortMapperRequest::CallIt(CallArgs {
+            prog: 100003,
+            vers: 3,
+            proc: 0,
+            args: Vec::new(),
+        }));
+        let record = RpcMessage::new(7, MessageType::Call(request.to_call_body().unwrap()))
+            .serialise()
+            .unwrap();
+        let reply = handle_message(&InMemoryRegistry::new(), record.into()).unwrap();
+        let reply = RpcMessage::try_from(reply.as_slice()).unwrap();
+        let Some(ReplyBody::Accepted(reply)) = reply.reply_body() else {
+            panic!("call was not accepted");
+        };
+        assert_eq!(reply.status(), &AcceptedStatus::ProcedureUnavailable);
+    }
+
+    #[tokio::test]
+    async fn refuses_oversized_records() {
+        let (mut client, server) = tokio::io::duplex(1024);
+        // A fragment claiming 2GiB is refused before anything is buffered
+        client
+            .write_all(&(LAST_FRAGMENT | 0x7fff_ffff).to_be_bytes())
+            .await
+            .unwrap();
+        let error = handle_client(server, &InMemoryRegistry::new())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("over the limit"), "{error}");
+    }
+}
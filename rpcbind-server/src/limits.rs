@@ -0,0 +1,18 @@
+use std::sync::OnceLock;
+
+use rpcbind_rs::request::Limits;
+
+static LIMITS: OnceLock<Limits> = OnceLock::new();
+
+/// Sets the bounds on call arguments for the rest of the process, before any request is
+/// handled.
+pub fn init(limits: Limits) {
+    LIMITS
+        .set(limits)
+        .expect("limits are only initialised once");
+}
+
+/// The limits given to [`init`], or the library defaults if it was never called.
+pub fn limits() -> &'static Limits {
+    LIMITS.get_or_init(Limits::default)
+}
@@ -0,0 +1,173 @@
+use std::{
+    fs, io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6},
+    ops::RangeInclusive,
+    os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::{
+            fs::{FileTypeExt, PermissionsExt},
+            net::UnixStream,
+        },
+    },
+    path::Path,
+};
+
+use anyhow::{Context, Result, bail};
+use nix::sys::socket::{
+    AddressFamily, Backlog, SockFlag, SockType, SockaddrIn6, bind, listen, setsockopt, socket,
+    sockopt,
+};
+use rpcbind_rs::{
+    netconfig::{NetConfig, NetConfigEntry, Semantics},
+    netid::Netid,
+};
+use tokio::net::{TcpListener, UdpSocket, UnixListener};
+
+use rpcbind_rs::universal_address::UniversalAddress;
+
+pub const DEFAULT_LOCAL_SOCKET: &str = "/var/run/rpcbind.sock";
+
+/// A socket requests are accepted on.
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(TcpListener),
+    Udp(UdpSocket),
+    Local(UnixListener),
+}
+
+/// A listener together with the netids it serves and the address rpcbind registers itself at.
+#[derive(Debug)]
+pub struct Endpoint {
+    pub net_ids: Vec<Netid>,
+    pub universal_address: UniversalAddress,
+    /// The rpcbind versions offered, as the portmapper protocol only exists for IPv4.
+    pub versions: RangeInclusive<u32>,
+    pub listener: Listener,
+}
+
+/// Binds a listener for every transport in `config` that rpcbind can serve, like the C
+/// daemon does for the visible entries and the local transports.
+///
+/// Transports that fail to bind, like IPv6 on a host without it, are skipped with a log.
+pub async fn bind_all(config: &NetConfig, port: u16, local_socket: &Path) -> Result<Vec<Endpoint>> {
+    let mut endpoints: Vec<Endpoint> = Vec::new();
+    let entries = config
+        .entries()
+        .iter()
+        .filter(|entry| entry.is_visible() || entry.protofmly == "loopback");
+    for entry in entries {
+        if entry.protofmly == "loopback" {
+            // Every loopback netid is served by the one socket
+            if let Some(endpoint) = endpoints
+                .iter_mut()
+                .find(|endpoint| matches!(endpoint.listener, Listener::Local(_)))
+            {
+                endpoint.net_ids.push(entry.netid.as_str().into());
+                continue;
+            }
+        }
+        match bind_entry(entry, port, local_socket).await {
+            Ok(Some(endpoint)) => endpoints.push(endpoint),
+            Ok(None) => println!("Not listening on {}: unsupported transport", entry.netid),
+            Err(e) => eprintln!("Not listening on {}: {e:#}", entry.netid),
+        }
+    }
+    anyhow::ensure!(!endpoints.is_empty(), "no transport could be bound");
+    Ok(endpoints)
+}
+
+async fn bind_entry(
+    entry: &NetConfigEntry,
+    port: u16,
+    local_socket: &Path,
+) -> Result<Option<Endpoint>> {
+    let stream = matches!(entry.semantics, Semantics::Cots | Semantics::CotsOrd);
+    let listener = match (entry.protofmly.as_str(), entry.proto.as_deref()) {
+        ("inet", Some("tcp")) if stream => {
+            Listener::Tcp(TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).await?)
+        }
+        ("inet", Some("udp")) if entry.semantics == Semantics::Clts => {
+            Listener::Udp(UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)).await?)
+        }
+        ("inet6", Some("tcp")) if stream => {
+            let socket = std::net::TcpListener::from(bind_ipv6_only(port, SockType::Stream)?);
+            Listener::Tcp(TcpListener::from_std(socket)?)
+        }
+        ("inet6", Some("udp")) if entry.semantics == Semantics::Clts => {
+            let socket = std::net::UdpSocket::from(bind_ipv6_only(port, SockType::Datagram)?);
+            Listener::Udp(UdpSocket::from_std(socket)?)
+        }
+        ("loopback", _) if stream => Listener::Local(bind_local(local_socket)?),
+        _ => return Ok(None),
+    };
+
+    let (universal_address, versions) = match &listener {
+        Listener::Tcp(listener) => ip_address(entry, listener.local_addr()?),
+        Listener::Udp(socket) => ip_address(entry, socket.local_addr()?),
+        Listener::Local(_) => (UniversalAddress::Local(local_socket.to_owned()), 3..=4),
+    };
+    Ok(Some(Endpoint {
+        net_ids: vec![entry.netid.as_str().into()],
+        universal_address,
+        versions,
+        listener,
+    }))
+}
+
+fn ip_address(entry: &NetConfigEntry, addr: SocketAddr) -> (UniversalAddress, RangeInclusive<u32>) {
+    let versions = if entry.protofmly == "inet" {
+        2..=4
+    } else {
+        3..=4
+    };
+    (addr.into(), versions)
+}
+
+/// Binds an IPv6 socket that does not also take IPv4 traffic, which has its own socket.
+fn bind_ipv6_only(port: u16, kind: SockType) -> Result<OwnedFd> {
+    let fd = socket(
+        AddressFamily::Inet6,
+        kind,
+        SockFlag::SOCK_CLOEXEC | SockFlag::SOCK_NONBLOCK,
+        None,
+    )?;
+    setsockopt(&fd, sockopt::Ipv6V6Only, &true)?;
+    setsockopt(&fd, sockopt::ReuseAddr, &true)?;
+    bind(
+        fd.as_raw_fd(),
+        &SockaddrIn6::from(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, port, 0, 0)),
+    )?;
+    if kind == SockType::Stream {
+        listen(&fd, Backlog::MAXCONN)?;
+    }
+    Ok(fd)
+}
+
+/// Removes the socket a previous run left at `path`, which would make binding it fail.
+///
+/// Fails rather than remove anything else found there, or a socket another server still
+/// accepts connections on.
+pub fn remove_stale_socket(path: &Path) -> Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).context(format!("inspecting {}", path.display())),
+    };
+    if !metadata.file_type().is_socket() {
+        bail!("{} exists and is not a socket", path.display());
+    }
+    match UnixStream::connect(path) {
+        Ok(_) => bail!("{} is in use by another server", path.display()),
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {}
+        Err(e) => return Err(e).context(format!("connecting to {}", path.display())),
+    }
+    fs::remove_file(path).context(format!("removing {}", path.display()))
+}
+
+fn bind_local(path: &Path) -> Result<UnixListener> {
+    remove_stale_socket(path)?;
+    let listener = UnixListener::bind(path).context(format!("binding {}", path.display()))?;
+    // Any local user may register their services
+    fs::set_permissions(path, fs::Permissions::from_mode(0o666))?;
+    Ok(listener)
+}
@@ -1,96 +1,56 @@
-use std::{
-    collections::hash_map::Entry,
-    net::{Ipv4Addr, SocketAddrV4},
-};
+use rpcbind_rs::{request::RpcRequest, response::RpcResponse, xdr_types::codec::XdrWriter};
 
-use facet::Facet;
-use onc_rpc::AcceptedStatus;
-use rpcbind_rs::{RpcBindResult, request::RpcRequest};
-
-use crate::{
-    RPCResult, STATE,
-    error::AcceptedStatusError,
-    state::{ProgramDescription, ProgramKey},
-};
+use crate::{RPCResult, registry::Registry, state::make_rpcb, stats::stats};
 
 mod portmapper;
 mod rpcbind;
 
-type RequestResult = RPCResult<Vec<u8>>;
-
-pub fn process_request(request: &RpcRequest) -> RequestResult {
-    match request {
-        RpcRequest::V2(port_mapper_request) => portmapper::process_request(port_mapper_request),
-        RpcRequest::V3(rpc_bind_request) | RpcRequest::V4(rpc_bind_request) => {
-            rpcbind::process_request(rpc_bind_request)
-        }
-    }
+/// The answer to a call, before it is encoded as the payload of the reply.
+#[derive(Debug)]
+pub enum Reply {
+    Response(RpcResponse),
+    /// A portmapper DUMP of the registry, read as it is encoded.
+    PortMapperDump,
+    /// An rpcbind DUMP of the registry in version 3 or 4 alike, read as it is encoded.
+    RpcBindDump,
 }
 
-fn set(key: ProgramKey, val: ProgramDescription) -> RequestResult {
-    let mut state = STATE.write();
-    let entry = state.entry(key);
-    let result = match entry {
-        Entry::Occupied(_) => false,
-        Entry::Vacant(vacant_entry) => {
-            vacant_entry.insert(val);
-            true
+impl Reply {
+    /// Encodes the payload of the reply, reading a DUMP from `registry`.
+    pub fn encode_payload(&self, registry: &dyn Registry) -> Vec<u8> {
+        // A registry can hold a very large number of entries, so a DUMP is written straight
+        // from the registry into the reply one entry at a time, rather than copied out first
+        let mut writer = XdrWriter::new();
+        match self {
+            Self::Response(response) => return response.encode_payload(),
+            Self::PortMapperDump => registry.for_each(&mut |key, description| {
+                if let Some(mapping) = portmapper::mapping(key, description) {
+                    writer.list_entry(&mapping);
+                }
+            }),
+            Self::RpcBindDump => registry.for_each(&mut |key, description| {
+                writer.list_entry(&make_rpcb(key, description));
+            }),
         }
-    };
-    serialize_result(&result)
-}
-
-fn decode_universal_address(universal_address: &str) -> RpcBindResult<SocketAddrV4> {
-    // See https://datatracker.ietf.org/doc/html/rfc5665#autoid-13
-    #[inline]
-    fn take_byte<'a, 'b>(iter: &'a mut impl Iterator<Item = &'b str>) -> RpcBindResult<u8> {
-        let byte_str = iter.next().ok_or(AcceptedStatus::GarbageArgs)?;
-        byte_str
-            .parse::<u8>()
-            .map_err(|_| AcceptedStatus::GarbageArgs)
+        writer.end_list();
+        writer.into_bytes()
     }
-
-    let mut split = universal_address.split('.');
-
-    let mut ip_addr = split.by_ref().take(4);
-    let (a, b, c, d) = (
-        take_byte(&mut ip_addr)?,
-        take_byte(&mut ip_addr)?,
-        take_byte(&mut ip_addr)?,
-        take_byte(&mut ip_addr)?,
-    );
-    let ip_addr = Ipv4Addr::new(a, b, c, d);
-
-    let port = [take_byte(&mut split)?, take_byte(&mut split)?];
-
-    Ok(SocketAddrV4::new(ip_addr, u16::from_be_bytes(port)))
 }
 
-#[inline]
-fn serialize_result<'f, Res: Facet<'f>>(res: &'f Res) -> RequestResult {
-    Ok(facet_xdr::to_vec(res).map_err(|_| AcceptedStatusError::SystemError)?)
-}
-
-#[cfg(test)]
-mod tests {
-    use std::net::{Ipv4Addr, SocketAddrV4};
-
-    use super::decode_universal_address;
-    use crate::state::ProgramDescription;
-
-    #[test]
-    fn address_decoder_test() {
-        let test_addr = SocketAddrV4::new(Ipv4Addr::new(0x01, 0x23, 0x45, 0x67), 0xB3A2);
-        let description = ProgramDescription {
-            addr: test_addr,
-            owner: None,
-        };
-
-        let universal_address = description.universal_address();
-        assert_eq!(universal_address, "1.35.69.103.179.162");
-
-        let decoded_addr = decode_universal_address(&universal_address).unwrap();
-
-        assert_eq!(test_addr, decoded_addr);
+pub fn process_request(registry: &dyn Registry, request: &RpcRequest) -> RPCResult<Reply> {
+    let reply = match request {
+        RpcRequest::V2(port_mapper_request) => {
+            portmapper::process_request(registry, port_mapper_request)
+        }
+        RpcRequest::V3(rpc_bind_request) => {
+            rpcbind::process_request(registry, rpc_bind_request, RpcResponse::V3)
+        }
+        RpcRequest::V4(rpc_bind_request) => {
+            rpcbind::process_request(registry, rpc_bind_request, RpcResponse::V4)
+        }
+    }?;
+    if let Reply::Response(response) = &reply {
+        stats().answered(request, response);
     }
+    Ok(reply)
 }
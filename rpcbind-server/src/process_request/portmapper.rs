@@ -1,79 +1,78 @@
 use std::net::{Ipv4Addr, SocketAddrV4};
 
 use rpcbind_rs::{
+    netid::Netid,
     request::PortMapperRequest,
-    xdr_types::{
-        CreateList,
-        port_mapper::{Mapping, PMapList},
-    },
+    response::{PortMapperResponse, RpcResponse},
+    xdr_types::port_mapper::Mapping,
 };
 
-use super::{RequestResult, serialize_result};
+use super::Reply;
+
 use crate::{
-    STATE,
+    RPCResult,
     error::AcceptedStatusError,
+    registry::Registry,
     state::{ProgramDescription, ProgramKey},
 };
 
-pub fn process_request(request: &PortMapperRequest) -> RequestResult {
-    #[allow(unused_variables)]
-    match request {
-        PortMapperRequest::Null => Ok(Vec::new()),
-        PortMapperRequest::Set(mapping) => set(mapping),
-        PortMapperRequest::Unset(mapping) => unset(mapping),
-        PortMapperRequest::GetPort(mapping) => get_port(mapping),
-        PortMapperRequest::Dump => dump(),
-        PortMapperRequest::CallIt(call_args) => todo!(),
-    }
+pub fn process_request(registry: &dyn Registry, request: &PortMapperRequest) -> RPCResult<Reply> {
+    let response = match request {
+        PortMapperRequest::Null => PortMapperResponse::Null,
+        PortMapperRequest::Set(mapping) => PortMapperResponse::Set(set(registry, mapping)?),
+        PortMapperRequest::Unset(mapping) => PortMapperResponse::Unset(unset(registry, mapping)),
+        PortMapperRequest::GetPort(mapping) => {
+            PortMapperResponse::GetPort(get_port(registry, mapping))
+        }
+        PortMapperRequest::Dump => return Ok(Reply::PortMapperDump),
+        PortMapperRequest::CallIt(_) => {
+            // Calls are only forwarded over UDP, before they get here
+            return Err(AcceptedStatusError::ProcedureUnavailable.into());
+        }
+    };
+    Ok(Reply::Response(RpcResponse::V2(response)))
 }
 
-fn set(mapping: &Mapping) -> RequestResult {
-    let key = ProgramKey::from(mapping);
+fn set(registry: &dyn Registry, mapping: &Mapping) -> RPCResult<bool> {
+    // Like the C daemon, only TCP and UDP mappings can be registered
+    let Some(key) = ProgramKey::from_mapping(mapping) else {
+        return Ok(false);
+    };
     let port = mapping
         .port
         .try_into()
         .map_err(|_| AcceptedStatusError::GarbageArgs)?;
     let val = ProgramDescription {
-        addr: SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port),
+        addr: SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port).into(),
         owner: None,
     };
 
-    super::set(key, val)
+    Ok(registry.set(key, val))
 }
 
-fn unset(mapping: &Mapping) -> RequestResult {
-    let mut state = STATE.write();
-    let key = ProgramKey::from(mapping);
-    // Protocol field ignored
-    state.retain(|k, _| !(k.version == key.version && k.program == key.program));
-    serialize_result(&true)
+fn unset(registry: &dyn Registry, mapping: &Mapping) -> bool {
+    // Like the C daemon, the protocol is ignored and both IPv4 transports are unset, reporting
+    // whether either was registered
+    let tcp = registry.unset(mapping.prog, mapping.vers, Some(&Netid::Tcp));
+    let udp = registry.unset(mapping.prog, mapping.vers, Some(&Netid::Udp));
+    tcp || udp
 }
 
-fn get_port(mapping: &Mapping) -> RequestResult {
-    let state = STATE.read();
-    let key = ProgramKey::from(mapping);
-    let ret_val = match state.get(&key) {
-        Some(val) => val.addr.port(),
+fn get_port(registry: &dyn Registry, mapping: &Mapping) -> u32 {
+    let key = ProgramKey::from_mapping(mapping);
+    let ret_val = match key.and_then(|key| registry.lookup(&key)) {
+        Some(val) => val.addr.port().unwrap_or_default(),
         None => 0,
     };
-    serialize_result(&u32::from(ret_val))
+    ret_val.into()
 }
 
-fn dump() -> RequestResult {
-    let state = STATE.read();
-    let mappings = state.iter().filter_map(|(key, description)| {
-        let prot = key.portmapper_description()?;
-        Some(Mapping {
-            prog: key.program,
-            vers: key.version,
-            prot,
-            port: description.addr.port().into(),
-        })
-    });
-    let list = PMapList::create_list(mappings);
-
-    match list {
-        Some(list) => serialize_result(&[list]),
-        None => serialize_result(&Option::<PMapList>::None),
-    }
+/// The mapping a registration is dumped as, if it is on a transport portmapper knows.
+pub(super) fn mapping(key: &ProgramKey, description: &ProgramDescription) -> Option<Mapping> {
+    Some(Mapping {
+        prog: key.program,
+        vers: key.version,
+        prot: key.net_id.portmapper_protocol()?,
+        port: description.addr.port()?.into(),
+    })
 }
@@ -1,86 +1,121 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use rpcbind_rs::{
+    netid::Netid,
     request::RpcBindRequest,
-    xdr_types::{
-        CreateList,
-        rpcbind::{RPCB, RPList},
-    },
+    response::{RpcBindResponse, RpcResponse},
+    universal_address::{UniversalAddress, UniversalAddressError},
+    xdr_types::rpcbind::{Entry, EntryList, RPCB},
 };
 
-use super::{RequestResult, decode_universal_address, serialize_result};
+use super::Reply;
+
 use crate::{
-    STATE,
+    RPCResult,
     error::AcceptedStatusError,
-    state::{ProgramDescription, ProgramKey, make_rpcb},
+    netconfig::net_config,
+    registry::Registry,
+    state::{ProgramDescription, ProgramKey},
+    stats::stats,
 };
 
-pub fn process_request(request: &RpcBindRequest) -> RequestResult {
-    #[allow(unused_variables)]
-    match request {
-        RpcBindRequest::Set(rpcb) => set(rpcb),
-        RpcBindRequest::Unset(rpcb) => unset(rpcb),
-        RpcBindRequest::GetAddr(rpcb) => get_addr(rpcb),
-        RpcBindRequest::Dump => dump(),
-        RpcBindRequest::GetTime => get_time(),
-        RpcBindRequest::UADDR2TADDR(_) => todo!(),
-        RpcBindRequest::TADDR2UADDR(netbuf) => todo!(),
-        RpcBindRequest::GETVERSADDR(rpcb) => todo!(),
-        RpcBindRequest::Indirect(rpcb_rmtcallargs) => todo!(),
-        RpcBindRequest::GetAddrList(rpcb) => todo!(),
-        RpcBindRequest::GetStat => {
-            // This call seems really annouing to do and a minor security risk
-            Err(AcceptedStatusError::ProcedureUnavailable.into())
+/// Answers `request`, whose result is wrapped by `version` in the version it was made in.
+pub fn process_request(
+    registry: &dyn Registry,
+    request: &RpcBindRequest,
+    version: fn(RpcBindResponse) -> RpcResponse,
+) -> RPCResult<Reply> {
+    let response = match request {
+        RpcBindRequest::Null => RpcBindResponse::Null,
+        RpcBindRequest::Set(rpcb) => RpcBindResponse::Set(set(registry, rpcb)?),
+        RpcBindRequest::Unset(rpcb) => RpcBindResponse::Unset(unset(registry, rpcb)),
+        RpcBindRequest::GetAddr(rpcb) => RpcBindResponse::GetAddr(get_addr(registry, rpcb)),
+        RpcBindRequest::Dump => return Ok(Reply::RpcBindDump),
+        RpcBindRequest::GetTime => RpcBindResponse::GetTime(get_time()?),
+        RpcBindRequest::Broadcast(_) | RpcBindRequest::Indirect(_) => {
+            // Calls are only forwarded over UDP, before they get here
+            return Err(AcceptedStatusError::ProcedureUnavailable.into());
         }
-    }
+        RpcBindRequest::UADDR2TADDR(_)
+        | RpcBindRequest::TADDR2UADDR(_)
+        | RpcBindRequest::GETVERSADDR(_) => {
+            // Decoded, but not implemented by this server yet
+            return Err(AcceptedStatusError::ProcedureUnavailable.into());
+        }
+        RpcBindRequest::GetAddrList(rpcb) => {
+            RpcBindResponse::GetAddrList(get_addr_list(registry, rpcb))
+        }
+        RpcBindRequest::GetStat => RpcBindResponse::GetStat(Box::new(stats().snapshot())),
+    };
+    Ok(Reply::Response(version(response)))
 }
 
-fn set(rpcb: &RPCB) -> RequestResult {
+fn set(registry: &dyn Registry, rpcb: &RPCB) -> RPCResult<bool> {
     let key = ProgramKey::from(rpcb);
+    let addr = match UniversalAddress::for_netid(net_config(), &key.net_id, &rpcb.r_addr) {
+        // Netids missing from netconfig are still accepted in any family
+        Err(UniversalAddressError::UnknownNetid(_)) => rpcb.r_addr.parse(),
+        result => result,
+    }
+    .map_err(|_| AcceptedStatusError::GarbageArgs)?;
     let val = ProgramDescription {
-        addr: decode_universal_address(&rpcb.r_addr)?,
+        addr,
         owner: (!rpcb.r_owner.is_empty()).then(|| rpcb.r_owner.clone()),
     };
-    super::set(key, val)
+    // Only an owner can renew a lease, so registrations without one never expire
+    Ok(match registry.lease_ttl() {
+        Some(ttl) if val.owner.is_some() => registry.set_leased(key, val, ttl),
+        _ => registry.set(key, val),
+    })
 }
 
-fn unset(rpcb: &RPCB) -> RequestResult {
-    let mut state = STATE.write();
-    let removed = if rpcb.r_netid.is_empty() {
-        let original_length = state.len();
-        state.retain(|k, _| !(rpcb.r_prog == k.program && rpcb.r_vers == k.version));
-        state.len() < original_length
-    } else {
-        let key = ProgramKey::from(rpcb);
-        state.remove(&key).is_some()
-    };
-    serialize_result(&removed)
+fn unset(registry: &dyn Registry, rpcb: &RPCB) -> bool {
+    let net_id = (!rpcb.r_netid.is_empty()).then(|| rpcb.netid());
+    registry.unset(rpcb.r_prog, rpcb.r_vers, net_id.as_ref())
 }
 
-fn get_addr(rpcb: &RPCB) -> RequestResult {
-    let state = STATE.read();
+fn get_addr(registry: &dyn Registry, rpcb: &RPCB) -> String {
     let key = ProgramKey::from(rpcb);
-    serialize_result(&match state.get(&key) {
-        Some(entry) => entry.universal_address(),
+    match registry.lookup(&key) {
+        Some(entry) => entry.addr.to_string(),
         None => String::new(),
-    })
+    }
 }
 
-fn dump() -> RequestResult {
-    let state = STATE.read();
-    let list = RPList::create_list(state.iter().map(make_rpcb));
-
-    match list {
-        Some(list) => serialize_result(&[list]),
-        None => serialize_result(&Option::<RPList>::None),
-    }
+/// Every address the program and version of `rpcb` is registered at, with the netconfig entry
+/// of its transport.
+///
+/// Entries are listed in netconfig order, which is the order rpcbind registers itself in. Like
+/// GETADDR, and unlike rpcbind, addresses are not merged with the caller's, and transports of
+/// every family are listed whichever one the call came in on.
+fn get_addr_list(registry: &dyn Registry, rpcb: &RPCB) -> EntryList {
+    net_config()
+        .entries()
+        .iter()
+        .filter_map(|transport| {
+            let key = ProgramKey {
+                program: rpcb.r_prog,
+                version: rpcb.r_vers,
+                net_id: Netid::from(transport.netid.as_str()),
+            };
+            let description = registry.lookup(&key)?;
+            Some(Entry {
+                r_maddr: description.addr.to_string(),
+                r_nc_netid: transport.netid.clone(),
+                r_nc_semantics: transport.semantics.code(),
+                r_nc_protofmly: transport.protofmly.clone(),
+                // As libtirpc reads netconfig, a missing protocol is left as written
+                r_nc_proto: transport.proto.clone().unwrap_or_else(|| "-".to_owned()),
+            })
+        })
+        .collect()
 }
 
-fn get_time() -> RequestResult {
+fn get_time() -> RPCResult<u32> {
     let since_epoch = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map_err(|_| AcceptedStatusError::SystemError)?
         .as_secs();
     // RPCBIND seems subject to the 2038 bug
-    serialize_result(&u32::try_from(since_epoch).map_err(|_| AcceptedStatusError::SystemError)?)
+    Ok(u32::try_from(since_epoch).map_err(|_| AcceptedStatusError::SystemError)?)
 }
@@ -0,0 +1,122 @@
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use rpcbind_rs::netid::Netid;
+use serde::Serialize;
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+
+use crate::state::{ProgramDescription, ProgramKey};
+
+mod file;
+mod in_memory;
+
+pub use file::FileRegistry;
+pub use in_memory::InMemoryRegistry;
+
+/// Storage for the registrations served by rpcbind.
+///
+/// Request handlers only talk to this trait, so the backing store can be
+/// swapped without touching the protocol code.
+pub trait Registry: Send + Sync {
+    /// Registers `description` under `key`.
+    ///
+    /// Like rpcbind, returns `false` without modifying anything if `key` is already registered,
+    /// unless the registry was built to let owners replace their registrations.
+    fn set(&self, key: ProgramKey, description: ProgramDescription) -> bool;
+
+    /// How long registrations made over rpcbind with an owner last unless renewed, or `None`
+    /// if they stay until unset.
+    fn lease_ttl(&self) -> Option<Duration>;
+
+    /// Like [`Registry::set`], but the registration only lasts `ttl`.
+    ///
+    /// Setting the same registration again from its owner renews the lease and returns `true`.
+    fn set_leased(&self, key: ProgramKey, description: ProgramDescription, ttl: Duration) -> bool;
+
+    /// Removes the registrations of `program` and `version`.
+    ///
+    /// If `net_id` is `None` every transport is removed, otherwise only the matching one.
+    /// Returns `true` if anything was removed.
+    fn unset(&self, program: u32, version: u32, net_id: Option<&Netid>) -> bool;
+
+    /// Removes the registration under `key` only if it is still `description`, returning
+    /// whether it was.
+    ///
+    /// Unlike a [`Registry::lookup`] followed by [`Registry::unset`], a registration made again
+    /// in between is kept.
+    fn remove(&self, key: &ProgramKey, description: &ProgramDescription) -> bool;
+
+    /// Removes every registration whose lease ran out by `now`, returning how many there were.
+    fn expire(&self, now: Instant) -> usize;
+
+    fn lookup(&self, key: &ProgramKey) -> Option<ProgramDescription>;
+
+    /// Calls `visit` with every registration in turn, without copying the registry first.
+    ///
+    /// Changes wait until it returns, so `visit` must not call back into the registry.
+    fn for_each(&self, visit: &mut dyn FnMut(&ProgramKey, &ProgramDescription));
+
+    fn dump(&self) -> Vec<(ProgramKey, ProgramDescription)> {
+        let mut registrations = Vec::new();
+        self.for_each(&mut |key, description| {
+            registrations.push((key.clone(), description.clone()));
+        });
+        registrations
+    }
+
+    /// Like [`Registry::dump`], with the time left on each lease at `now`, or `None` for a
+    /// registration without one.
+    fn dump_leases(&self, now: Instant) -> Vec<(ProgramKey, ProgramDescription, Option<Duration>)>;
+
+    /// Subscribes to every change made to the registry from now on.
+    ///
+    /// A subscriber that falls [`WATCH_CAPACITY`] events behind is dropped, so the receiver ends
+    /// once the events already queued are read, and has to watch again to catch up.
+    fn watch(&self) -> Receiver<RegistryEvent>;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RegistryEvent {
+    Added {
+        key: ProgramKey,
+        description: ProgramDescription,
+    },
+    Removed {
+        key: ProgramKey,
+        description: ProgramDescription,
+    },
+    /// Removed because its lease ran out.
+    Expired {
+        key: ProgramKey,
+        description: ProgramDescription,
+    },
+    Changed {
+        key: ProgramKey,
+        old: ProgramDescription,
+        new: ProgramDescription,
+    },
+}
+
+/// How many events can wait for a subscriber before it is dropped.
+pub const WATCH_CAPACITY: usize = 1024;
+
+/// The set of subscribers created by [`Registry::watch`].
+#[derive(Debug, Default)]
+struct Watchers(Mutex<Vec<Sender<RegistryEvent>>>);
+
+impl Watchers {
+    fn subscribe(&self) -> Receiver<RegistryEvent> {
+        let (sender, receiver) = channel(WATCH_CAPACITY);
+        self.0.lock().push(sender);
+        receiver
+    }
+
+    fn notify(&self, event: RegistryEvent) {
+        // Subscribers that have gone away or fallen too far behind are dropped here, rather
+        // than queueing events for them without bound
+        self.0
+            .lock()
+            .retain(|sender| sender.try_send(event.clone()).is_ok());
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use rpcbind_rs::netid::Netid;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::Receiver;
+
+use super::{InMemoryRegistry, Registry, RegistryEvent};
+use crate::state::{ProgramDescription, ProgramKey};
+
+/// A registry that survives restarts by writing every change through to a file.
+///
+/// Each line of the file holds one registration as a JSON object, the way the control socket
+/// writes them, so netids, addresses and owners from callers are escaped whatever they hold.
+/// Leased registrations are only kept in memory, as they would otherwise come back without
+/// their lease, and so never expire, after a restart.
+#[derive(Debug)]
+pub struct FileRegistry {
+    memory: InMemoryRegistry,
+    path: PathBuf,
+    // Serialises writers so an older snapshot never replaces a newer one
+    persist_lock: Mutex<()>,
+}
+
+impl FileRegistry {
+    /// Opens the registry stored at `path`, starting empty if the file does not exist yet.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let memory = InMemoryRegistry::new();
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                for (index, line) in content.lines().enumerate() {
+                    let line: Line = serde_json::from_str(line).with_context(|| {
+                        format!("{}:{}: invalid registration", path.display(), index + 1)
+                    })?;
+                    memory.set(line.key, line.description);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e).context(format!("reading {}", path.display())),
+        }
+        Ok(Self {
+            memory,
+            path,
+            persist_lock: Mutex::new(()),
+        })
+    }
+
+    /// See [`InMemoryRegistry::with_owner_replacement`].
+    pub fn with_owner_replacement(mut self, enabled: bool) -> Self {
+        self.memory = self.memory.with_owner_replacement(enabled);
+        self
+    }
+
+    /// See [`InMemoryRegistry::with_lease_ttl`].
+    pub fn with_lease_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.memory = self.memory.with_lease_ttl(ttl);
+        self
+    }
+
+    fn persist(&self) {
+        let _guard = self.persist_lock.lock();
+        let permanent: Vec<_> = self
+            .memory
+            .dump_leases(Instant::now())
+            .into_iter()
+            .filter(|(_, _, ttl)| ttl.is_none())
+            .map(|(key, description, _)| Line { key, description })
+            .collect();
+        if let Err(e) = write_atomically(&self.path, &permanent) {
+            eprintln!(
+                "Error persisting registry to {}: {e:?}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+impl Registry for FileRegistry {
+    fn lease_ttl(&self) -> Option<Duration> {
+        self.memory.lease_ttl()
+    }
+
+    fn set(&self, key: ProgramKey, description: ProgramDescription) -> bool {
+        let added = self.memory.set(key, description);
+        if added {
+            self.persist();
+        }
+        added
+    }
+
+    fn set_leased(&self, key: ProgramKey, description: ProgramDescription, ttl: Duration) -> bool {
+        // Only replacing a permanent registration changes what is on file
+        let replaced = self.memory.lookup(&key).is_some();
+        let added = self.memory.set_leased(key, description, ttl);
+        if added && replaced {
+            self.persist();
+        }
+        added
+    }
+
+    fn unset(&self, program: u32, version: u32, net_id: Option<&Netid>) -> bool {
+        let removed = self.memory.unset(program, version, net_id);
+        if removed {
+            self.persist();
+        }
+        removed
+    }
+
+    fn remove(&self, key: &ProgramKey, description: &ProgramDescription) -> bool {
+        let removed = self.memory.remove(key, description);
+        if removed {
+            self.persist();
+        }
+        removed
+    }
+
+    fn expire(&self, now: Instant) -> usize {
+        // Leased registrations were never on file
+        self.memory.expire(now)
+    }
+
+    fn lookup(&self, key: &ProgramKey) -> Option<ProgramDescription> {
+        self.memory.lookup(key)
+    }
+
+    fn for_each(&self, visit: &mut dyn FnMut(&ProgramKey, &ProgramDescription)) {
+        self.memory.for_each(visit)
+    }
+
+    fn dump_leases(&self, now: Instant) -> Vec<(ProgramKey, ProgramDescription, Option<Duration>)> {
+        self.memory.dump_leases(now)
+    }
+
+    fn watch(&self) -> Receiver<RegistryEvent> {
+        self.memory.watch()
+    }
+}
+
+/// A registration as it is stored on one line of the file.
+#[derive(Serialize, Deserialize)]
+struct Line {
+    key: ProgramKey,
+    description: ProgramDescription,
+}
+
+fn write_atomically(path: &Path, registrations: &[Line]) -> Result<()> {
+    let mut content = Vec::new();
+    for registration in registrations {
+        serde_json::to_writer(&mut content, registration)?;
+        content.push(b'\n');
+    }
+
+    // Write next to the target so the rename cannot cross file systems
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, content)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use rpcbind_rs::netid::Netid;
+
+    use super::FileRegistry;
+    use crate::{
+        registry::Registry,
+        state::{ProgramDescription, ProgramKey},
+    };
+
+    #[test]
+    fn registrations_survive_reopen() {
+        let path = std::env::temp_dir().join(format!("rpcbind-registry-{}", std::process::id()));
+        let key = ProgramKey {
+            program: 100003,
+            version: 3,
+            net_id: Netid::Tcp,
+        };
+        let description = ProgramDescription {
+            addr: "127.0.0.1.8.1".parse().unwrap(),
+            owner: Some("nfs server".to_owned()),
+        };
+
+        let registry = FileRegistry::open(&path).unwrap();
+        assert!(registry.set(key.clone(), description.clone()));
+        assert!(registry.set(
+            ProgramKey {
+                version: 4,
+                ..key.clone()
+            },
+            description.clone()
+        ));
+        assert!(registry.unset(100003, 4, None));
+        drop(registry);
+
+        let reopened = FileRegistry::open(&path).unwrap();
+        assert_eq!(reopened.dump(), vec![(key, description)]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn leased_registrations_are_not_persisted() {
+        let path =
+            std::env::temp_dir().join(format!("rpcbind-registry-leased-{}", std::process::id()));
+        let key = ProgramKey {
+            program: 100003,
+            version: 3,
+            net_id: Netid::Tcp,
+        };
+        let description = ProgramDescription {
+            addr: "127.0.0.1.8.1".parse().unwrap(),
+            owner: Some("nfs".to_owned()),
+        };
+        let permanent = ProgramKey {
+            version: 4,
+            ..key.clone()
+        };
+
+        let registry = FileRegistry::open(&path).unwrap();
+        assert!(registry.set_leased(key.clone(), description.clone(), Duration::from_secs(60)));
+        assert!(registry.set(permanent.clone(), description.clone()));
+        drop(registry);
+
+        let reopened = FileRegistry::open(&path).unwrap();
+        assert_eq!(reopened.dump(), vec![(permanent, description)]);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn hostile_fields_survive_reopen() {
+        let path =
+            std::env::temp_dir().join(format!("rpcbind-registry-hostile-{}", std::process::id()));
+        // Everything here comes from callers, who could break a line or field apart
+        let key = ProgramKey {
+            program: 100003,
+            version: 3,
+            net_id: Netid::from("my net\nid \"x\""),
+        };
+        let description = ProgramDescription {
+            addr: "/run/nfs socket\n100003 3 tcp 0.0.0.0.8.1 -"
+                .parse()
+                .unwrap(),
+            owner: Some("nfs\n100005 1 udp 0.0.0.0.8.1 mountd\\".to_owned()),
+        };
+
+        let registry = FileRegistry::open(&path).unwrap();
+        assert!(registry.set(key.clone(), description.clone()));
+        drop(registry);
+
+        let reopened = FileRegistry::open(&path).unwrap();
+        assert_eq!(reopened.dump(), vec![(key, description)]);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
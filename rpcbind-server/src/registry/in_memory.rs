@@ -0,0 +1,339 @@
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    time::{Duration, Instant},
+};
+
+use parking_lot::RwLock;
+use rpcbind_rs::netid::Netid;
+use tokio::sync::mpsc::Receiver;
+
+use super::{Registry, RegistryEvent, Watchers};
+use crate::state::{ProgramDescription, ProgramKey};
+
+#[derive(Debug, Default)]
+pub struct InMemoryRegistry {
+    map: RwLock<HashMap<ProgramKey, Registration>>,
+    watchers: Watchers,
+    owner_replacement: bool,
+    lease_ttl: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct Registration {
+    description: ProgramDescription,
+    /// When the lease runs out, or `None` for a registration that stays until unset.
+    expires: Option<Instant>,
+}
+
+impl InMemoryRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets the owner of a registration replace it with [`Registry::set`], so a restarted
+    /// service can move to a new address without unsetting the old one first.
+    ///
+    /// rpcbind refuses to set a key that is already registered, which stays the default.
+    pub fn with_owner_replacement(mut self, enabled: bool) -> Self {
+        self.owner_replacement = enabled;
+        self
+    }
+
+    /// Leases registrations made over rpcbind with an owner for `ttl`, after which they are
+    /// removed unless the owner sets them again.
+    ///
+    /// rpcbind keeps every registration until it is unset, so `None` is the default.
+    pub fn with_lease_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.lease_ttl = ttl;
+        self
+    }
+
+    fn insert(
+        &self,
+        key: ProgramKey,
+        description: ProgramDescription,
+        expires: Option<Instant>,
+    ) -> bool {
+        let mut map = self.map.write();
+        match map.entry(key) {
+            Entry::Occupied(mut occupied_entry) => {
+                let current = occupied_entry.get_mut();
+                let same_owner = current.description.owner.is_some()
+                    && current.description.owner == description.owner;
+                let renewal = current.expires.is_some()
+                    && expires.is_some()
+                    && current.description == description;
+                if !same_owner || !(renewal || self.owner_replacement) {
+                    return false;
+                }
+                current.expires = expires;
+                if current.description != description {
+                    let old = std::mem::replace(&mut current.description, description.clone());
+                    self.watchers.notify(RegistryEvent::Changed {
+                        key: occupied_entry.key().clone(),
+                        old,
+                        new: description,
+                    });
+                }
+                true
+            }
+            Entry::Vacant(vacant_entry) => {
+                let event = RegistryEvent::Added {
+                    key: vacant_entry.key().clone(),
+                    description: description.clone(),
+                };
+                vacant_entry.insert(Registration {
+                    description,
+                    expires,
+                });
+                self.watchers.notify(event);
+                true
+            }
+        }
+    }
+}
+
+impl Registry for InMemoryRegistry {
+    fn lease_ttl(&self) -> Option<Duration> {
+        self.lease_ttl
+    }
+
+    fn set(&self, key: ProgramKey, description: ProgramDescription) -> bool {
+        self.insert(key, description, None)
+    }
+
+    fn set_leased(&self, key: ProgramKey, description: ProgramDescription, ttl: Duration) -> bool {
+        self.insert(key, description, Some(Instant::now() + ttl))
+    }
+
+    fn unset(&self, program: u32, version: u32, net_id: Option<&Netid>) -> bool {
+        let mut map = self.map.write();
+        let original_length = map.len();
+        map.retain(|key, registration| {
+            let matches = key.program == program
+                && key.version == version
+                && net_id.is_none_or(|net_id| key.net_id == *net_id);
+            if matches {
+                self.watchers.notify(RegistryEvent::Removed {
+                    key: key.clone(),
+                    description: registration.description.clone(),
+                });
+            }
+            !matches
+        });
+        map.len() < original_length
+    }
+
+    fn remove(&self, key: &ProgramKey, description: &ProgramDescription) -> bool {
+        let mut map = self.map.write();
+        if map
+            .get(key)
+            .is_none_or(|registration| registration.description != *description)
+        {
+            return false;
+        }
+        map.remove(key);
+        self.watchers.notify(RegistryEvent::Removed {
+            key: key.clone(),
+            description: description.clone(),
+        });
+        true
+    }
+
+    fn expire(&self, now: Instant) -> usize {
+        let mut map = self.map.write();
+        let original_length = map.len();
+        map.retain(|key, registration| {
+            let expired = registration.expires.is_some_and(|expires| expires <= now);
+            if expired {
+                self.watchers.notify(RegistryEvent::Expired {
+                    key: key.clone(),
+                    description: registration.description.clone(),
+                });
+            }
+            !expired
+        });
+        original_length - map.len()
+    }
+
+    fn lookup(&self, key: &ProgramKey) -> Option<ProgramDescription> {
+        self.map
+            .read()
+            .get(key)
+            .map(|registration| registration.description.clone())
+    }
+
+    fn for_each(&self, visit: &mut dyn FnMut(&ProgramKey, &ProgramDescription)) {
+        for (key, registration) in self.map.read().iter() {
+            visit(key, &registration.description);
+        }
+    }
+
+    fn dump_leases(&self, now: Instant) -> Vec<(ProgramKey, ProgramDescription, Option<Duration>)> {
+        self.map
+            .read()
+            .iter()
+            .map(|(key, registration)| {
+                let remaining = registration
+                    .expires
+                    .map(|expires| expires.saturating_duration_since(now));
+                (key.clone(), registration.description.clone(), remaining)
+            })
+            .collect()
+    }
+
+    fn watch(&self) -> Receiver<RegistryEvent> {
+        self.watchers.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use rpcbind_rs::netid::Netid;
+    use tokio::sync::mpsc::error::TryRecvError;
+
+    use super::InMemoryRegistry;
+    use crate::{
+        registry::{Registry, RegistryEvent, WATCH_CAPACITY},
+        state::{ProgramDescription, ProgramKey},
+    };
+
+    const TTL: Duration = Duration::from_secs(30);
+
+    fn key(version: u32) -> ProgramKey {
+        ProgramKey {
+            program: 100003,
+            version,
+            net_id: Netid::Tcp,
+        }
+    }
+
+    fn description(owner: &str) -> ProgramDescription {
+        ProgramDescription {
+            addr: "127.0.0.1.8.1".parse().unwrap(),
+            owner: Some(owner.to_owned()),
+        }
+    }
+
+    fn moved(owner: &str) -> ProgramDescription {
+        ProgramDescription {
+            addr: "127.0.0.1.8.2".parse().unwrap(),
+            ..description(owner)
+        }
+    }
+
+    #[test]
+    fn set_keeps_existing_registrations() {
+        let registry = InMemoryRegistry::new();
+        assert!(registry.set(key(3), description("nfs")));
+        let mut events = registry.watch();
+        assert!(!registry.set(key(3), description("nfs")));
+        assert!(!registry.set(key(3), moved("nfs")));
+        assert_eq!(registry.lookup(&key(3)), Some(description("nfs")));
+        assert!(events.try_recv().is_err());
+
+        let registry = InMemoryRegistry::new().with_owner_replacement(true);
+        assert!(registry.set(key(3), description("nfs")));
+        let mut events = registry.watch();
+        assert!(!registry.set(key(3), moved("other")));
+        assert!(registry.set(key(3), moved("nfs")));
+        assert_eq!(registry.lookup(&key(3)), Some(moved("nfs")));
+        assert_eq!(
+            events.try_recv().unwrap(),
+            RegistryEvent::Changed {
+                key: key(3),
+                old: description("nfs"),
+                new: moved("nfs")
+            }
+        );
+    }
+
+    #[test]
+    fn remove_only_matches_the_same_registration() {
+        let registry = InMemoryRegistry::new();
+        assert!(registry.set(key(3), description("nfs")));
+        let mut events = registry.watch();
+        assert!(!registry.remove(&key(3), &moved("nfs")));
+        assert!(!registry.remove(&key(4), &description("nfs")));
+        assert!(events.try_recv().is_err());
+
+        assert!(registry.remove(&key(3), &description("nfs")));
+        assert_eq!(registry.lookup(&key(3)), None);
+        assert_eq!(
+            events.try_recv().unwrap(),
+            RegistryEvent::Removed {
+                key: key(3),
+                description: description("nfs")
+            }
+        );
+    }
+
+    #[test]
+    fn lagging_watchers_are_dropped() {
+        let registry = InMemoryRegistry::new();
+        let mut lagging = registry.watch();
+        for version in 0..=WATCH_CAPACITY as u32 {
+            assert!(registry.set(key(version), description("nfs")));
+        }
+        let mut watching = registry.watch();
+        assert!(registry.unset(100003, 0, None));
+
+        // The events queued before falling behind can still be read
+        for _ in 0..WATCH_CAPACITY {
+            assert!(matches!(
+                lagging.try_recv(),
+                Ok(RegistryEvent::Added { .. })
+            ));
+        }
+        assert_eq!(lagging.try_recv(), Err(TryRecvError::Disconnected));
+        assert!(matches!(
+            watching.try_recv(),
+            Ok(RegistryEvent::Removed { .. })
+        ));
+    }
+
+    #[test]
+    fn leases_expire_unless_renewed() {
+        let registry = InMemoryRegistry::new();
+        assert!(registry.set_leased(key(3), description("nfs"), TTL));
+        let leased = Instant::now();
+        assert!(registry.set(key(4), description("nfs")));
+
+        let mut leases = registry.dump_leases(leased);
+        leases.sort_by_key(|(key, ..)| key.version);
+        assert!(leases[0].2.is_some_and(|remaining| remaining <= TTL));
+        assert_eq!(leases[1].2, None);
+
+        // Only the owner renews a lease, and only at the same address
+        assert!(!registry.set_leased(key(3), description("other"), TTL));
+        assert!(!registry.set_leased(key(3), moved("nfs"), TTL));
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(registry.set_leased(key(3), description("nfs"), TTL));
+        assert_eq!(registry.expire(leased + TTL), 0);
+
+        let mut events = registry.watch();
+        assert_eq!(registry.expire(leased + TTL * 2), 1);
+        assert_eq!(registry.dump(), [(key(4), description("nfs"))]);
+        assert_eq!(
+            events.try_recv().unwrap(),
+            RegistryEvent::Expired {
+                key: key(3),
+                description: description("nfs")
+            }
+        );
+    }
+
+    #[test]
+    fn replacing_a_lease_makes_it_permanent() {
+        let registry = InMemoryRegistry::new().with_owner_replacement(true);
+        assert!(registry.set_leased(key(3), description("nfs"), TTL));
+        assert!(registry.set(key(3), description("nfs")));
+        assert_eq!(registry.expire(Instant::now() + TTL * 2), 0);
+        assert_eq!(
+            registry.dump_leases(Instant::now()),
+            [(key(3), description("nfs"), None)]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::{collections::HashSet, fs, io, path::Path, sync::Arc, time::Duration};
+
+use rpcbind_rs::netid::{IPPROTO_TCP, IPPROTO_UDP};
+
+use crate::{
+    PROGRAM_ID,
+    registry::Registry,
+    state::{ProgramDescription, ProgramKey},
+};
+
+/// Where Linux lists the sockets of the network namespace rpcbind runs in.
+const PROC_NET: &str = "/proc/net";
+
+/// The state `/proc/net/tcp` gives a socket that is listening.
+const TCP_LISTEN: &str = "0A";
+
+/// The TCP ports something listens on and the UDP ports something is bound to.
+#[derive(Debug, Default, PartialEq)]
+pub struct BoundPorts {
+    tcp: HashSet<u16>,
+    udp: HashSet<u16>,
+}
+
+impl BoundPorts {
+    /// Reads the socket tables in `dir`, which is `/proc/net` outside of tests.
+    ///
+    /// The tables for IPv6 are missing when it is disabled, and then taken as empty.
+    pub fn read(dir: &Path) -> io::Result<Self> {
+        let table = |name: &str| match fs::read_to_string(dir.join(name)) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound && name.ends_with('6') => {
+                Ok(String::new())
+            }
+            result => result,
+        };
+        let mut ports = Self::default();
+        for name in ["tcp", "tcp6"] {
+            ports
+                .tcp
+                .extend(parse_table(&table(name)?, Some(TCP_LISTEN)));
+        }
+        for name in ["udp", "udp6"] {
+            ports.udp.extend(parse_table(&table(name)?, None));
+        }
+        Ok(ports)
+    }
+
+    /// Whether the port of a registration is bound, or `None` if it is not on a TCP or UDP
+    /// transport and cannot be told.
+    fn is_bound(&self, key: &ProgramKey, description: &ProgramDescription) -> Option<bool> {
+        let port = description.addr.port()?;
+        match key.net_id.protocol()? {
+            IPPROTO_TCP => Some(self.tcp.contains(&port)),
+            IPPROTO_UDP => Some(self.udp.contains(&port)),
+            _ => None,
+        }
+    }
+}
+
+/// The local ports of the sockets in a table of `/proc/net`, only counting those in `state`
+/// if given.
+fn parse_table<'a>(table: &'a str, state: Option<&'a str>) -> impl Iterator<Item = u16> + 'a {
+    // The first line names the columns
+    table.lines().skip(1).filter_map(move |line| {
+        let mut columns = line.split_whitespace().skip(1);
+        let (local_address, _remote_address, socket_state) =
+            (columns.next()?, columns.next()?, columns.next()?);
+        if state.is_some_and(|state| state != socket_state) {
+            return None;
+        }
+        let (_, port) = local_address.rsplit_once(':')?;
+        u16::from_str_radix(port, 16).ok()
+    })
+}
+
+/// The registrations in `registry` on a port nothing is bound to, besides rpcbind's own.
+pub fn stale(registry: &dyn Registry, ports: &BoundPorts) -> Vec<(ProgramKey, ProgramDescription)> {
+    registry
+        .dump()
+        .into_iter()
+        .filter(|(key, description)| {
+            key.program != PROGRAM_ID && ports.is_bound(key, description) == Some(false)
+        })
+        .collect()
+}
+
+/// Removes the registrations in `stale` that have not changed since they were found.
+fn remove(registry: &dyn Registry, stale: &[(ProgramKey, ProgramDescription)]) {
+    for (key, description) in stale {
+        // The service may have registered again in the meantime
+        registry.remove(key, description);
+    }
+}
+
+/// Reports registrations whose port nothing is bound to every `interval`, also removing them
+/// if `remove_stale` is set, until the socket tables cannot be read.
+pub async fn check(registry: Arc<dyn Registry>, interval: Duration, remove_stale: bool) {
+    let mut interval = tokio::time::interval(interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let ports = match BoundPorts::read(Path::new(PROC_NET)) {
+            Ok(ports) => ports,
+            Err(e) => {
+                eprintln!("Stopped checking for stale registrations: {e:?}");
+                return;
+            }
+        };
+        let stale = stale(registry.as_ref(), &ports);
+        for (key, description) in &stale {
+            let action = if remove_stale { "Removing" } else { "Found" };
+            println!(
+                "{action} stale registration of {} version {} on {}: nothing is bound to {}",
+                key.program, key.version, key.net_id, description.addr
+            );
+        }
+        if remove_stale {
+            remove(registry.as_ref(), &stale);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs};
+
+    use rpcbind_rs::netid::Netid;
+
+    use super::{BoundPorts, remove, stale};
+    use crate::{
+        PROGRAM_ID,
+        registry::{InMemoryRegistry, Registry},
+        state::{ProgramDescription, ProgramKey},
+    };
+
+    const TCP: &str = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000:006F 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1001 1 0 100 0 0 10 0
+   1: 0100007F:0801 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1002 1 0 100 0 0 10 0
+   2: 0100007F:9C40 0100007F:0802 01 00000000:00000000 00:00000000 00000000     0        0 1003 1 0 20 4 30 10 -1
+";
+    const UDP6: &str = "\
+  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  10: 00000000000000000000000000000000:006F 00000000000000000000000000000000:0000 07 00000000:00000000 00:00000000 00000000     0        0 2001 2 0 0
+  11: 00000000000000000000000001000000:0803 00000000000000000000000000000000:0000 07 00000000:00000000 00:00000000 00000000     0        0 2002 2 0 0
+";
+
+    fn ports(tcp: &[u16], udp: &[u16]) -> BoundPorts {
+        BoundPorts {
+            tcp: HashSet::from_iter(tcp.iter().copied()),
+            udp: HashSet::from_iter(udp.iter().copied()),
+        }
+    }
+
+    fn registration(program: u32, net_id: Netid, addr: &str) -> (ProgramKey, ProgramDescription) {
+        let key = ProgramKey {
+            program,
+            version: 3,
+            net_id,
+        };
+        let description = ProgramDescription {
+            addr: addr.parse().unwrap(),
+            owner: None,
+        };
+        (key, description)
+    }
+
+    #[test]
+    fn reads_listening_tcp_and_bound_udp_ports() {
+        let dir = std::env::temp_dir().join(format!("rpcbind-proc-net-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tcp"), TCP).unwrap();
+        fs::write(dir.join("udp"), TCP.lines().next().unwrap()).unwrap();
+        fs::write(dir.join("udp6"), UDP6).unwrap();
+
+        // Without tcp6, as when IPv6 is disabled
+        let read = BoundPorts::read(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(read, ports(&[111, 2049], &[111, 2051]));
+    }
+
+    #[test]
+    fn finds_and_removes_unbound_registrations() {
+        let registry = InMemoryRegistry::new();
+        let registrations = [
+            registration(PROGRAM_ID, Netid::Tcp, "0.0.0.0.0.111"),
+            registration(100003, Netid::Tcp, "0.0.0.0.8.1"),
+            registration(100003, Netid::Udp, "0.0.0.0.8.1"),
+            registration(100005, Netid::Tcp6, "::.8.2"),
+            registration(100005, Netid::Local, "/run/mountd.sock"),
+        ];
+        for (key, description) in registrations.clone() {
+            assert!(registry.set(key, description));
+        }
+
+        // Only TCP port 2049 is bound, and rpcbind's own port is never checked
+        let mut found = stale(&registry, &ports(&[2049], &[]));
+        found.sort_by_key(|(key, _)| key.program);
+        assert_eq!(found, [registrations[2].clone(), registrations[3].clone()]);
+
+        remove(&registry, &found);
+        let mut left = registry.dump();
+        left.sort_by_key(|(key, _)| (key.program, key.net_id.to_string()));
+        assert_eq!(
+            left,
+            [
+                registrations[0].clone(),
+                registrations[1].clone(),
+                registrations[4].clone()
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_registrations_that_moved() {
+        let registry = InMemoryRegistry::new().with_owner_replacement(true);
+        let (key, description) = registration(100003, Netid::Tcp, "0.0.0.0.8.1");
+        let owned = ProgramDescription {
+            owner: Some("nfs".to_owned()),
+            ..description
+        };
+        registry.set(key.clone(), owned.clone());
+        let found = stale(&registry, &BoundPorts::default());
+
+        let moved = ProgramDescription {
+            addr: "0.0.0.0.8.2".parse().unwrap(),
+            ..owned
+        };
+        assert!(registry.set(key.clone(), moved.clone()));
+        remove(&registry, &found);
+        assert_eq!(registry.lookup(&key), Some(moved));
+    }
+}
@@ -0,0 +1,310 @@
+//! Counts of the calls answered, reported by `RPCBPROC_GETSTAT` like rpcbind's `rpcb_stat.c`.
+
+use parking_lot::Mutex;
+use rpcbind_rs::{
+    netid::Netid,
+    request::{PortMapperRequest, RpcBindRequest, RpcRequest},
+    response::{PortMapperResponse, RpcBindResponse, RpcResponse},
+    xdr_types::rpcbind::{
+        AddrList, HIGHPROC_2, HIGHPROC_3, HIGHPROC_4, Proc, RmtCallArgs, RmtCallList, RpcbsAddr,
+        RpcbsRmtCall, STAT_HIGHPROC, Stat, StatByVers, VERS_STAT,
+    },
+};
+
+use crate::netconfig::net_config;
+
+/// How many lookups and remote calls are listed for each version. Callers choose the programs
+/// they ask about, so beyond it new ones are only counted towards their procedure, rather than
+/// growing the lists without bound.
+const MAX_LISTED: usize = 256;
+
+static STATS: Stats = Stats::new();
+
+/// The statistics of the calls this process answered, which like rpcbind's are kept from the
+/// start of the process and shared by every transport.
+pub fn stats() -> &'static Stats {
+    &STATS
+}
+
+#[derive(Debug)]
+pub struct Stats(Mutex<[Counts; VERS_STAT as usize]>);
+
+/// The counts of one protocol version.
+#[derive(Debug)]
+struct Counts {
+    procedures: [i32; STAT_HIGHPROC as usize],
+    sets: i32,
+    unsets: i32,
+    /// Most recently added first, as rpcbind prepends to its lists.
+    lookups: Vec<RpcbsAddr>,
+    /// Most recently added first, as rpcbind prepends to its lists.
+    remote_calls: Vec<RpcbsRmtCall>,
+}
+
+impl Counts {
+    const fn new() -> Self {
+        Self {
+            procedures: [0; STAT_HIGHPROC as usize],
+            sets: 0,
+            unsets: 0,
+            lookups: Vec::new(),
+            remote_calls: Vec::new(),
+        }
+    }
+
+    fn lookup(&mut self, program: u32, version: u32, net_id: &str, found: bool) {
+        let index = self.lookups.iter().position(|lookup| {
+            lookup.prog == program && lookup.vers == version && lookup.netid == net_id
+        });
+        let lookup = match index {
+            Some(index) => &mut self.lookups[index],
+            // Like rpcbind, lookups on transports missing from netconfig are not listed
+            None if self.lookups.len() < MAX_LISTED && net_config().get(net_id).is_some() => {
+                self.lookups.insert(
+                    0,
+                    RpcbsAddr {
+                        prog: program,
+                        vers: version,
+                        success: 0,
+                        failure: 0,
+                        netid: net_id.to_owned(),
+                    },
+                );
+                &mut self.lookups[0]
+            }
+            None => return,
+        };
+        let count = if found {
+            &mut lookup.success
+        } else {
+            &mut lookup.failure
+        };
+        *count = count.saturating_add(1);
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            info: Proc(self.procedures),
+            setinfo: self.sets,
+            unsetinfo: self.unsets,
+            addrinfo: AddrList::from(self.lookups.clone()),
+            rmtinfo: RmtCallList::from(self.remote_calls.clone()),
+        }
+    }
+}
+
+/// The index of `version` in the counts, and the last procedure counted for it.
+fn version_stat(version: u32) -> Option<(usize, u32)> {
+    match version {
+        2 => Some((0, HIGHPROC_2)),
+        3 => Some((1, HIGHPROC_3)),
+        4 => Some((2, HIGHPROC_4)),
+        _ => None,
+    }
+}
+
+impl Stats {
+    const fn new() -> Self {
+        Self(Mutex::new([Counts::new(), Counts::new(), Counts::new()]))
+    }
+
+    /// Counts a call to `procedure` of rpcbind's `version`, before its arguments are decoded.
+    pub fn call(&self, version: u32, procedure: u32) {
+        let Some((index, last)) = version_stat(version) else {
+            return;
+        };
+        if procedure <= last {
+            let count = &mut self.0.lock()[index].procedures[procedure as usize];
+            *count = count.saturating_add(1);
+        }
+    }
+
+    /// Counts the successful SETs and UNSETs, and the lookups, among answered calls.
+    pub fn answered(&self, request: &RpcRequest, response: &RpcResponse) {
+        let Some((index, _)) = version_stat(request.version()) else {
+            return;
+        };
+        let counts = &mut self.0.lock()[index];
+        match (request, response) {
+            (
+                _,
+                RpcResponse::V2(PortMapperResponse::Set(true))
+                | RpcResponse::V3(RpcBindResponse::Set(true))
+                | RpcResponse::V4(RpcBindResponse::Set(true)),
+            ) => counts.sets = counts.sets.saturating_add(1),
+            (
+                _,
+                RpcResponse::V2(PortMapperResponse::Unset(true))
+                | RpcResponse::V3(RpcBindResponse::Unset(true))
+                | RpcResponse::V4(RpcBindResponse::Unset(true)),
+            ) => counts.unsets = counts.unsets.saturating_add(1),
+            (
+                RpcRequest::V2(PortMapperRequest::GetPort(mapping)),
+                RpcResponse::V2(PortMapperResponse::GetPort(port)),
+            ) => {
+                // Like rpcbind, any protocol but UDP is counted as TCP
+                let net_id = match mapping.netid() {
+                    Some(Netid::Udp) => Netid::Udp,
+                    _ => Netid::Tcp,
+                };
+                counts.lookup(mapping.prog, mapping.vers, net_id.as_str(), *port != 0);
+            }
+            (
+                RpcRequest::V3(RpcBindRequest::GetAddr(rpcb))
+                | RpcRequest::V4(RpcBindRequest::GetAddr(rpcb)),
+                RpcResponse::V3(RpcBindResponse::GetAddr(addr))
+                | RpcResponse::V4(RpcBindResponse::GetAddr(addr)),
+            ) => counts.lookup(rpcb.r_prog, rpcb.r_vers, &rpcb.r_netid, !addr.is_empty()),
+            _ => {}
+        }
+    }
+
+    /// Counts a call forwarded from a caller on `net_id` by `version` of rpcbind, whose
+    /// program was `found` registered.
+    pub fn remote_call(
+        &self,
+        version: u32,
+        args: &RmtCallArgs,
+        net_id: &Netid,
+        indirect: bool,
+        found: bool,
+    ) {
+        let Some((index, _)) = version_stat(version) else {
+            return;
+        };
+        let calls = &mut self.0.lock()[index].remote_calls;
+        let index = calls.iter().position(|call| {
+            call.prog == args.prog
+                && call.vers == args.vers
+                && call.proc == args.proc
+                && call.netid == net_id.as_str()
+        });
+        let call = match index {
+            Some(index) => &mut calls[index],
+            None if calls.len() < MAX_LISTED => {
+                calls.insert(
+                    0,
+                    RpcbsRmtCall {
+                        prog: args.prog,
+                        vers: args.vers,
+                        proc: args.proc,
+                        success: 0,
+                        failure: 0,
+                        indirect: 0,
+                        netid: net_id.to_string(),
+                    },
+                );
+                &mut calls[0]
+            }
+            None => return,
+        };
+        let count = if found {
+            &mut call.success
+        } else {
+            &mut call.failure
+        };
+        *count = count.saturating_add(1);
+        if indirect {
+            call.indirect = call.indirect.saturating_add(1);
+        }
+    }
+
+    /// The statistics as `RPCBPROC_GETSTAT` answers them.
+    pub fn snapshot(&self) -> StatByVers {
+        let counts = self.0.lock();
+        StatByVers(counts.each_ref().map(Counts::stat))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rpcbind_rs::{
+        netid::Netid,
+        request::{PortMapperRequest, RpcBindRequest, RpcRequest},
+        response::{PortMapperResponse, RpcBindResponse, RpcResponse},
+        xdr_types::{
+            port_mapper::Mapping,
+            rpcbind::{RPCB, RmtCallArgs},
+        },
+    };
+
+    use super::{MAX_LISTED, Stats};
+
+    fn get_addr(program: u32, net_id: &str) -> RpcRequest {
+        RpcRequest::V4(RpcBindRequest::GetAddr(RPCB {
+            r_prog: program,
+            r_vers: 1,
+            r_netid: net_id.to_owned(),
+            r_addr: String::new(),
+            r_owner: String::new(),
+        }))
+    }
+
+    #[test]
+    fn counts_like_rpcbind() {
+        let stats = Stats::new();
+        stats.call(2, 1);
+        stats.call(3, 12);
+        stats.call(4, 12);
+        stats.call(5, 0);
+        stats.answered(
+            &RpcRequest::V2(PortMapperRequest::Set(Mapping {
+                prog: 100003,
+                vers: 3,
+                prot: 17,
+                port: 2049,
+            })),
+            &RpcResponse::V2(PortMapperResponse::Set(true)),
+        );
+        stats.answered(
+            &RpcRequest::V2(PortMapperRequest::GetPort(Mapping {
+                prog: 100003,
+                vers: 3,
+                prot: 1,
+                port: 0,
+            })),
+            &RpcResponse::V2(PortMapperResponse::GetPort(0)),
+        );
+        let found = RpcResponse::V4(RpcBindResponse::GetAddr("127.0.0.1.8.1".to_owned()));
+        stats.answered(&get_addr(100003, "udp"), &found);
+        stats.answered(&get_addr(100005, "udp"), &found);
+        stats.answered(&get_addr(100003, "udp"), &found);
+        stats.answered(&get_addr(100003, "sctp"), &found);
+
+        let stat = stats.snapshot();
+        let [v2, v3, v4] = &stat.0;
+        assert_eq!(v2.info.0[1], 1);
+        assert_eq!(v2.setinfo, 1);
+        // Beyond the last procedure of version 3
+        assert_eq!(v3.info.0, [0; 13]);
+        assert_eq!(v4.info.0[12], 1);
+        assert_eq!(v2.addrinfo[0].netid, "tcp");
+        assert_eq!(v2.addrinfo[0].failure, 1);
+        let lookups: Vec<_> = v4
+            .addrinfo
+            .iter()
+            .map(|lookup| (lookup.prog, lookup.netid.as_str(), lookup.success))
+            .collect();
+        assert_eq!(lookups, [(100005, "udp", 1), (100003, "udp", 2)]);
+    }
+
+    #[test]
+    fn bounds_the_lists() {
+        let stats = Stats::new();
+        let found = RpcResponse::V4(RpcBindResponse::GetAddr(String::new()));
+        for program in 0..MAX_LISTED as u32 + 10 {
+            stats.answered(&get_addr(program, "tcp"), &found);
+            let args = RmtCallArgs {
+                prog: program,
+                vers: 1,
+                proc: 0,
+                args: Vec::new(),
+            };
+            stats.remote_call(4, &args, &Netid::Udp, true, false);
+        }
+        let stat = stats.snapshot();
+        assert_eq!(stat.0[2].addrinfo.len(), MAX_LISTED);
+        assert_eq!(stat.0[2].rmtinfo.len(), MAX_LISTED);
+        assert_eq!(stat.0[2].rmtinfo[0].indirect, 1);
+    }
+}
@@ -0,0 +1,249 @@
+/*
+ * Makes the calls of one wire format fixture with libtirpc's client, on a single TCP
+ * connection, and checks each result as libtirpc decodes it.
+ *
+ * Usage: capture HOST PORT FIXTURE
+ *
+ * Prints the title of the fixture, then a description of each call before making it, for the
+ * capture_fixture example to pair with the records it relays.
+ */
+
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <rpc/rpc.h>
+#include <rpc/pmap_prot.h>
+#include <rpc/rpcb_prot.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define NFS_PROGRAM 100003
+#define MOUNT_PROGRAM 100005
+#define IPPROTO_ICMP_NUMBER 1
+
+static CLIENT *client;
+static const char *current;
+
+static void fail(const char *reason)
+{
+	fprintf(stderr, "%s: %s\n", current, reason);
+	exit(1);
+}
+
+/* Calls PROCEDURE of PROGRAM and VERSION, which must fail with EXPECTED or succeed. */
+static void call(const char *description, rpcprog_t program, rpcvers_t version,
+		 rpcproc_t procedure, xdrproc_t encode, void *args, xdrproc_t decode,
+		 void *result, enum clnt_stat expected)
+{
+	struct timeval timeout = { 5, 0 };
+	enum clnt_stat status;
+
+	current = description;
+	printf("%s\n", description);
+	fflush(stdout);
+	clnt_control(client, CLSET_PROG, (char *)&program);
+	clnt_control(client, CLSET_VERS, (char *)&version);
+	status = clnt_call(client, procedure, encode, args, decode, result, timeout);
+	if (status != expected)
+		fail(clnt_sperrno(status));
+}
+
+static void null(const char *description, rpcvers_t version)
+{
+	call(description, PMAPPROG, version, NULLPROC, (xdrproc_t)xdr_void, NULL,
+	     (xdrproc_t)xdr_void, NULL, RPC_SUCCESS);
+}
+
+static void expect_bool(bool_t result, bool_t expected)
+{
+	if (result != expected)
+		fail(expected ? "expected TRUE" : "expected FALSE");
+}
+
+static void pmap_bool(const char *description, rpcproc_t procedure, u_long program,
+		      u_long version, u_long protocol, u_long port, bool_t expected)
+{
+	struct pmap map = { program, version, protocol, port };
+	bool_t result;
+
+	call(description, PMAPPROG, PMAPVERS, procedure, (xdrproc_t)xdr_pmap, &map,
+	     (xdrproc_t)xdr_bool, &result, RPC_SUCCESS);
+	expect_bool(result, expected);
+}
+
+static void getport(const char *description, u_long protocol, u_long expected)
+{
+	struct pmap map = { NFS_PROGRAM, 3, protocol, 0 };
+	u_long port;
+
+	call(description, PMAPPROG, PMAPVERS, PMAPPROC_GETPORT, (xdrproc_t)xdr_pmap, &map,
+	     (xdrproc_t)xdr_u_long, &port, RPC_SUCCESS);
+	if (port != expected)
+		fail("unexpected port");
+}
+
+static void dump(const char *description, int expected)
+{
+	struct pmaplist *list = NULL, *entry;
+	int count = 0;
+
+	call(description, PMAPPROG, PMAPVERS, PMAPPROC_DUMP, (xdrproc_t)xdr_void, NULL,
+	     (xdrproc_t)xdr_pmaplist_ptr, &list, RPC_SUCCESS);
+	for (entry = list; entry != NULL; entry = entry->pml_next)
+		count++;
+	if (count != expected)
+		fail("unexpected number of mappings");
+	xdr_free((xdrproc_t)xdr_pmaplist_ptr, (char *)&list);
+}
+
+static void rpcb_bool(const char *description, rpcvers_t version, rpcproc_t procedure,
+		      char *netid, char *addr, bool_t expected)
+{
+	rpcb parms = { NFS_PROGRAM, 3, netid, addr, "nfs" };
+	bool_t result;
+
+	call(description, RPCBPROG, version, procedure, (xdrproc_t)xdr_rpcb, &parms,
+	     (xdrproc_t)xdr_bool, &result, RPC_SUCCESS);
+	expect_bool(result, expected);
+}
+
+static void getaddr(const char *description, rpcvers_t version, char *netid,
+		    const char *expected)
+{
+	rpcb parms = { NFS_PROGRAM, 3, netid, "", "" };
+	char *addr = NULL;
+
+	call(description, RPCBPROG, version, RPCBPROC_GETADDR, (xdrproc_t)xdr_rpcb, &parms,
+	     (xdrproc_t)xdr_wrapstring, &addr, RPC_SUCCESS);
+	if (strcmp(addr, expected) != 0)
+		fail("unexpected address");
+	xdr_free((xdrproc_t)xdr_wrapstring, (char *)&addr);
+}
+
+/* The program and version of a mapping, without its protocol and port. */
+static bool_t xdr_half_pmap(XDR *xdrs, struct pmap *map)
+{
+	return xdr_u_long(xdrs, &map->pm_prog) && xdr_u_long(xdrs, &map->pm_vers);
+}
+
+/* An rpcb whose netid declares 256 bytes, of which only "tcp" and its padding follow. */
+static bool_t xdr_overlong_netid(XDR *xdrs, rpcb *parms)
+{
+	u_int32_t length = 256;
+	char netid[4] = "tcp";
+
+	return xdr_u_int32_t(xdrs, &parms->r_prog) && xdr_u_int32_t(xdrs, &parms->r_vers) &&
+	       xdr_u_int32_t(xdrs, &length) && xdr_opaque(xdrs, netid, sizeof(netid));
+}
+
+static void errors(void)
+{
+	struct pmap map = { NFS_PROGRAM, 3, 0, 0 };
+	rpcb parms = { NFS_PROGRAM, 3, NULL, NULL, NULL };
+
+	printf("Calls rpcbind rejects\n");
+	call("A call to NFS, which this server is not: PROG_UNAVAIL", NFS_PROGRAM, 3, NULLPROC,
+	     (xdrproc_t)xdr_void, NULL, (xdrproc_t)xdr_void, NULL, RPC_PROGUNAVAIL);
+	call("Version 5: PROG_MISMATCH, supporting versions 2 to 4", PMAPPROG, 5, NULLPROC,
+	     (xdrproc_t)xdr_void, NULL, (xdrproc_t)xdr_void, NULL, RPC_PROGVERSMISMATCH);
+	call("RPCBPROC procedure 99: PROC_UNAVAIL", RPCBPROG, RPCBVERS4, 99, (xdrproc_t)xdr_void,
+	     NULL, (xdrproc_t)xdr_void, NULL, RPC_PROCUNAVAIL);
+	call("PMAPPROC_SET with half a mapping: GARBAGE_ARGS", PMAPPROG, PMAPVERS, PMAPPROC_SET,
+	     (xdrproc_t)xdr_half_pmap, &map, (xdrproc_t)xdr_void, NULL, RPC_CANTDECODEARGS);
+	call("RPCBPROC_GETADDR whose netid claims more bytes than were sent: GARBAGE_ARGS",
+	     RPCBPROG, RPCBVERS4, RPCBPROC_GETADDR, (xdrproc_t)xdr_overlong_netid, &parms,
+	     (xdrproc_t)xdr_void, NULL, RPC_CANTDECODEARGS);
+}
+
+static void nulls(void)
+{
+	printf("NULL in every version\n");
+	null("PMAPPROC_NULL", PMAPVERS);
+	null("RPCBPROC_NULL version 3", RPCBVERS);
+	null("RPCBPROC_NULL version 4", RPCBVERS4);
+}
+
+static void portmapper(void)
+{
+	printf("Registering, looking up and removing a program with portmapper\n");
+	getport("PMAPPROC_GETPORT of NFS 3 over TCP before it is registered: port 0",
+		IPPROTO_TCP, 0);
+	pmap_bool("PMAPPROC_SET NFS 3 over TCP at port 2049: TRUE", PMAPPROC_SET, NFS_PROGRAM, 3,
+		  IPPROTO_TCP, 2049, TRUE);
+	getport("PMAPPROC_GETPORT over TCP: 2049", IPPROTO_TCP, 2049);
+	getport("PMAPPROC_GETPORT over UDP, which was not registered: 0", IPPROTO_UDP, 0);
+	pmap_bool("PMAPPROC_SET of ICMP, which portmapper cannot map: FALSE", PMAPPROC_SET,
+		  NFS_PROGRAM, 3, IPPROTO_ICMP_NUMBER, 2049, FALSE);
+	pmap_bool("PMAPPROC_UNSET, ignoring the protocol and port: TRUE", PMAPPROC_UNSET,
+		  NFS_PROGRAM, 3, 0, 0, TRUE);
+	getport("PMAPPROC_GETPORT after the unset: 0", IPPROTO_TCP, 0);
+	pmap_bool("PMAPPROC_UNSET again, with nothing left to remove: FALSE", PMAPPROC_UNSET,
+		  NFS_PROGRAM, 3, 0, 0, FALSE);
+}
+
+static void portmapper_dump(void)
+{
+	printf("Listing registrations with portmapper, whose order is not part of the protocol\n");
+	dump("PMAPPROC_DUMP of the empty registry: a FALSE pointer", 0);
+	pmap_bool("PMAPPROC_SET NFS 3 over TCP at port 2049", PMAPPROC_SET, NFS_PROGRAM, 3,
+		  IPPROTO_TCP, 2049, TRUE);
+	pmap_bool("PMAPPROC_SET mountd 1 over UDP at port 20048", PMAPPROC_SET, MOUNT_PROGRAM, 1,
+		  IPPROTO_UDP, 20048, TRUE);
+	dump("PMAPPROC_DUMP: both mappings", 2);
+	pmap_bool("PMAPPROC_UNSET NFS 3", PMAPPROC_UNSET, NFS_PROGRAM, 3, 0, 0, TRUE);
+	pmap_bool("PMAPPROC_UNSET mountd 1", PMAPPROC_UNSET, MOUNT_PROGRAM, 1, 0, 0, TRUE);
+}
+
+static void rpcbind(void)
+{
+	printf("Registering, looking up and removing a program with rpcbind versions 3 and 4\n");
+	getaddr("RPCBPROC_GETADDR version 3 before registering: an empty address", RPCBVERS,
+		"tcp", "");
+	rpcb_bool("RPCBPROC_SET version 3 of NFS 3 over tcp at port 2049: TRUE", RPCBVERS,
+		  RPCBPROC_SET, "tcp", "127.0.0.1.8.1", TRUE);
+	getaddr("RPCBPROC_GETADDR version 4", RPCBVERS4, "tcp", "127.0.0.1.8.1");
+	getaddr("RPCBPROC_GETADDR version 4 over tcp6, which was not registered", RPCBVERS4,
+		"tcp6", "");
+	rpcb_bool("RPCBPROC_UNSET version 4 of the tcp registration: TRUE", RPCBVERS4,
+		  RPCBPROC_UNSET, "tcp", "", TRUE);
+	rpcb_bool("RPCBPROC_UNSET again, with nothing left to remove: FALSE", RPCBVERS4,
+		  RPCBPROC_UNSET, "tcp", "", FALSE);
+	getaddr("RPCBPROC_GETADDR version 3 after the unset", RPCBVERS, "tcp", "");
+}
+
+int main(int argc, char **argv)
+{
+	static const struct {
+		const char *name;
+		void (*calls)(void);
+	} fixtures[] = {
+		{ "errors", errors },
+		{ "null", nulls },
+		{ "portmapper", portmapper },
+		{ "portmapper_dump", portmapper_dump },
+		{ "rpcbind", rpcbind },
+	};
+	struct sockaddr_in addr = { .sin_family = AF_INET };
+	int sock = RPC_ANYSOCK;
+	size_t i;
+
+	if (argc != 4 || inet_pton(AF_INET, argv[1], &addr.sin_addr) != 1) {
+		fprintf(stderr, "usage: %s HOST PORT FIXTURE\n", argv[0]);
+		return 2;
+	}
+	addr.sin_port = htons(atoi(argv[2]));
+	client = clnttcp_create(&addr, PMAPPROG, PMAPVERS, &sock, 0, 0);
+	if (client == NULL) {
+		fprintf(stderr, "%s\n", clnt_spcreateerror(argv[1]));
+		return 1;
+	}
+	for (i = 0; i < sizeof(fixtures) / sizeof(fixtures[0]); i++) {
+		if (strcmp(argv[3], fixtures[i].name) == 0) {
+			fixtures[i].calls();
+			clnt_destroy(client);
+			return 0;
+		}
+	}
+	fprintf(stderr, "unknown fixture %s\n", argv[3]);
+	return 2;
+}
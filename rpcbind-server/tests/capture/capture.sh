@@ -0,0 +1,42 @@
+#!/bin/sh
+# Regenerates tests/fixtures from the calls of a libtirpc client, which needs gcc and
+# libtirpc's headers (libtirpc-dev on Debian).
+#
+# With RPCBIND=host:port, the calls go to that rpcbind over TCP. It must start with nothing
+# registered for the programs the fixtures use, and its DUMP replies list its own registrations
+# too, which tests/wire_format.rs does not expect. Otherwise each fixture is answered by a fresh
+# tests/capture/rpcbind.c, whose replies libtirpc's server side encodes.
+set -eu
+
+cd "$(dirname "$0")/../.."
+build=$(mktemp -d)
+server=
+trap 'kill $server 2>/dev/null || true; rm -rf "$build"' EXIT
+for program in capture rpcbind; do
+	gcc -Wall -Werror -I/usr/include/tirpc -o "$build/$program" \
+		"tests/capture/$program.c" -ltirpc
+done
+cargo build -q -p rpcbind-server --example capture_fixture
+
+for fixture in errors null portmapper portmapper_dump rpcbind; do
+	if [ -n "${RPCBIND:-}" ]; then
+		address=$RPCBIND
+		source="rpcbind at $RPCBIND,"
+	else
+		"$build/rpcbind" >"$build/port" &
+		server=$!
+		while [ ! -s "$build/port" ]; do sleep 0.1; done
+		address=127.0.0.1:$(cat "$build/port")
+		source="tests/capture/rpcbind.c, a stand-in for rpcbind whose replies the server \
+side of libtirpc $(pkg-config --modversion libtirpc) encoded,"
+	fi
+	cargo run -q -p rpcbind-server --example capture_fixture -- \
+		"$build/capture" "$address" "$fixture" "$source" >"$build/fixture"
+	cp "$build/fixture" "tests/fixtures/$fixture.hex"
+	if [ -n "$server" ]; then
+		kill "$server"
+		wait "$server" 2>/dev/null || true
+		server=
+		rm "$build/port"
+	fi
+done
@@ -0,0 +1,253 @@
+/*
+ * A registry answering portmapper and rpcbind calls through libtirpc's server side, for
+ * capture.sh to record replies from when no rpcbind is given to it.
+ *
+ * Usage: rpcbind
+ *
+ * Listens on an ephemeral TCP port of 127.0.0.1, printing it once ready. Every reply is built
+ * by libtirpc: results with svc_sendreply() and its XDR routines, and PROG_UNAVAIL,
+ * PROG_MISMATCH, PROC_UNAVAIL and GARBAGE_ARGS by svc_run()'s dispatch and svcerr_*().
+ *
+ * What it answers follows rpcbind's rules as far as the fixtures reach: a SET of a registered
+ * program, version and netid is refused, portmapper only maps TCP and UDP and its UNSET removes
+ * both, and an empty netid unsets every transport. It does not check owners, merge addresses
+ * with the caller's or forward calls.
+ */
+
+#include <arpa/inet.h>
+#include <netinet/in.h>
+#include <rpc/rpc.h>
+#include <rpc/pmap_prot.h>
+#include <rpc/rpcb_prot.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <time.h>
+#include <unistd.h>
+
+static rpcblist_ptr registry;
+
+static rpcblist_ptr *find(rpcprog_t program, rpcvers_t version, const char *netid)
+{
+	rpcblist_ptr *entry;
+
+	for (entry = &registry; *entry != NULL; entry = &(*entry)->rpcb_next) {
+		rpcb *map = &(*entry)->rpcb_map;
+		if (map->r_prog == program && map->r_vers == version &&
+		    strcmp(map->r_netid, netid) == 0)
+			break;
+	}
+	return entry;
+}
+
+static bool_t set(rpcprog_t program, rpcvers_t version, const char *netid, const char *addr,
+		  const char *owner)
+{
+	rpcblist_ptr *end = find(program, version, netid);
+
+	if (*end != NULL)
+		return FALSE;
+	*end = calloc(1, sizeof(**end));
+	(*end)->rpcb_map.r_prog = program;
+	(*end)->rpcb_map.r_vers = version;
+	(*end)->rpcb_map.r_netid = strdup(netid);
+	(*end)->rpcb_map.r_addr = strdup(addr);
+	(*end)->rpcb_map.r_owner = strdup(owner);
+	return TRUE;
+}
+
+/* Removes PROGRAM and VERSION on NETID, or on every transport if NETID is empty. */
+static bool_t unset(rpcprog_t program, rpcvers_t version, const char *netid)
+{
+	rpcblist_ptr *entry = &registry, removed;
+	bool_t found = FALSE;
+
+	while (*entry != NULL) {
+		rpcb *map = &(*entry)->rpcb_map;
+		if (map->r_prog != program || map->r_vers != version ||
+		    (netid[0] != '\0' && strcmp(map->r_netid, netid) != 0)) {
+			entry = &(*entry)->rpcb_next;
+			continue;
+		}
+		removed = *entry;
+		*entry = removed->rpcb_next;
+		removed->rpcb_next = NULL;
+		xdr_free((xdrproc_t)xdr_rpcblist_ptr, (char *)&removed);
+		found = TRUE;
+	}
+	return found;
+}
+
+static const char *pmap_netid(u_long protocol)
+{
+	switch (protocol) {
+	case IPPROTO_TCP:
+		return "tcp";
+	case IPPROTO_UDP:
+		return "udp";
+	default:
+		return NULL;
+	}
+}
+
+/* The port of a universal address ending in ".p1.p2". */
+static u_long uaddr_port(const char *addr)
+{
+	const char *low = strrchr(addr, '.'), *high;
+
+	for (high = low - 1; high > addr && *high != '.'; high--)
+		;
+	return atoi(high + 1) << 8 | atoi(low + 1);
+}
+
+static void pmap_dump(SVCXPRT *xprt)
+{
+	struct pmaplist *list = NULL, **end = &list;
+	rpcblist_ptr entry;
+
+	for (entry = registry; entry != NULL; entry = entry->rpcb_next) {
+		rpcb *map = &entry->rpcb_map;
+		u_long protocol = strcmp(map->r_netid, "tcp") == 0 ? IPPROTO_TCP :
+				  strcmp(map->r_netid, "udp") == 0 ? IPPROTO_UDP : 0;
+		if (protocol == 0)
+			continue;
+		*end = calloc(1, sizeof(**end));
+		(*end)->pml_map.pm_prog = map->r_prog;
+		(*end)->pml_map.pm_vers = map->r_vers;
+		(*end)->pml_map.pm_prot = protocol;
+		(*end)->pml_map.pm_port = uaddr_port(map->r_addr);
+		end = &(*end)->pml_next;
+	}
+	svc_sendreply(xprt, (xdrproc_t)xdr_pmaplist_ptr, &list);
+	xdr_free((xdrproc_t)xdr_pmaplist_ptr, (char *)&list);
+}
+
+static void pmap_service(struct svc_req *request, SVCXPRT *xprt)
+{
+	struct pmap map;
+	rpcblist_ptr *entry;
+	const char *netid;
+	char addr[32];
+	bool_t result;
+	u_long port;
+
+	switch (request->rq_proc) {
+	case PMAPPROC_NULL:
+		svc_sendreply(xprt, (xdrproc_t)xdr_void, NULL);
+		return;
+	case PMAPPROC_DUMP:
+		pmap_dump(xprt);
+		return;
+	case PMAPPROC_SET:
+	case PMAPPROC_UNSET:
+	case PMAPPROC_GETPORT:
+		break;
+	default:
+		svcerr_noproc(xprt);
+		return;
+	}
+	if (!svc_getargs(xprt, (xdrproc_t)xdr_pmap, (char *)&map)) {
+		svcerr_decode(xprt);
+		return;
+	}
+	netid = pmap_netid(map.pm_prot);
+	switch (request->rq_proc) {
+	case PMAPPROC_SET:
+		snprintf(addr, sizeof(addr), "0.0.0.0.%lu.%lu", map.pm_port >> 8 & 0xff,
+			 map.pm_port & 0xff);
+		result = netid != NULL && set(map.pm_prog, map.pm_vers, netid, addr, "unknown");
+		svc_sendreply(xprt, (xdrproc_t)xdr_bool, &result);
+		break;
+	case PMAPPROC_UNSET:
+		result = unset(map.pm_prog, map.pm_vers, "tcp");
+		result = unset(map.pm_prog, map.pm_vers, "udp") || result;
+		svc_sendreply(xprt, (xdrproc_t)xdr_bool, &result);
+		break;
+	case PMAPPROC_GETPORT:
+		entry = netid != NULL ? find(map.pm_prog, map.pm_vers, netid) : NULL;
+		port = entry != NULL && *entry != NULL ? uaddr_port((*entry)->rpcb_map.r_addr) : 0;
+		svc_sendreply(xprt, (xdrproc_t)xdr_u_long, &port);
+		break;
+	}
+}
+
+static void rpcb_service(struct svc_req *request, SVCXPRT *xprt)
+{
+	rpcb parms;
+	rpcblist_ptr *entry;
+	char *addr;
+	bool_t result;
+	u_int32_t now;
+
+	switch (request->rq_proc) {
+	case NULLPROC:
+		svc_sendreply(xprt, (xdrproc_t)xdr_void, NULL);
+		return;
+	case RPCBPROC_DUMP:
+		svc_sendreply(xprt, (xdrproc_t)xdr_rpcblist_ptr, &registry);
+		return;
+	case RPCBPROC_GETTIME:
+		now = time(NULL);
+		svc_sendreply(xprt, (xdrproc_t)xdr_u_int32_t, &now);
+		return;
+	case RPCBPROC_SET:
+	case RPCBPROC_UNSET:
+	case RPCBPROC_GETADDR:
+		break;
+	default:
+		svcerr_noproc(xprt);
+		return;
+	}
+	memset(&parms, 0, sizeof(parms));
+	if (!svc_getargs(xprt, (xdrproc_t)xdr_rpcb, (char *)&parms)) {
+		svcerr_decode(xprt);
+		return;
+	}
+	switch (request->rq_proc) {
+	case RPCBPROC_SET:
+		result = set(parms.r_prog, parms.r_vers, parms.r_netid, parms.r_addr,
+			     parms.r_owner);
+		svc_sendreply(xprt, (xdrproc_t)xdr_bool, &result);
+		break;
+	case RPCBPROC_UNSET:
+		result = unset(parms.r_prog, parms.r_vers, parms.r_netid);
+		svc_sendreply(xprt, (xdrproc_t)xdr_bool, &result);
+		break;
+	case RPCBPROC_GETADDR:
+		entry = find(parms.r_prog, parms.r_vers, parms.r_netid);
+		addr = *entry != NULL ? (*entry)->rpcb_map.r_addr : "";
+		svc_sendreply(xprt, (xdrproc_t)xdr_wrapstring, &addr);
+		break;
+	}
+	svc_freeargs(xprt, (xdrproc_t)xdr_rpcb, (char *)&parms);
+}
+
+int main(void)
+{
+	struct sockaddr_in addr = { .sin_family = AF_INET };
+	socklen_t len = sizeof(addr);
+	SVCXPRT *xprt;
+	int sock;
+
+	addr.sin_addr.s_addr = htonl(INADDR_LOOPBACK);
+	sock = socket(AF_INET, SOCK_STREAM, 0);
+	if (sock < 0 || bind(sock, (struct sockaddr *)&addr, sizeof(addr)) != 0 ||
+	    listen(sock, SOMAXCONN) != 0 ||
+	    getsockname(sock, (struct sockaddr *)&addr, &len) != 0) {
+		perror("rpcbind");
+		return 1;
+	}
+	xprt = svc_vc_create(sock, 0, 0);
+	/* Without a netconfig, the programs are only registered with this dispatcher */
+	if (xprt == NULL || !svc_reg(xprt, PMAPPROG, PMAPVERS, pmap_service, NULL) ||
+	    !svc_reg(xprt, RPCBPROG, RPCBVERS, rpcb_service, NULL) ||
+	    !svc_reg(xprt, RPCBPROG, RPCBVERS4, rpcb_service, NULL)) {
+		fprintf(stderr, "rpcbind: cannot serve\n");
+		return 1;
+	}
+	printf("%d\n", ntohs(addr.sin_port));
+	fflush(stdout);
+	svc_run();
+	return 1;
+}
@@ -0,0 +1,128 @@
+//! Runs the server logic on one end of an in-memory stream, with a registry of its own, so
+//! tests can make calls end to end without binding sockets.
+
+use std::sync::Arc;
+
+use onc_rpc::{AcceptedStatus, CallBody, MessageType, ReplyBody, RpcMessage, auth::AuthFlavor};
+use rpcbind_rs::{
+    client::{ClientError, ClientResult},
+    request::{PROGRAM, RpcRequest},
+    response::RpcResponse,
+};
+use rpcbind_server::{handle_client, registry::InMemoryRegistry};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream},
+    task::JoinHandle,
+};
+
+/// A connection to a server started for a single test.
+pub struct Harness {
+    stream: DuplexStream,
+    server: JoinHandle<()>,
+    pub registry: Arc<InMemoryRegistry>,
+    xid: u32,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        Self::with_registry(InMemoryRegistry::new())
+    }
+
+    /// Starts a server with `registry`, for options the default one does not have.
+    pub fn with_registry(registry: InMemoryRegistry) -> Self {
+        let (stream, server) = tokio::io::duplex(64 * 1024);
+        let registry = Arc::new(registry);
+        let server = tokio::spawn({
+            let registry = registry.clone();
+            async move { handle_client(server, registry.as_ref()).await.unwrap() }
+        });
+        Self {
+            stream,
+            server,
+            registry,
+            xid: 0,
+        }
+    }
+
+    /// Makes `request` and decodes the reply as its result.
+    pub async fn call(&mut self, request: RpcRequest) -> ClientResult<RpcResponse> {
+        let record = self.exchange(request.to_call_body()?).await;
+        let message = RpcMessage::try_from(record.as_slice())?;
+        let reply = message.reply_body().ok_or(ClientError::NotAReply)?;
+        RpcResponse::from_reply(&request, reply)
+    }
+
+    /// Calls `procedure` of any program and version with `payload` as its arguments, whether
+    /// or not they decode, and returns the result undecoded.
+    pub async fn call_raw(
+        &mut self,
+        program: u32,
+        version: u32,
+        procedure: u32,
+        payload: Vec<u8>,
+    ) -> ClientResult<Vec<u8>> {
+        let body = CallBody::new(
+            program,
+            version,
+            procedure,
+            AuthFlavor::<&[u8]>::AuthNone(None),
+            AuthFlavor::<&[u8]>::AuthNone(None),
+            payload,
+        );
+        let record = self.exchange(body).await;
+        let message = RpcMessage::try_from(record.as_slice())?;
+        let Some(ReplyBody::Accepted(reply)) = message.reply_body() else {
+            panic!("the server never denies a call: {message:?}");
+        };
+        Err(ClientError::Failed(match reply.status() {
+            AcceptedStatus::Success(payload) => return Ok(payload.to_vec()),
+            AcceptedStatus::ProgramUnavailable => AcceptedStatus::ProgramUnavailable,
+            AcceptedStatus::ProgramMismatch { low, high } => AcceptedStatus::ProgramMismatch {
+                low: *low,
+                high: *high,
+            },
+            AcceptedStatus::ProcedureUnavailable => AcceptedStatus::ProcedureUnavailable,
+            AcceptedStatus::GarbageArgs => AcceptedStatus::GarbageArgs,
+            AcceptedStatus::SystemError => AcceptedStatus::SystemError,
+        }))
+    }
+
+    /// [`Harness::call_raw`] for a procedure of rpcbind.
+    pub async fn call_rpcbind(
+        &mut self,
+        version: u32,
+        procedure: u32,
+        payload: Vec<u8>,
+    ) -> ClientResult<Vec<u8>> {
+        self.call_raw(PROGRAM, version, procedure, payload).await
+    }
+
+    /// Sends `body` in a record of its own and returns the reply record.
+    async fn exchange(&mut self, body: CallBody<impl AsRef<[u8]>, impl AsRef<[u8]>>) -> Vec<u8> {
+        self.xid += 1;
+        let call = RpcMessage::new(self.xid, MessageType::Call(body));
+        self.stream
+            .write_all(&call.serialise().unwrap())
+            .await
+            .unwrap();
+
+        let mut record = vec![0; 4];
+        self.stream.read_exact(&mut record).await.unwrap();
+        let header = u32::from_be_bytes(record[..4].try_into().unwrap());
+        assert!(header & (1 << 31) != 0, "replies fit in a single fragment");
+        record.resize(4 + (header & !(1 << 31)) as usize, 0);
+        self.stream.read_exact(&mut record[4..]).await.unwrap();
+
+        assert_eq!(
+            RpcMessage::try_from(record.as_slice()).unwrap().xid(),
+            self.xid
+        );
+        record
+    }
+
+    /// Closes the connection and waits for the server to finish with it.
+    pub async fn close(self) {
+        drop(self.stream);
+        self.server.await.unwrap();
+    }
+}
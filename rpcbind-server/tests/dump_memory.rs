@@ -0,0 +1,179 @@
+//! The heap memory a connection holds while dumping a large registry.
+//!
+//! A binary of its own, since the allocator it counts with replaces the global one.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::Arc,
+    thread,
+};
+
+use onc_rpc::{MessageType, RpcMessage};
+use rpcbind_rs::{
+    netid::Netid,
+    request::{PortMapperRequest, RpcBindRequest, RpcRequest},
+    response::{PortMapperResponse, RpcBindResponse, RpcResponse},
+};
+use rpcbind_server::{
+    handle_client,
+    registry::{InMemoryRegistry, Registry},
+    state::{ProgramDescription, ProgramKey},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream},
+    runtime::Runtime,
+};
+
+const REGISTRATIONS: u32 = 100_000;
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    static LIVE: Cell<usize> = const { Cell::new(0) };
+    static PEAK: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Tracks the most heap memory held at once by a thread inside [`peak_heap`].
+struct PeakAllocator;
+
+impl PeakAllocator {
+    fn track(allocated: usize, freed: usize) {
+        // Allocations while the thread exits have no thread locals left to count in
+        let _ = COUNTING.try_with(|counting| {
+            if counting.get() {
+                let live = (LIVE.get() + allocated).saturating_sub(freed);
+                LIVE.set(live);
+                PEAK.set(PEAK.get().max(live));
+            }
+        });
+    }
+}
+
+unsafe impl GlobalAlloc for PeakAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        Self::track(layout.size(), 0);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        Self::track(0, layout.size());
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // Counted as if it grew in place, which is the least a copy can hold
+        Self::track(new_size, layout.size());
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: PeakAllocator = PeakAllocator;
+
+/// Runs `f`, returning its result and the most heap memory it held at once.
+fn peak_heap<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    LIVE.set(0);
+    PEAK.set(0);
+    COUNTING.set(true);
+    let result = f();
+    COUNTING.set(false);
+    (result, PEAK.get())
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+}
+
+/// Makes `request` on `stream`, returning the decoded result and the length of the reply.
+async fn call(stream: &mut DuplexStream, xid: u32, request: &RpcRequest) -> (RpcResponse, usize) {
+    let call = RpcMessage::new(xid, MessageType::Call(request.to_call_body().unwrap()));
+    stream.write_all(&call.serialise().unwrap()).await.unwrap();
+
+    let mut record = vec![0; 4];
+    stream.read_exact(&mut record).await.unwrap();
+    let header = u32::from_be_bytes(record[..4].try_into().unwrap());
+    assert!(header & (1 << 31) != 0, "replies fit in a single fragment");
+    record.resize(4 + (header & !(1 << 31)) as usize, 0);
+    stream.read_exact(&mut record[4..]).await.unwrap();
+
+    let message = RpcMessage::try_from(record.as_slice()).unwrap();
+    assert_eq!(message.xid(), xid);
+    let response = RpcResponse::from_reply(request, message.reply_body().unwrap()).unwrap();
+    (response, record.len())
+}
+
+#[test]
+fn dumps_large_registries() {
+    let registry = Arc::new(InMemoryRegistry::new());
+    for index in 0..REGISTRATIONS {
+        let key = ProgramKey {
+            program: 200_000 + index / 2,
+            version: 1,
+            net_id: if index % 2 == 0 {
+                Netid::Tcp
+            } else {
+                Netid::Udp
+            },
+        };
+        let description = ProgramDescription {
+            addr: "127.0.0.1.8.1".parse().unwrap(),
+            owner: None,
+        };
+        assert!(registry.set(key, description));
+    }
+
+    let (mut stream, server) = tokio::io::duplex(64 * 1024);
+    // Neither encoding nor decoding recurses per entry, so a small stack is enough, and only
+    // the server's thread counts what it allocates
+    let server = thread::Builder::new()
+        .stack_size(256 * 1024)
+        .spawn(move || {
+            let runtime = runtime();
+            let (result, peak) =
+                peak_heap(|| runtime.block_on(handle_client(server, registry.as_ref())));
+            result.unwrap();
+            peak
+        })
+        .unwrap();
+
+    let client = thread::Builder::new()
+        .stack_size(256 * 1024)
+        .spawn(move || {
+            runtime().block_on(async move {
+                let request = RpcRequest::V2(PortMapperRequest::Dump);
+                let (response, mut longest) = call(&mut stream, 1, &request).await;
+                let RpcResponse::V2(PortMapperResponse::Dump(list)) = response else {
+                    panic!("not a dump");
+                };
+                assert_eq!(list.len(), REGISTRATIONS as usize);
+
+                for (xid, request) in [
+                    (2, RpcRequest::V3(RpcBindRequest::Dump)),
+                    (3, RpcRequest::V4(RpcBindRequest::Dump)),
+                ] {
+                    let (response, len) = call(&mut stream, xid, &request).await;
+                    let (RpcResponse::V3(RpcBindResponse::Dump(list))
+                    | RpcResponse::V4(RpcBindResponse::Dump(list))) = response
+                    else {
+                        panic!("not a dump");
+                    };
+                    assert_eq!(list.len(), REGISTRATIONS as usize);
+                    assert!(list.iter().all(|rpcb| rpcb.r_addr == "127.0.0.1.8.1"));
+                    longest = longest.max(len);
+                }
+                longest
+            })
+        })
+        .unwrap();
+
+    let longest = client.join().unwrap();
+    let peak = server.join().unwrap();
+    // The payload, with room to grow into, and the record it is copied into, where copying the
+    // registry out first would hold every registration on top
+    assert!(
+        peak <= 3 * longest + 1024 * 1024,
+        "{peak} bytes held for a reply of {longest}"
+    );
+}
@@ -0,0 +1,572 @@
+//! Every procedure of every version, called end to end over an in-memory connection.
+
+mod common;
+
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use common::Harness;
+use onc_rpc::AcceptedStatus;
+use rpcbind_rs::{
+    client::ClientError,
+    netconfig::Semantics,
+    netid::{IPPROTO_TCP, IPPROTO_UDP},
+    request::{PROGRAM, PortMapperRequest, RpcBindRequest, RpcRequest},
+    response::{PortMapperResponse, RpcBindResponse, RpcResponse},
+    xdr_types::{
+        codec::XdrCodec,
+        port_mapper::{CallArgs, Mapping},
+        rpcbind::{Entry, NetBuf, RPCB, RmtCallArgs},
+    },
+};
+use rpcbind_server::{
+    control, lease,
+    registry::{InMemoryRegistry, Registry},
+};
+use serde_json::Value;
+use tokio::{io::AsyncReadExt, net::UnixStream};
+
+const NFS: u32 = 100003;
+
+fn mapping(prot: u32, port: u32) -> Mapping {
+    Mapping {
+        prog: NFS,
+        vers: 3,
+        prot,
+        port,
+    }
+}
+
+fn rpcb(netid: &str, addr: &str) -> RPCB {
+    RPCB {
+        r_prog: NFS,
+        r_vers: 3,
+        r_netid: netid.to_owned(),
+        r_addr: addr.to_owned(),
+        r_owner: "superuser".to_owned(),
+    }
+}
+
+/// Wraps `request` in each rpcbind version.
+fn both_versions(request: RpcBindRequest) -> [RpcRequest; 2] {
+    [RpcRequest::V3(request.clone()), RpcRequest::V4(request)]
+}
+
+/// The result of an rpcbind call, whichever version answered.
+fn rpcbind(response: RpcResponse) -> RpcBindResponse {
+    match response {
+        RpcResponse::V3(response) | RpcResponse::V4(response) => response,
+        response => panic!("rpcbind answered with {response:?}"),
+    }
+}
+
+fn failed<T: std::fmt::Debug>(result: Result<T, ClientError>) -> AcceptedStatus<[u8; 0]> {
+    match result {
+        Err(ClientError::Failed(status)) => status,
+        result => panic!("expected the call to fail, got {result:?}"),
+    }
+}
+
+#[tokio::test]
+async fn null_answers_every_version() {
+    let mut harness = Harness::new();
+    let calls = [
+        RpcRequest::V2(PortMapperRequest::Null),
+        RpcRequest::V3(RpcBindRequest::Null),
+        RpcRequest::V4(RpcBindRequest::Null),
+    ];
+    for request in calls {
+        let version = request.version();
+        let response = harness.call(request).await.unwrap();
+        assert_eq!(response.version(), version);
+        assert!(matches!(
+            response,
+            RpcResponse::V2(PortMapperResponse::Null)
+                | RpcResponse::V3(RpcBindResponse::Null)
+                | RpcResponse::V4(RpcBindResponse::Null)
+        ));
+    }
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn port_mapper_registrations() {
+    let mut harness = Harness::new();
+    let mut call = async |request| match harness.call(RpcRequest::V2(request)).await.unwrap() {
+        RpcResponse::V2(response) => response,
+        response => panic!("version 2 answered with {response:?}"),
+    };
+
+    assert_eq!(
+        call(PortMapperRequest::GetPort(mapping(IPPROTO_TCP, 0))).await,
+        PortMapperResponse::GetPort(0)
+    );
+    assert_eq!(
+        call(PortMapperRequest::Set(mapping(IPPROTO_TCP, 2049))).await,
+        PortMapperResponse::Set(true)
+    );
+    assert_eq!(
+        call(PortMapperRequest::Set(mapping(IPPROTO_TCP, 2050))).await,
+        PortMapperResponse::Set(false),
+        "a registration without an owner is not replaced"
+    );
+    assert_eq!(
+        call(PortMapperRequest::Set(mapping(IPPROTO_UDP, 2049))).await,
+        PortMapperResponse::Set(true)
+    );
+    assert_eq!(
+        call(PortMapperRequest::GetPort(mapping(IPPROTO_TCP, 0))).await,
+        PortMapperResponse::GetPort(2049)
+    );
+
+    let PortMapperResponse::Dump(mappings) = call(PortMapperRequest::Dump).await else {
+        panic!("DUMP answered with something else");
+    };
+    let mut mappings = mappings.into_vec();
+    mappings.sort_by_key(|mapping| mapping.prot);
+    assert_eq!(
+        mappings,
+        [mapping(IPPROTO_TCP, 2049), mapping(IPPROTO_UDP, 2049)]
+    );
+
+    assert_eq!(
+        call(PortMapperRequest::Unset(mapping(IPPROTO_TCP, 0))).await,
+        PortMapperResponse::Unset(true)
+    );
+    assert_eq!(
+        call(PortMapperRequest::GetPort(mapping(IPPROTO_UDP, 0))).await,
+        PortMapperResponse::GetPort(0),
+        "UNSET removes every protocol"
+    );
+    assert_eq!(
+        call(PortMapperRequest::Unset(mapping(IPPROTO_TCP, 0))).await,
+        PortMapperResponse::Unset(false)
+    );
+    assert_eq!(
+        call(PortMapperRequest::Dump).await,
+        PortMapperResponse::Dump(Default::default())
+    );
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn callit_is_unavailable_on_connections() {
+    // Calls are only forwarded over UDP
+    let mut harness = Harness::new();
+    let request = RpcRequest::V2(PortMapperRequest::CallIt(CallArgs {
+        prog: NFS,
+        vers: 3,
+        proc: 0,
+        args: Vec::new(),
+    }));
+    assert_eq!(
+        failed(harness.call(request).await),
+        AcceptedStatus::ProcedureUnavailable
+    );
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn rpcbind_registrations() {
+    for version in [3, 4] {
+        let mut harness = Harness::new();
+        let mut call = async |request| {
+            let request = match version {
+                3 => RpcRequest::V3(request),
+                _ => RpcRequest::V4(request),
+            };
+            rpcbind(harness.call(request).await.unwrap())
+        };
+
+        assert_eq!(
+            call(RpcBindRequest::GetAddr(rpcb("tcp", ""))).await,
+            RpcBindResponse::GetAddr(String::new())
+        );
+        assert_eq!(
+            call(RpcBindRequest::Set(rpcb("tcp", "127.0.0.1.8.1"))).await,
+            RpcBindResponse::Set(true)
+        );
+        assert_eq!(
+            call(RpcBindRequest::Set(rpcb("tcp", "127.0.0.1.8.2"))).await,
+            RpcBindResponse::Set(false),
+            "like rpcbind, a registration is kept even against its owner"
+        );
+        assert_eq!(
+            call(RpcBindRequest::Set(rpcb("udp", "127.0.0.1.8.1"))).await,
+            RpcBindResponse::Set(true)
+        );
+        assert_eq!(
+            call(RpcBindRequest::GetAddr(rpcb("tcp", ""))).await,
+            RpcBindResponse::GetAddr("127.0.0.1.8.1".to_owned())
+        );
+
+        let RpcBindResponse::Dump(rpcbs) = call(RpcBindRequest::Dump).await else {
+            panic!("DUMP answered with something else");
+        };
+        let mut rpcbs = rpcbs.into_vec();
+        rpcbs.sort_by(|a, b| a.r_netid.cmp(&b.r_netid));
+        assert_eq!(
+            rpcbs,
+            [rpcb("tcp", "127.0.0.1.8.1"), rpcb("udp", "127.0.0.1.8.1")]
+        );
+
+        assert_eq!(
+            call(RpcBindRequest::Unset(rpcb("udp", ""))).await,
+            RpcBindResponse::Unset(true)
+        );
+        assert_eq!(
+            call(RpcBindRequest::GetAddr(rpcb("tcp", ""))).await,
+            RpcBindResponse::GetAddr("127.0.0.1.8.1".to_owned()),
+            "UNSET with a netid removes only that transport"
+        );
+        assert_eq!(
+            call(RpcBindRequest::Unset(rpcb("", ""))).await,
+            RpcBindResponse::Unset(true)
+        );
+        assert_eq!(
+            call(RpcBindRequest::Dump).await,
+            RpcBindResponse::Dump(Default::default())
+        );
+        harness.close().await;
+    }
+}
+
+/// The seconds left on each lease in the admin dump served at `path`, by netid.
+async fn leases(path: &Path) -> Vec<(String, Option<u64>)> {
+    let mut dump = String::new();
+    UnixStream::connect(path)
+        .await
+        .unwrap()
+        .read_to_string(&mut dump)
+        .await
+        .unwrap();
+    let mut leases: Vec<_> = dump
+        .lines()
+        .map(|line| {
+            let lease: Value = serde_json::from_str(line).unwrap();
+            let net_id = lease["key"]["net_id"].as_str().unwrap().to_owned();
+            (net_id, lease["ttl"].as_u64())
+        })
+        .collect();
+    leases.sort();
+    leases
+}
+
+#[tokio::test]
+async fn leases_are_renewed_by_set_until_swept() {
+    const TTL: Duration = Duration::from_secs(2);
+    let mut harness = Harness::with_registry(InMemoryRegistry::new().with_lease_ttl(Some(TTL)));
+    tokio::spawn(lease::sweep(harness.registry.clone()));
+    let admin = std::env::temp_dir().join(format!("rpcbind-admin-{}", std::process::id()));
+    tokio::spawn({
+        let (admin, registry) = (admin.clone(), harness.registry.clone());
+        async move { control::serve_dump(&admin, registry).await.unwrap() }
+    });
+
+    let set = RpcRequest::V4(RpcBindRequest::Set(rpcb("tcp", "127.0.0.1.8.1")));
+    assert_eq!(
+        harness.call(set.clone()).await.unwrap(),
+        RpcResponse::V4(RpcBindResponse::Set(true))
+    );
+    // Without an owner, as portmapper sets them, a registration is never leased
+    let unowned = RpcRequest::V2(PortMapperRequest::Set(mapping(IPPROTO_UDP, 2049)));
+    assert_eq!(
+        harness.call(unowned).await.unwrap(),
+        RpcResponse::V2(PortMapperResponse::Set(true))
+    );
+    while !admin.exists() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(
+        leases(&admin).await,
+        [("tcp".to_owned(), Some(1)), ("udp".to_owned(), None)]
+    );
+
+    tokio::time::sleep(TTL * 3 / 4).await;
+    assert_eq!(leases(&admin).await[0], ("tcp".to_owned(), Some(0)));
+    assert_eq!(
+        harness.call(set).await.unwrap(),
+        RpcResponse::V4(RpcBindResponse::Set(true)),
+        "the owner setting the same registration again renews it"
+    );
+    let renewed = Instant::now();
+    assert_eq!(leases(&admin).await[0], ("tcp".to_owned(), Some(1)));
+
+    let getaddr = RpcRequest::V4(RpcBindRequest::GetAddr(rpcb("tcp", "")));
+    while harness.call(getaddr.clone()).await.unwrap()
+        != RpcResponse::V4(RpcBindResponse::GetAddr(String::new()))
+    {
+        assert!(renewed.elapsed() < TTL * 3, "the lease was never swept");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(
+        renewed.elapsed() >= TTL,
+        "swept before the renewed lease ran out"
+    );
+    assert_eq!(leases(&admin).await, [("udp".to_owned(), None)]);
+    std::fs::remove_file(&admin).unwrap();
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn versions_share_the_registry() {
+    let mut harness = Harness::new();
+    let set = RpcRequest::V2(PortMapperRequest::Set(mapping(IPPROTO_TCP, 2049)));
+    assert_eq!(
+        harness.call(set).await.unwrap(),
+        RpcResponse::V2(PortMapperResponse::Set(true))
+    );
+    for request in both_versions(RpcBindRequest::GetAddr(rpcb("tcp", ""))) {
+        let RpcBindResponse::GetAddr(addr) = rpcbind(harness.call(request).await.unwrap()) else {
+            panic!("GETADDR answered with something else");
+        };
+        assert!(addr.ends_with(".8.1"), "{addr}");
+    }
+
+    let set = RpcRequest::V4(RpcBindRequest::Set(rpcb("udp", "127.0.0.1.8.1")));
+    assert_eq!(
+        harness.call(set).await.unwrap(),
+        RpcResponse::V4(RpcBindResponse::Set(true))
+    );
+    let getport = RpcRequest::V2(PortMapperRequest::GetPort(mapping(IPPROTO_UDP, 0)));
+    assert_eq!(
+        harness.call(getport).await.unwrap(),
+        RpcResponse::V2(PortMapperResponse::GetPort(2049))
+    );
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn set_rejects_addresses_of_another_family() {
+    let mut harness = Harness::new();
+    for request in both_versions(RpcBindRequest::Set(rpcb("tcp6", "127.0.0.1.8.1"))) {
+        assert_eq!(
+            failed(harness.call(request).await),
+            AcceptedStatus::GarbageArgs
+        );
+    }
+    assert_eq!(harness.registry.dump(), []);
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn gettime_is_the_current_time() {
+    let mut harness = Harness::new();
+    for request in both_versions(RpcBindRequest::GetTime) {
+        let before = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let RpcBindResponse::GetTime(time) = rpcbind(harness.call(request).await.unwrap()) else {
+            panic!("GETTIME answered with something else");
+        };
+        assert!((before..=before + 1).contains(&u64::from(time)), "{time}");
+    }
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn addr_list_follows_netconfig() {
+    let mut harness = Harness::new();
+    let mut call = async |request| rpcbind(harness.call(RpcRequest::V4(request)).await.unwrap());
+    for (netid, addr) in [
+        ("tcp6", "::1.8.1"),
+        ("tcp", "127.0.0.1.8.1"),
+        ("udp", "0.0.0.0.8.1"),
+    ] {
+        assert_eq!(
+            call(RpcBindRequest::Set(rpcb(netid, addr))).await,
+            RpcBindResponse::Set(true)
+        );
+    }
+    let other_version = RPCB {
+        r_vers: 4,
+        ..rpcb("udp6", "::.8.2")
+    };
+    call(RpcBindRequest::Set(other_version)).await;
+
+    let RpcBindResponse::GetAddrList(entries) =
+        call(RpcBindRequest::GetAddrList(rpcb("", ""))).await
+    else {
+        panic!("GETADDRLIST answered with something else");
+    };
+    let entry = |r_maddr: &str, r_nc_netid: &str, r_nc_semantics, r_nc_protofmly: &str| Entry {
+        r_maddr: r_maddr.to_owned(),
+        r_nc_netid: r_nc_netid.to_owned(),
+        r_nc_semantics,
+        r_nc_protofmly: r_nc_protofmly.to_owned(),
+        r_nc_proto: r_nc_netid.trim_end_matches('6').to_owned(),
+    };
+    // In the order of libtirpc's default netconfig
+    assert_eq!(
+        entries.into_vec(),
+        [
+            entry("0.0.0.0.8.1", "udp", Semantics::Clts.code(), "inet"),
+            entry("127.0.0.1.8.1", "tcp", Semantics::CotsOrd.code(), "inet"),
+            entry("::1.8.1", "tcp6", Semantics::CotsOrd.code(), "inet6"),
+        ]
+    );
+    assert_eq!(
+        call(RpcBindRequest::GetAddrList(RPCB {
+            r_vers: 5,
+            ..rpcb("", "")
+        }))
+        .await,
+        RpcBindResponse::GetAddrList(Default::default())
+    );
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn stats_count_calls() {
+    // Counts are shared by every test in this binary, so this one looks up a program of its own
+    const PROGRAM: u32 = 0x2000_0031;
+    let mut harness = Harness::new();
+    let lookup = RPCB {
+        r_prog: PROGRAM,
+        ..rpcb("tcp", "127.0.0.1.8.1")
+    };
+    for request in [
+        RpcBindRequest::GetAddr(lookup.clone()),
+        RpcBindRequest::Set(lookup.clone()),
+        RpcBindRequest::GetAddr(lookup.clone()),
+        RpcBindRequest::GetAddr(lookup),
+    ] {
+        harness.call(RpcRequest::V4(request)).await.unwrap();
+    }
+
+    let RpcBindResponse::GetStat(stats) = rpcbind(
+        harness
+            .call(RpcRequest::V4(RpcBindRequest::GetStat))
+            .await
+            .unwrap(),
+    ) else {
+        panic!("GETSTAT answered with something else");
+    };
+    let [_, _, v4] = &stats.0;
+    assert!(v4.info.0[3] >= 3, "GETADDR was called three times");
+    assert!(v4.info.0[12] >= 1, "GETSTAT counts itself");
+    assert!(v4.setinfo >= 1);
+    let lookups: Vec<_> = v4
+        .addrinfo
+        .iter()
+        .filter(|lookup| lookup.prog == PROGRAM)
+        .map(|lookup| {
+            (
+                lookup.vers,
+                lookup.netid.as_str(),
+                lookup.success,
+                lookup.failure,
+            )
+        })
+        .collect();
+    assert_eq!(lookups, [(3, "tcp", 2, 1)]);
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn unimplemented_procedures_are_unavailable() {
+    let mut harness = Harness::new();
+    let rmtcall = RmtCallArgs {
+        prog: NFS,
+        vers: 3,
+        proc: 0,
+        args: Vec::new(),
+    };
+    let netbuf = NetBuf {
+        maxlen: 16,
+        buf: vec![0, 2, 8, 1, 127, 0, 0, 1],
+    };
+    let requests = [
+        RpcBindRequest::Broadcast(rmtcall.clone()),
+        RpcBindRequest::UADDR2TADDR("127.0.0.1.8.1".to_owned()),
+        RpcBindRequest::TADDR2UADDR(netbuf),
+        RpcBindRequest::GETVERSADDR(rpcb("tcp", "")),
+        RpcBindRequest::Indirect(rmtcall),
+    ];
+    for request in requests {
+        let request = RpcRequest::V4(request);
+        let description = format!("{request:?}");
+        assert_eq!(
+            failed(harness.call(request).await),
+            AcceptedStatus::ProcedureUnavailable,
+            "{description}"
+        );
+    }
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn other_versions_are_mismatched() {
+    let mut harness = Harness::new();
+    for version in [0, 1, 5, u32::MAX] {
+        assert_eq!(
+            failed(harness.call_rpcbind(version, 0, Vec::new()).await),
+            AcceptedStatus::ProgramMismatch { low: 2, high: 4 },
+            "version {version}"
+        );
+    }
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn other_programs_are_unavailable() {
+    let mut harness = Harness::new();
+    assert_eq!(
+        failed(harness.call_raw(NFS, 3, 0, Vec::new()).await),
+        AcceptedStatus::ProgramUnavailable
+    );
+    assert_eq!(
+        failed(harness.call_raw(PROGRAM + 1, 2, 0, Vec::new()).await),
+        AcceptedStatus::ProgramUnavailable
+    );
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn unknown_procedures_are_unavailable() {
+    let mut harness = Harness::new();
+    // Version 3 stops at TADDR2UADDR
+    for (version, procedure) in [(2, 6), (3, 9), (3, 12), (3, 13), (4, 13), (4, u32::MAX)] {
+        assert_eq!(
+            failed(harness.call_rpcbind(version, procedure, Vec::new()).await),
+            AcceptedStatus::ProcedureUnavailable,
+            "version {version} procedure {procedure}"
+        );
+    }
+    harness.close().await;
+}
+
+#[tokio::test]
+async fn malformed_arguments_are_garbage() {
+    let mut harness = Harness::new();
+    let mut truncated = rpcb("tcp", "127.0.0.1.8.1").to_xdr();
+    truncated.truncate(truncated.len() - 8);
+    let too_long = rpcb(&"x".repeat(1000), "").to_xdr();
+    let calls = [
+        // GETPORT with half a mapping
+        (2, 3, mapping(IPPROTO_TCP, 0).to_xdr()[..8].to_vec()),
+        // SET with no arguments at all
+        (2, 1, Vec::new()),
+        (3, 1, truncated.clone()),
+        (4, 3, truncated),
+        // A netid past the limit
+        (4, 3, too_long),
+        // A string that is not UTF-8
+        (4, 7, vec![0, 0, 0, 2, 0xff, 0xfe, 0, 0]),
+    ];
+    for (version, procedure, payload) in calls {
+        assert_eq!(
+            failed(harness.call_rpcbind(version, procedure, payload).await),
+            AcceptedStatus::GarbageArgs,
+            "version {version} procedure {procedure}"
+        );
+    }
+
+    // The connection is still served after the errors
+    let response = harness.call(RpcRequest::V4(RpcBindRequest::Null)).await;
+    assert_eq!(response.unwrap(), RpcResponse::V4(RpcBindResponse::Null));
+    harness.close().await;
+}
@@ -0,0 +1,226 @@
+//! Forwarding over UDP, between servers and programs running on sockets of their own.
+
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+use onc_rpc::{
+    AcceptedReply, AcceptedStatus, MessageType, ReplyBody, RpcMessage, auth::AuthFlavor,
+};
+use rpcbind_rs::{
+    client::{BroadcastVersion, ClientError, broadcast},
+    netid::Netid,
+    request::{RpcBindRequest, RpcRequest},
+    response::{RpcBindResponse, RpcResponse},
+    universal_address::UniversalAddress,
+    xdr_types::rpcbind::{RmtCallArgs, RmtCallRes},
+};
+use rpcbind_server::{
+    registry::{InMemoryRegistry, Registry},
+    serve_udp,
+    state::{ProgramDescription, ProgramKey},
+};
+use tokio::net::UdpSocket;
+
+const ECHO: u32 = 200_000;
+
+/// Starts a server with a registry of its own on a UDP socket, returning its address.
+async fn server() -> (SocketAddr, Arc<InMemoryRegistry>) {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    let registry = Arc::new(InMemoryRegistry::new());
+    tokio::spawn({
+        let registry = registry.clone();
+        async move { serve_udp(socket, registry.as_ref()).await.unwrap() }
+    });
+    (addr, registry)
+}
+
+/// Starts a program answering every call with its arguments, registered as version 1 of
+/// [`ECHO`] in `registry`.
+async fn echo(registry: &InMemoryRegistry) -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut datagram = vec![0u8; 65535];
+        loop {
+            let (len, peer) = socket.recv_from(&mut datagram).await.unwrap();
+            let mut record = (len as u32 | 1 << 31).to_be_bytes().to_vec();
+            record.extend_from_slice(&datagram[..len]);
+            let call = RpcMessage::try_from(record.as_slice()).unwrap();
+            let body = call.call_body().unwrap();
+            assert_eq!(
+                (body.program(), body.program_version(), body.procedure()),
+                (ECHO, 1, 1)
+            );
+            let reply = RpcMessage::new(
+                call.xid(),
+                MessageType::Reply(ReplyBody::Accepted(AcceptedReply::new(
+                    AuthFlavor::<&[u8]>::AuthNone(None),
+                    AcceptedStatus::Success(*body.payload()),
+                ))),
+            );
+            socket
+                .send_to(&reply.serialise().unwrap()[4..], peer)
+                .await
+                .unwrap();
+        }
+    });
+    assert!(registry.set(
+        ProgramKey {
+            program: ECHO,
+            version: 1,
+            net_id: Netid::Udp,
+        },
+        ProgramDescription {
+            addr: addr.into(),
+            owner: None,
+        },
+    ));
+    addr
+}
+
+fn echo_args(vers: u32) -> RmtCallArgs {
+    RmtCallArgs {
+        prog: ECHO,
+        vers,
+        proc: 1,
+        args: vec![0, 0, 0, 7],
+    }
+}
+
+#[tokio::test]
+async fn broadcast_reaches_every_server_running_the_program() {
+    let (first, registry) = server().await;
+    let first_echo = echo(&registry).await;
+    let (second, registry) = server().await;
+    let second_echo = echo(&registry).await;
+    // Stays silent, as it has no program to forward to
+    let (third, _) = server().await;
+
+    for version in [
+        BroadcastVersion::V2,
+        BroadcastVersion::V3,
+        BroadcastVersion::V4,
+    ] {
+        let mut replies = broadcast(
+            &[first, second, third],
+            version,
+            echo_args(1),
+            Duration::from_millis(500),
+        )
+        .await
+        .unwrap();
+        replies.sort_by_key(|reply| reply.responder);
+        let found: Vec<_> = replies
+            .iter()
+            .map(|reply| (reply.responder, reply.universal_address.clone()))
+            .collect();
+        let mut expected = [
+            (first, UniversalAddress::from(first_echo).to_string()),
+            (second, UniversalAddress::from(second_echo).to_string()),
+        ];
+        expected.sort();
+        assert_eq!(found, expected, "{version:?}");
+        assert!(replies.iter().all(|reply| reply.results == [0, 0, 0, 7]));
+    }
+}
+
+/// Sends an `RPCBPROC_INDIRECT` call to the server at `addr`, returning the socket its reply
+/// comes back to.
+async fn send_indirect(addr: SocketAddr, args: RmtCallArgs) -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let request = RpcRequest::V4(RpcBindRequest::Indirect(args));
+    let call = RpcMessage::new(1, MessageType::Call(request.to_call_body().unwrap()));
+    socket
+        .send_to(&call.serialise().unwrap()[4..], addr)
+        .await
+        .unwrap();
+    socket
+}
+
+/// Makes an `RPCBPROC_INDIRECT` call to the server at `addr`.
+async fn indirect(addr: SocketAddr, args: RmtCallArgs) -> Result<RmtCallRes, ClientError> {
+    let request = RpcRequest::V4(RpcBindRequest::Indirect(args.clone()));
+    let socket = send_indirect(addr, args).await;
+    let mut datagram = vec![0u8; 65535];
+    let len = socket.recv(&mut datagram).await.unwrap();
+    let mut record = (len as u32 | 1 << 31).to_be_bytes().to_vec();
+    record.extend_from_slice(&datagram[..len]);
+    let reply = RpcMessage::try_from(record.as_slice()).unwrap();
+    match RpcResponse::from_reply(&request, reply.reply_body().unwrap())? {
+        RpcResponse::V4(RpcBindResponse::Indirect(result)) => Ok(result),
+        response => panic!("unexpected response {response:?}"),
+    }
+}
+
+#[tokio::test]
+async fn indirect_answers_when_it_cannot_forward() {
+    let (addr, registry) = server().await;
+    assert!(matches!(
+        indirect(addr, echo_args(1)).await,
+        Err(ClientError::Failed(AcceptedStatus::ProgramUnavailable))
+    ));
+
+    let echo = echo(&registry).await;
+    assert_eq!(
+        indirect(addr, echo_args(1)).await.unwrap(),
+        RmtCallRes {
+            addr: UniversalAddress::from(echo).to_string(),
+            results: vec![0, 0, 0, 7],
+        }
+    );
+    assert!(matches!(
+        indirect(addr, echo_args(2)).await,
+        Err(ClientError::Failed(AcceptedStatus::ProgramMismatch {
+            low: 1,
+            high: 1
+        }))
+    ));
+
+    // rpcbind does not call itself
+    let args = RmtCallArgs {
+        prog: 100000,
+        ..echo_args(4)
+    };
+    assert!(matches!(
+        indirect(addr, args).await,
+        Err(ClientError::Failed(AcceptedStatus::ProcedureUnavailable))
+    ));
+}
+
+#[tokio::test]
+async fn calls_beyond_the_forwarding_limit_are_dropped() {
+    // As many as forward.rs lets through at once
+    const MAX_FORWARDS: usize = 64;
+
+    let (addr, registry) = server().await;
+    // A program that never answers keeps every forward it is sent in flight
+    let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    assert!(registry.set(
+        ProgramKey {
+            program: ECHO,
+            version: 1,
+            net_id: Netid::Udp,
+        },
+        ProgramDescription {
+            addr: silent.local_addr().unwrap().into(),
+            owner: None,
+        },
+    ));
+    let mut waiting = Vec::new();
+    for _ in 0..MAX_FORWARDS {
+        waiting.push(send_indirect(addr, echo_args(1)).await);
+    }
+
+    // Would be answered with PROG_UNAVAIL straight away if there were room
+    let args = RmtCallArgs {
+        prog: ECHO + 1,
+        ..echo_args(1)
+    };
+    let socket = send_indirect(addr, args).await;
+    let mut datagram = vec![0u8; 65535];
+    assert!(
+        tokio::time::timeout(Duration::from_millis(500), socket.recv(&mut datagram))
+            .await
+            .is_err()
+    );
+}
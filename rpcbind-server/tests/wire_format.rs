@@ -0,0 +1,137 @@
+//! Replays the calls in `tests/fixtures` on a connection and compares each reply with the
+//! expected one byte for byte, pairing them by xid and ignoring the order of list entries.
+
+use std::{fs, path::Path};
+
+use onc_rpc::{AcceptedStatus, ReplyBody, RpcMessage};
+use rpcbind_rs::{
+    request::RpcRequest,
+    response::{PortMapperResponse, RpcBindResponse, RpcResponse},
+    xdr_types::{XdrList, codec::XdrCodec},
+};
+use rpcbind_server::{handle_client, registry::InMemoryRegistry};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// A call and its expected reply, after the comment describing them.
+struct Exchange {
+    description: String,
+    call: Vec<u8>,
+    reply: Vec<u8>,
+}
+
+fn parse_fixture(text: &str) -> Vec<Exchange> {
+    let mut exchanges = Vec::new();
+    let mut description = String::new();
+    let mut call = None;
+    for line in text.lines() {
+        let words = |line: &str| -> Vec<u8> {
+            line.split_whitespace()
+                .flat_map(|word| u32::from_str_radix(word, 16).unwrap().to_be_bytes())
+                .collect()
+        };
+        if let Some(comment) = line.strip_prefix("# ") {
+            description = comment.to_owned();
+        } else if let Some(line) = line.strip_prefix('>') {
+            call = Some(words(line));
+        } else if let Some(line) = line.strip_prefix('<') {
+            exchanges.push(Exchange {
+                description: description.clone(),
+                call: call.take().expect("a reply follows its call"),
+                reply: words(line),
+            });
+        }
+    }
+    exchanges
+}
+
+fn xid(record: &[u8]) -> u32 {
+    u32::from_be_bytes(record[4..8].try_into().unwrap())
+}
+
+fn split_records(mut bytes: &[u8]) -> Vec<Vec<u8>> {
+    let mut records = Vec::new();
+    while !bytes.is_empty() {
+        let len = (u32::from_be_bytes(bytes[..4].try_into().unwrap()) & !(1 << 31)) as usize;
+        let (record, rest) = bytes.split_at(4 + len);
+        records.push(record.to_vec());
+        bytes = rest;
+    }
+    records
+}
+
+fn sorted<T: XdrCodec>(list: XdrList<T>) -> Vec<u8> {
+    let mut entries = list.into_vec();
+    entries.sort_by_key(XdrCodec::to_xdr);
+    XdrList::from(entries).to_xdr()
+}
+
+/// The reply to `call` with the entries of any list sorted, as registries keep no order.
+fn canonical(call: &[u8], reply: &[u8]) -> Vec<u8> {
+    let call = RpcMessage::try_from(call).unwrap();
+    let Ok(request) = RpcRequest::from_body(call.call_body().unwrap()) else {
+        return reply.to_vec();
+    };
+    let message = RpcMessage::try_from(reply).unwrap();
+    let Some(ReplyBody::Accepted(accepted)) = message.reply_body() else {
+        return reply.to_vec();
+    };
+    let AcceptedStatus::Success(payload) = accepted.status() else {
+        return reply.to_vec();
+    };
+    let list = match RpcResponse::decode(&request, payload).unwrap() {
+        RpcResponse::V2(PortMapperResponse::Dump(mappings)) => sorted(mappings),
+        RpcResponse::V3(RpcBindResponse::Dump(rpcbs))
+        | RpcResponse::V4(RpcBindResponse::Dump(rpcbs)) => sorted(rpcbs),
+        _ => return reply.to_vec(),
+    };
+    let mut canonical = reply[..reply.len() - payload.len()].to_vec();
+    canonical.extend(list);
+    canonical
+}
+
+async fn replay(exchanges: &[Exchange]) -> Vec<Vec<u8>> {
+    let (mut client, server) = tokio::io::duplex(64 * 1024);
+    let registry = InMemoryRegistry::new();
+    let server = async { handle_client(server, &registry).await.unwrap() };
+    let client = async {
+        for exchange in exchanges {
+            client.write_all(&exchange.call).await.unwrap();
+        }
+        client.shutdown().await.unwrap();
+        let mut replies = Vec::new();
+        client.read_to_end(&mut replies).await.unwrap();
+        split_records(&replies)
+    };
+    tokio::join!(server, client).1
+}
+
+#[tokio::test]
+async fn replies_match_fixtures() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let mut paths: Vec<_> = fs::read_dir(fixtures)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let exchanges = parse_fixture(&fs::read_to_string(&path).unwrap());
+        let replies = replay(&exchanges).await;
+        assert_eq!(replies.len(), exchanges.len(), "{name}: number of replies");
+
+        for exchange in &exchanges {
+            let reply = replies
+                .iter()
+                .find(|reply| xid(reply) == xid(&exchange.call))
+                .unwrap_or_else(|| panic!("{name}: no reply to {}", exchange.description));
+            assert_eq!(
+                canonical(&exchange.call, reply),
+                canonical(&exchange.call, &exchange.reply),
+                "{name}: {}",
+                exchange.description
+            );
+        }
+    }
+}
@@ -0,0 +1,23 @@
+//! Serves an empty registry over TCP on an ephemeral port of 127.0.0.1, printing the port once
+//! it listens, for tests/capture/capture.sh to make its calls to.
+
+use std::sync::Arc;
+
+use rpcbind_server::{handle_client, registry::InMemoryRegistry};
+use tokio::net::TcpListener;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    println!("{}", listener.local_addr()?.port());
+    let registry = Arc::new(InMemoryRegistry::new());
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_client(stream, registry.as_ref()).await {
+                eprintln!("Error handling client {e:?}");
+            }
+        });
+    }
+}
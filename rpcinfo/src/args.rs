@@ -0,0 +1,163 @@
+use anyhow::{Result, anyhow, bail};
+use rpcbind_rs::{netid::Netid, rpc_names::RpcNames};
+
+pub const USAGE: &str = "\
+Usage: rpcinfo [--json] [-m | -s] [host]
+       rpcinfo [--json] -p [host]
+       rpcinfo -T netid host prognum [versnum]
+       rpcinfo [--json] -l host prognum versnum
+       rpcinfo -b prognum versnum
+       rpcinfo -d [-T netid] prognum versnum
+";
+
+const DEFAULT_HOST: &str = "localhost";
+
+/// The parsed command line.
+#[derive(Debug)]
+pub struct Args {
+    pub command: Command,
+    /// Print the result as JSON instead of libtirpc's columns.
+    pub json: bool,
+}
+
+/// What to ask rpcbind, chosen by the flags given on the command line.
+#[derive(Debug)]
+pub enum Command {
+    /// `rpcinfo [host]`: every registration, one per line.
+    Dump { host: String },
+    /// `rpcinfo -s [host]`: registrations grouped by program.
+    Summary { host: String },
+    /// `rpcinfo -p [host]`: the version 2 portmapper registrations.
+    PortMapperDump { host: String },
+    /// `rpcinfo -m [host]`: the server's call statistics.
+    Stats { host: String },
+    /// `rpcinfo -T netid host prognum [versnum]`: calls the program's NULL procedure.
+    Ping {
+        netid: Netid,
+        host: String,
+        program: u32,
+        version: Option<u32>,
+    },
+    /// `rpcinfo -l host prognum versnum`: every address the program is registered at.
+    AddrList {
+        host: String,
+        program: u32,
+        version: u32,
+    },
+    /// `rpcinfo -b prognum versnum`: finds every server on the local network running the program.
+    Broadcast { program: u32, version: u32 },
+    /// `rpcinfo -d [-T netid] prognum versnum`: removes a registration from the local rpcbind.
+    Delete {
+        netid: Option<Netid>,
+        program: u32,
+        version: u32,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    Default,
+    Summary,
+    PortMapper,
+    Stats,
+    AddrList,
+    Broadcast,
+    Delete,
+}
+
+impl Args {
+    /// Parses the arguments, resolving program names through `names`.
+    pub fn from_args(mut args: impl Iterator<Item = String>, names: &RpcNames) -> Result<Self> {
+        let mut mode = Mode::Default;
+        let mut netid = None;
+        let mut json = false;
+        let mut operands = Vec::new();
+        while let Some(arg) = args.next() {
+            let flag_mode = match arg.as_str() {
+                "-s" => Mode::Summary,
+                "-p" => Mode::PortMapper,
+                "-m" => Mode::Stats,
+                "-l" => Mode::AddrList,
+                "-b" => Mode::Broadcast,
+                "-d" => Mode::Delete,
+                "--json" => {
+                    json = true;
+                    continue;
+                }
+                "-T" => {
+                    let value = args.next().ok_or_else(|| anyhow!("-T requires a netid"))?;
+                    netid = Some(Netid::from(value.as_str()));
+                    continue;
+                }
+                flag if flag.starts_with('-') => bail!("unknown option {flag}"),
+                _ => {
+                    operands.push(arg);
+                    continue;
+                }
+            };
+            if mode != Mode::Default {
+                bail!("conflicting options");
+            }
+            mode = flag_mode;
+        }
+
+        let mut operands = operands.into_iter();
+        let command = match (mode, netid) {
+            (Mode::Default, Some(netid)) => Command::Ping {
+                netid,
+                host: operands.next().ok_or_else(|| anyhow!("missing host"))?,
+                program: program(operands.next(), names)?,
+                version: operands.next().map(|v| version(Some(v))).transpose()?,
+            },
+            (Mode::Delete, netid) => Command::Delete {
+                netid,
+                program: program(operands.next(), names)?,
+                version: version(operands.next())?,
+            },
+            (_, Some(_)) => bail!("-T can only be used to ping or with -d"),
+            (Mode::Broadcast, None) => Command::Broadcast {
+                program: program(operands.next(), names)?,
+                version: version(operands.next())?,
+            },
+            (Mode::AddrList, None) => Command::AddrList {
+                host: operands.next().ok_or_else(|| anyhow!("missing host"))?,
+                program: program(operands.next(), names)?,
+                version: version(operands.next())?,
+            },
+            (mode, None) => {
+                let host = operands.next().unwrap_or_else(|| DEFAULT_HOST.to_owned());
+                match mode {
+                    Mode::Summary => Command::Summary { host },
+                    Mode::PortMapper => Command::PortMapperDump { host },
+                    Mode::Stats => Command::Stats { host },
+                    _ => Command::Dump { host },
+                }
+            }
+        };
+        if operands.next().is_some() {
+            bail!("too many arguments");
+        }
+        if json
+            && matches!(
+                command,
+                Command::Ping { .. } | Command::Broadcast { .. } | Command::Delete { .. }
+            )
+        {
+            bail!("--json is only supported for dumps, -l and -m");
+        }
+        Ok(Self { command, json })
+    }
+}
+
+fn program(arg: Option<String>, names: &RpcNames) -> Result<u32> {
+    let arg = arg.ok_or_else(|| anyhow!("missing program number"))?;
+    names
+        .resolve(&arg)
+        .ok_or_else(|| anyhow!("{arg} is unknown service"))
+}
+
+fn version(arg: Option<String>) -> Result<u32> {
+    let arg = arg.ok_or_else(|| anyhow!("missing version number"))?;
+    arg.parse()
+        .map_err(|_| anyhow!("{arg} is illegal version number"))
+}
@@ -0,0 +1,321 @@
+use std::{
+    env,
+    net::{Ipv4Addr, SocketAddr, ToSocketAddrs},
+    process::ExitCode,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow, bail};
+use onc_rpc::AcceptedStatus;
+use rpcbind_rs::{
+    client::{
+        BroadcastVersion, ClientError, ClientResult, DEFAULT_TIMEOUT, Protocol,
+        RPCB_MULTICAST_ADDR, RpcBindVersion,
+        blocking::{self, PortMapperClient, RpcBindClient},
+    },
+    netid::Netid,
+    rpc_names::RpcNames,
+    universal_address::UniversalAddress,
+    xdr_types::rpcbind::{RPCB, RmtCallArgs},
+};
+use serde::Serialize;
+
+use crate::args::{Args, Command, USAGE};
+
+mod args;
+mod report;
+
+const RPCBIND_PORT: u16 = 111;
+const BROADCAST_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn main() -> ExitCode {
+    // Without the database, programs are shown and accepted by number only
+    let names = RpcNames::load().unwrap_or_default();
+    let args = match Args::from_args(env::args().skip(1), &names) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("rpcinfo: {e}");
+            eprint!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match run(args, &names) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("rpcinfo: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(Args { command, json }: Args, names: &RpcNames) -> Result<ExitCode> {
+    match command {
+        Command::Dump { host } => {
+            let entries = rpcbind_dump(resolve(&host, None)?).map_err(cant_contact("rpcbind"))?;
+            if json {
+                print_json(&entries)?;
+            } else {
+                print!("{}", report::dump(&entries, names));
+            }
+        }
+        Command::Summary { host } => {
+            let entries = rpcbind_dump(resolve(&host, None)?).map_err(cant_contact("rpcbind"))?;
+            let programs = report::summarize(&entries, names);
+            if json {
+                print_json(&programs)?;
+            } else {
+                print!("{}", report::summary(&programs));
+            }
+        }
+        Command::PortMapperDump { host } => {
+            let mut client = PortMapperClient::connect(resolve(&host, None)?, Protocol::Tcp)
+                .map_err(cant_contact("portmapper"))?;
+            let list = client.dump().map_err(cant_contact("portmapper"))?;
+            let mappings = list.into_vec();
+            if json {
+                print_json(&mappings)?;
+            } else {
+                print!("{}", report::port_mapper_dump(&mappings, names));
+            }
+        }
+        Command::Stats { host } => {
+            let mut client =
+                RpcBindClient::connect(resolve(&host, None)?, Protocol::Tcp, RpcBindVersion::V4)
+                    .map_err(cant_contact("rpcbind"))?;
+            let stats = client.get_stat().map_err(cant_contact("rpcbind"))?;
+            if json {
+                print_json(&report::stats_json(&stats, names))?;
+            } else {
+                print!("{}", report::stats(&stats, names));
+            }
+        }
+        Command::AddrList {
+            host,
+            program,
+            version,
+        } => {
+            let mut client =
+                RpcBindClient::connect(resolve(&host, None)?, Protocol::Tcp, RpcBindVersion::V4)
+                    .map_err(cant_contact("rpcbind"))?;
+            let list = client
+                .get_addr_list(rpcb(program, version, ""))
+                .map_err(cant_contact("rpcbind"))?;
+            let entries = list.into_vec();
+            if json {
+                print_json(&entries)?;
+            } else {
+                print!("{}", report::addr_list(program, version, &entries, names));
+            }
+        }
+        Command::Ping {
+            netid,
+            host,
+            program,
+            version,
+        } => return ping(&netid, &host, program, version),
+        Command::Broadcast { program, version } => return broadcast(program, version),
+        Command::Delete {
+            netid,
+            program,
+            version,
+        } => {
+            let local = SocketAddr::from(([127, 0, 0, 1], rpcbind_port()));
+            let rpcb = rpcb(program, version, netid.as_ref().map_or("", Netid::as_str));
+            let deleted = with_rpcbind(local, Protocol::Tcp, |client| client.unset(rpcb.clone()))
+                .map_err(cant_contact("rpcbind"))?;
+            if !deleted {
+                bail!("Could not delete registration for prog {program} version {version}");
+            }
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Calls the NULL procedure of every requested version, like `rpcinfo -T`.
+///
+/// Without a version, the program is first called with version 0 so the server's version
+/// mismatch reply tells which versions to try.
+fn ping(netid: &Netid, host: &str, program: u32, version: Option<u32>) -> Result<ExitCode> {
+    let (protocol, ipv6) = match netid {
+        Netid::Tcp => (Protocol::Tcp, false),
+        Netid::Udp => (Protocol::Udp, false),
+        Netid::Tcp6 => (Protocol::Tcp, true),
+        Netid::Udp6 => (Protocol::Udp, true),
+        _ => bail!("unsupported netid {netid}"),
+    };
+    let rpcbind = resolve(host, Some(ipv6))?;
+
+    let uaddr = match version {
+        Some(version) => with_rpcbind(rpcbind, protocol, |client| {
+            client.get_addr(rpcb(program, version, netid.as_str()))
+        })
+        .map_err(cant_contact("rpcbind"))?,
+        None => with_rpcbind(rpcbind, protocol, |client| client.dump())
+            .map_err(cant_contact("rpcbind"))?
+            .into_iter()
+            .find(|rpcb| rpcb.r_prog == program && rpcb.netid() == *netid)
+            .map(|rpcb| rpcb.r_addr)
+            .unwrap_or_default(),
+    };
+    if uaddr.is_empty() {
+        eprintln!("rpcinfo: RPC: Program not registered");
+        println!("program {program} is not available");
+        return Ok(ExitCode::FAILURE);
+    }
+    let addr = uaddr
+        .parse::<UniversalAddress>()
+        .ok()
+        .and_then(|address| address.socket_addr())
+        .ok_or_else(|| anyhow!("rpcbind returned the malformed address {uaddr}"))?;
+
+    let versions = match version {
+        Some(version) => version..=version,
+        None => match blocking::ping(addr, protocol, program, 0, DEFAULT_TIMEOUT) {
+            Ok(()) => 0..=0,
+            Err(ClientError::Failed(AcceptedStatus::ProgramMismatch { low, high })) => low..=high,
+            Err(e) => {
+                eprintln!("rpcinfo: {e}");
+                println!("program {program} is not available");
+                return Ok(ExitCode::FAILURE);
+            }
+        },
+    };
+    let mut code = ExitCode::SUCCESS;
+    for version in versions {
+        match blocking::ping(addr, protocol, program, version, DEFAULT_TIMEOUT) {
+            Ok(()) => println!("program {program} version {version} ready and waiting"),
+            Err(e) => {
+                eprintln!("rpcinfo: {e}");
+                println!("program {program} version {version} is not available");
+                code = ExitCode::FAILURE;
+            }
+        }
+    }
+    Ok(code)
+}
+
+/// Describes a failed call the way libtirpc's rpcinfo does.
+fn cant_contact(service: &str) -> impl FnOnce(ClientError) -> anyhow::Error {
+    move |e| anyhow!("can't contact {service}: {e}")
+}
+
+/// Calls the NULL procedure through every rpcbind on the local network, like `rpcinfo -b`.
+///
+/// IPv4 hosts are reached with a portmapper broadcast, which every rpcbind understands, and
+/// IPv6 hosts through rpcbind's multicast group.
+fn broadcast(program: u32, version: u32) -> Result<ExitCode> {
+    let args = RmtCallArgs {
+        prog: program,
+        vers: version,
+        proc: 0,
+        args: Vec::new(),
+    };
+    let targets = [
+        (
+            SocketAddr::from((Ipv4Addr::BROADCAST, RPCBIND_PORT)),
+            BroadcastVersion::V2,
+        ),
+        (
+            SocketAddr::from((RPCB_MULTICAST_ADDR, RPCBIND_PORT)),
+            BroadcastVersion::V4,
+        ),
+    ];
+    let results: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = targets
+            .into_iter()
+            .map(|(target, broadcast_version)| {
+                let args = args.clone();
+                scope.spawn(move || {
+                    blocking::broadcast(&[target], broadcast_version, args, BROADCAST_TIMEOUT)
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("broadcast thread panicked"))
+            .collect()
+    });
+
+    let mut printed = Vec::new();
+    let mut failure = None;
+    for result in results {
+        match result {
+            Ok(replies) => {
+                for reply in replies {
+                    if !printed.contains(&reply.universal_address) {
+                        println!("{} {}", reply.universal_address, reply.responder.ip());
+                        printed.push(reply.universal_address);
+                    }
+                }
+            }
+            Err(e) => failure = Some(e),
+        }
+    }
+    if printed.is_empty() {
+        let reason = failure.map_or_else(|| ClientError::TimedOut.to_string(), |e| e.to_string());
+        bail!("broadcast failed: {reason}");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Dumps every registration, falling back to version 3 for servers without version 4.
+fn rpcbind_dump(addr: SocketAddr) -> ClientResult<Vec<RPCB>> {
+    Ok(with_rpcbind(addr, Protocol::Tcp, |client| client.dump())?.into_vec())
+}
+
+/// Runs `call` against rpcbind version 4, retrying with version 3 if the server is too old.
+fn with_rpcbind<T>(
+    addr: SocketAddr,
+    protocol: Protocol,
+    call: impl Fn(&mut RpcBindClient) -> ClientResult<T>,
+) -> ClientResult<T> {
+    let mut client = RpcBindClient::connect(addr, protocol, RpcBindVersion::V4)?;
+    match call(&mut client) {
+        Err(ClientError::Failed(AcceptedStatus::ProgramMismatch { .. })) => call(
+            &mut RpcBindClient::connect(addr, protocol, RpcBindVersion::V3)?,
+        ),
+        result => result,
+    }
+}
+
+/// The port rpcbind is asked on, which `RPCBIND_PORT` overrides to reach a server listening
+/// elsewhere, as the tests do. Broadcasts always go to rpcbind's own port.
+fn rpcbind_port() -> u16 {
+    env::var("RPCBIND_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(RPCBIND_PORT)
+}
+
+/// Finds rpcbind on `host`, restricted to one address family if `ipv6` is given.
+///
+/// Without a family IPv4 is preferred, as that is what most servers listen on.
+fn resolve(host: &str, ipv6: Option<bool>) -> Result<SocketAddr> {
+    let addrs: Vec<SocketAddr> = (host, rpcbind_port())
+        .to_socket_addrs()
+        .map_err(|_| anyhow!("{host}: unknown host"))?
+        .collect();
+    let wanted = ipv6.unwrap_or(false);
+    addrs
+        .iter()
+        .find(|addr| addr.is_ipv6() == wanted)
+        .or(addrs.first().filter(|_| ipv6.is_none()))
+        .copied()
+        .ok_or_else(|| anyhow!("{host}: no address for the requested netid"))
+}
+
+fn rpcb(program: u32, version: u32, netid: &str) -> RPCB {
+    RPCB {
+        r_prog: program,
+        r_vers: version,
+        r_netid: netid.to_owned(),
+        r_addr: String::new(),
+        r_owner: String::new(),
+    }
+}
@@ -0,0 +1,506 @@
+//! Formats replies exactly like the libtirpc rpcinfo, so scripts parsing its output keep working.
+
+use std::fmt::Write;
+
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use rpcbind_rs::{
+    netconfig::Semantics,
+    rpc_names::RpcNames,
+    xdr_types::{
+        port_mapper::Mapping,
+        rpcbind::{Entry, RPCB, Stat, StatByVers},
+    },
+};
+
+const IPPROTO_TCP: u32 = 6;
+const IPPROTO_UDP: u32 = 17;
+
+/// The columns of `rpcinfo -m` end on multiples of this.
+const TABSTOP: usize = 8;
+
+/// Procedures shared by both protocols, whose counts `rpcinfo -m` singles out.
+const SET: usize = 1;
+const UNSET: usize = 2;
+const GETADDR: usize = 3;
+const CALLIT: usize = 5;
+const INDIRECT: usize = 10;
+
+const NOTHING_REGISTERED: &str = "No remote programs registered.\n";
+
+const PMAP_PROCS: [&str; 6] = ["NULL", "SET", "UNSET", "GETPORT", "DUMP", "CALLIT"];
+const RPCB_PROCS: [&str; 13] = [
+    "NULL", "SET", "UNSET", "GETADDR", "DUMP", "CALLIT", "TIME", "U2T", "T2U", "VERADDR",
+    "INDRECT", "GETLIST", "GETSTAT",
+];
+
+/// `rpcinfo -p`
+pub fn port_mapper_dump(mappings: &[Mapping], names: &RpcNames) -> String {
+    if mappings.is_empty() {
+        return NOTHING_REGISTERED.to_owned();
+    }
+    let mut out = String::from("   program vers proto   port  service\n");
+    for mapping in mappings {
+        write!(out, "{:>10}{:>5}", mapping.prog, mapping.vers).unwrap();
+        match mapping.prot {
+            IPPROTO_TCP => write!(out, "{:>6}", "tcp"),
+            IPPROTO_UDP => write!(out, "{:>6}", "udp"),
+            prot => write!(out, "{prot:>6}"),
+        }
+        .unwrap();
+        write!(out, "{:>7}", mapping.port).unwrap();
+        match names.name(mapping.prog) {
+            Some(name) => writeln!(out, "  {name}"),
+            None => writeln!(out),
+        }
+        .unwrap();
+    }
+    out
+}
+
+/// `rpcinfo [host]`
+pub fn dump(entries: &[RPCB], names: &RpcNames) -> String {
+    if entries.is_empty() {
+        return NOTHING_REGISTERED.to_owned();
+    }
+    let mut out =
+        String::from("   program version netid     address                service    owner\n");
+    for entry in entries {
+        writeln!(
+            out,
+            "{:>10}{:>5}    {:<9} {:<22} {:<10} {}",
+            entry.r_prog,
+            entry.r_vers,
+            entry.r_netid,
+            entry.r_addr,
+            names.name(entry.r_prog).unwrap_or("-"),
+            entry.r_owner,
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// The registrations of one program, as listed by `rpcinfo -s`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ProgramSummary {
+    pub program: u32,
+    pub versions: Vec<u32>,
+    pub netids: Vec<String>,
+    pub service: Option<String>,
+    pub owner: String,
+}
+
+/// Groups registrations by program.
+///
+/// Programs keep the order they were dumped in, while versions and netids are listed most
+/// recently seen first, as libtirpc prepends them to its lists.
+pub fn summarize(entries: &[RPCB], names: &RpcNames) -> Vec<ProgramSummary> {
+    let mut programs: Vec<ProgramSummary> = Vec::new();
+    for entry in entries {
+        let index = match programs.iter().position(|p| p.program == entry.r_prog) {
+            Some(index) => index,
+            None => {
+                programs.push(ProgramSummary {
+                    program: entry.r_prog,
+                    versions: Vec::new(),
+                    netids: Vec::new(),
+                    service: names.name(entry.r_prog).map(str::to_owned),
+                    owner: entry.r_owner.clone(),
+                });
+                programs.len() - 1
+            }
+        };
+        let program = &mut programs[index];
+        if !program.versions.contains(&entry.r_vers) {
+            program.versions.insert(0, entry.r_vers);
+        }
+        if !program.netids.contains(&entry.r_netid) {
+            program.netids.insert(0, entry.r_netid.clone());
+        }
+    }
+    programs
+}
+
+/// `rpcinfo -s`
+pub fn summary(programs: &[ProgramSummary]) -> String {
+    if programs.is_empty() {
+        return NOTHING_REGISTERED.to_owned();
+    }
+    let mut out =
+        String::from("   program version(s) netid(s)                         service     owner\n");
+    for program in programs {
+        let versions = program
+            .versions
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        writeln!(
+            out,
+            "{:>10}  {:<10}{:<32}  {:<11}{}",
+            program.program,
+            versions,
+            program.netids.join(","),
+            program.service.as_deref().unwrap_or("-"),
+            program.owner,
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// `rpcinfo -l`
+pub fn addr_list(program: u32, version: u32, entries: &[Entry], names: &RpcNames) -> String {
+    if entries.is_empty() {
+        return NOTHING_REGISTERED.to_owned();
+    }
+    let mut out = String::from("   program vers  tp_family/name/class    address\t\t  service\n");
+    for entry in entries {
+        let semantics = match Semantics::from_code(entry.r_nc_semantics) {
+            Some(Semantics::Clts) => "clts",
+            Some(Semantics::Cots) => "cots",
+            // libtirpc shows every other semantics as ordered
+            _ => "cots_ord",
+        };
+        let transport = format!(
+            "{}/{}/{} ",
+            entry.r_nc_protofmly, entry.r_nc_proto, semantics
+        );
+        writeln!(
+            out,
+            "{program:>10}{version:>3}    {transport:<24}{:<24} {:<13}",
+            entry.r_maddr,
+            names.name(program).unwrap_or("-"),
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// `rpcinfo -m --json`, with one object per protocol version.
+pub fn stats_json(stats: &StatByVers, names: &RpcNames) -> Value {
+    let [v2, v3, v4] = &stats.0;
+    [
+        (2, &PMAP_PROCS[..], v2),
+        (3, &RPCB_PROCS[..9], v3),
+        (4, &RPCB_PROCS[..], v4),
+    ]
+    .into_iter()
+    .map(|(version, procs, stat)| {
+        let procedures: serde_json::Map<String, Value> = procs
+            .iter()
+            .zip(stat.info.0)
+            .map(|(proc, count)| (proc.to_string(), count.into()))
+            .collect();
+        let lookups: Vec<Value> = stat
+            .addrinfo
+            .iter()
+            .map(|lookup| {
+                json!({
+                    "program": lookup.prog,
+                    "service": names.name(lookup.prog),
+                    "version": lookup.vers,
+                    "netid": lookup.netid,
+                    "success": lookup.success,
+                    "failure": lookup.failure,
+                })
+            })
+            .collect();
+        let calls: Vec<Value> = stat
+            .rmtinfo
+            .iter()
+            .map(|call| {
+                json!({
+                    "program": call.prog,
+                    "service": names.name(call.prog),
+                    "version": call.vers,
+                    "procedure": call.proc,
+                    "netid": call.netid,
+                    "indirect": call.indirect != 0,
+                    "success": call.success,
+                    "failure": call.failure,
+                })
+            })
+            .collect();
+        json!({
+            "version": version,
+            "procedures": procedures,
+            "set_successes": stat.setinfo,
+            "unset_successes": stat.unsetinfo,
+            "lookups": lookups,
+            "remote_calls": calls,
+        })
+    })
+    .collect()
+}
+
+/// `rpcinfo -m`
+pub fn stats(stats: &StatByVers, names: &RpcNames) -> String {
+    let [v2, v3, v4] = &stats.0;
+    let mut out = String::new();
+
+    writeln!(out, "PORTMAP (version 2) statistics").unwrap();
+    procedure_counts(&mut out, &PMAP_PROCS, 0, v2);
+    writeln!(out).unwrap();
+    if v2.info.0[CALLIT] != 0 {
+        writeln!(out, "PMAP_RMTCALL call statistics").unwrap();
+        remote_calls(&mut out, 2, v2, names);
+        writeln!(out).unwrap();
+    }
+    if v2.info.0[GETADDR] != 0 {
+        writeln!(out, "PMAP_GETPORT call statistics").unwrap();
+        lookups(&mut out, v2, names);
+        writeln!(out).unwrap();
+    }
+
+    writeln!(out, "RPCBIND (version 3) statistics").unwrap();
+    procedure_counts(&mut out, &RPCB_PROCS[..9], 0, v3);
+    writeln!(out).unwrap();
+    if v3.info.0[CALLIT] != 0 {
+        writeln!(out, "RPCB_RMTCALL (version 3) call statistics").unwrap();
+        remote_calls(&mut out, 3, v3, names);
+        writeln!(out).unwrap();
+    }
+    if v3.info.0[GETADDR] != 0 {
+        writeln!(out, "RPCB_GETADDR (version 3) call statistics").unwrap();
+        lookups(&mut out, v3, names);
+        writeln!(out).unwrap();
+    }
+
+    // Version 4 has too many procedures for one row, and no blank line after its last list
+    writeln!(out, "RPCBIND (version 4) statistics").unwrap();
+    procedure_counts(&mut out, &RPCB_PROCS[..9], 0, v4);
+    procedure_counts(&mut out, &RPCB_PROCS[9..], 9, v4);
+    if v4.info.0[CALLIT] != 0 || v4.info.0[INDIRECT] != 0 {
+        writeln!(out, "\nRPCB_RMTCALL (version 4) call statistics").unwrap();
+        remote_calls(&mut out, 4, v4, names);
+    }
+    if v4.info.0[GETADDR] != 0 {
+        writeln!(out, "\nRPCB_GETADDR (version 4) call statistics").unwrap();
+        lookups(&mut out, v4, names);
+    }
+    out
+}
+
+/// A row of procedure names over their counts, from the procedure numbered `first`.
+///
+/// Each column is padded to the tab stop after its count, with the successful calls before the
+/// count for the procedures that can fail.
+fn procedure_counts(out: &mut String, procs: &[&str], first: usize, stat: &Stat) {
+    let lookup_successes = stat
+        .addrinfo
+        .iter()
+        .fold(0i32, |sum, lookup| sum.wrapping_add(lookup.success));
+    let call_successes = stat
+        .rmtinfo
+        .iter()
+        .fold(0i32, |sum, call| sum.wrapping_add(call.success));
+    let mut header = String::new();
+    let mut counts = String::new();
+    for (index, proc) in (first..).zip(procs) {
+        let calls = stat.info.0[index];
+        let count = match index {
+            SET => format!("{}/{calls}", stat.setinfo),
+            UNSET => format!("{}/{calls}", stat.unsetinfo),
+            GETADDR => format!("{lookup_successes}/{calls}"),
+            CALLIT => format!("{call_successes}/{calls}"),
+            _ => calls.to_string(),
+        };
+        let width = TABSTOP * (1 + count.len() / TABSTOP);
+        write!(header, "{proc:<width$}").unwrap();
+        write!(counts, "{count:<width$}").unwrap();
+    }
+    writeln!(out, "{header}\n{counts}").unwrap();
+}
+
+/// The calls forwarded by `version`, which from version 4 tells how many were indirect.
+fn remote_calls(out: &mut String, version: u32, stat: &Stat, names: &RpcNames) {
+    if version == 4 {
+        writeln!(out, "prog\t\tvers\tproc\tnetid\tindirect success failure").unwrap();
+    } else {
+        writeln!(out, "prog\t\tvers\tproc\tnetid\tsuccess\tfailure").unwrap();
+    }
+    for call in stat.rmtinfo.iter() {
+        write!(
+            out,
+            "{:<16}{}\t{}\t{}\t",
+            program_name(call.prog, names),
+            call.vers,
+            call.proc,
+            call.netid,
+        )
+        .unwrap();
+        if version == 4 {
+            write!(out, "{}\t ", call.indirect).unwrap();
+        }
+        writeln!(out, "{}\t{}", call.success, call.failure).unwrap();
+    }
+}
+
+fn lookups(out: &mut String, stat: &Stat, names: &RpcNames) {
+    writeln!(out, "prog\t\tvers\tnetid\t  success\tfailure").unwrap();
+    for lookup in stat.addrinfo.iter() {
+        writeln!(
+            out,
+            "{:<16}{}\t{}\t  {:<12}\t{}",
+            program_name(lookup.prog, names),
+            lookup.vers,
+            lookup.netid,
+            lookup.success,
+            lookup.failure,
+        )
+        .unwrap();
+    }
+}
+
+fn program_name(program: u32, names: &RpcNames) -> String {
+    names
+        .name(program)
+        .map(str::to_owned)
+        .unwrap_or_else(|| program.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use rpcbind_rs::{
+        rpc_names::RpcNames,
+        xdr_types::{
+            port_mapper::Mapping,
+            rpcbind::{AddrList, Proc, RPCB, RmtCallList, RpcbsAddr, Stat, StatByVers},
+        },
+    };
+    use serde_json::json;
+
+    use super::{dump, port_mapper_dump, stats_json, summarize, summary};
+
+    fn names() -> RpcNames {
+        RpcNames::parse("portmapper\t100000\tportmap sunrpc rpcbind\nnfs\t\t100003\tnfsprog\n")
+    }
+
+    fn rpcb(r_prog: u32, r_vers: u32, r_netid: &str, r_addr: &str) -> RPCB {
+        RPCB {
+            r_prog,
+            r_vers,
+            r_netid: r_netid.to_owned(),
+            r_addr: r_addr.to_owned(),
+            r_owner: "superuser".to_owned(),
+        }
+    }
+
+    fn registrations() -> Vec<RPCB> {
+        vec![
+            rpcb(100000, 4, "tcp6", "::.0.111"),
+            rpcb(100000, 3, "tcp6", "::.0.111"),
+            rpcb(100000, 4, "tcp", "0.0.0.0.0.111"),
+            rpcb(100000, 3, "tcp", "0.0.0.0.0.111"),
+            rpcb(100000, 2, "tcp", "0.0.0.0.0.111"),
+            rpcb(100000, 2, "udp", "0.0.0.0.0.111"),
+            rpcb(100024, 1, "udp", "0.0.0.0.160.13"),
+        ]
+    }
+
+    #[test]
+    fn port_mapper_dump_format() {
+        let mappings = [
+            Mapping {
+                prog: 100000,
+                vers: 4,
+                prot: 6,
+                port: 111,
+            },
+            Mapping {
+                prog: 100024,
+                vers: 1,
+                prot: 17,
+                port: 41037,
+            },
+        ];
+        assert_eq!(
+            port_mapper_dump(&mappings, &names()),
+            "   program vers proto   port  service\n\
+             \x20   100000    4   tcp    111  portmapper\n\
+             \x20   100024    1   udp  41037\n"
+        );
+        assert_eq!(
+            port_mapper_dump(&[], &names()),
+            "No remote programs registered.\n"
+        );
+    }
+
+    #[test]
+    fn dump_format() {
+        let out = dump(&registrations(), &names());
+        let mut lines = out.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "   program version netid     address                service    owner"
+        );
+        assert_eq!(
+            lines.next().unwrap(),
+            "    100000    4    tcp6      ::.0.111               portmapper superuser"
+        );
+        assert_eq!(
+            lines.last().unwrap(),
+            "    100024    1    udp       0.0.0.0.160.13         -          superuser"
+        );
+    }
+
+    #[test]
+    fn summary_groups_by_program() {
+        assert_eq!(
+            summary(&summarize(&registrations(), &names())),
+            "   program version(s) netid(s)                         service     owner\n\
+             \x20   100000  2,3,4     udp,tcp,tcp6                      portmapper superuser\n\
+             \x20   100024  1         udp                               -          superuser\n"
+        );
+    }
+
+    #[test]
+    fn stats_json_flattens_lists() {
+        let stat = |addrinfo| Stat {
+            info: Proc([1; 13]),
+            setinfo: 1,
+            unsetinfo: 0,
+            addrinfo,
+            rmtinfo: RmtCallList::new(),
+        };
+        let lookup = |prog| RpcbsAddr {
+            prog,
+            vers: 1,
+            success: 2,
+            failure: 0,
+            netid: "udp".to_owned(),
+        };
+        let lookups = AddrList::from(vec![lookup(100000), lookup(100024)]);
+        let stats = StatByVers([stat(lookups), stat(AddrList::new()), stat(AddrList::new())]);
+
+        let json = stats_json(&stats, &names());
+        assert_eq!(json[0]["version"], 2);
+        assert_eq!(json[0]["procedures"].as_object().unwrap().len(), 6);
+        assert_eq!(json[0]["procedures"]["GETPORT"], 1);
+        assert_eq!(
+            json[0]["lookups"],
+            json!([
+                {
+                    "program": 100000,
+                    "service": "portmapper",
+                    "version": 1,
+                    "netid": "udp",
+                    "success": 2,
+                    "failure": 0,
+                },
+                {
+                    "program": 100024,
+                    "service": null,
+                    "version": 1,
+                    "netid": "udp",
+                    "success": 2,
+                    "failure": 0,
+                },
+            ])
+        );
+        assert_eq!(json[2]["procedures"].as_object().unwrap().len(), 13);
+    }
+}
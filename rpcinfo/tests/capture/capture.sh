@@ -0,0 +1,27 @@
+#!/bin/sh
+# Regenerates tests/fixtures from libtirpc's client, which needs gcc and libtirpc's headers
+# (libtirpc-dev on Debian).
+#
+# tests/capture/rpcinfo.c makes the calls of tests/cli.rs to a fresh server from the serve
+# example, then asks for the address list and statistics, printing them the way rpcbind's
+# rpcinfo does. The server is rpcbind-rs, as GETSTAT counts the calls of the server answering
+# them, but every reply is decoded by libtirpc.
+set -eu
+
+cd "$(dirname "$0")/../.."
+build=$(mktemp -d)
+server=
+trap 'kill $server 2>/dev/null || true; rm -rf "$build"' EXIT
+gcc -Wall -Werror -I/usr/include/tirpc -o "$build/rpcinfo" tests/capture/rpcinfo.c -ltirpc
+cargo build -q -p rpcinfo --example serve
+
+../target/debug/examples/serve >"$build/port" &
+server=$!
+while [ ! -s "$build/port" ]; do sleep 0.1; done
+capture() {
+	"$build/rpcinfo" 127.0.0.1 "$(cat "$build/port")" "$@"
+}
+capture calls
+capture -l 536870913 1 >tests/fixtures/addr_list.txt
+capture -l 536870913 2 >tests/fixtures/addr_list_empty.txt
+capture -m >tests/fixtures/stats.txt
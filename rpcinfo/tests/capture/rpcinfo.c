@@ -0,0 +1,318 @@
+/*
+ * Makes the calls of tests/cli.rs with libtirpc's client, printing the answers to `rpcinfo -l`
+ * and `rpcinfo -m` the way rpcbind's rpcinfo does, for capture.sh to record.
+ *
+ * Usage: rpcinfo HOST PORT calls
+ *        rpcinfo HOST PORT -l PROGNUM VERSNUM
+ *        rpcinfo HOST PORT -m
+ *
+ * `calls` makes the registrations and lookups the output reflects, printing nothing. The
+ * printing follows rpcbaddrlist() and rpcbgetstat() of rpcinfo.c in rpcbind 1.2.6, with the
+ * rows of procedure counts rpcbgetstat() repeats for each version in print_procs(), and the
+ * client connecting to PORT instead of to rpcbind's.
+ */
+
+#include <arpa/inet.h>
+#include <netconfig.h>
+#include <netdb.h>
+#include <netinet/in.h>
+#include <rpc/rpc.h>
+#include <rpc/pmap_prot.h>
+#include <rpc/rpcb_prot.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define TABSTOP 8
+#define MAXFIELD 64
+#define MAXLINE 256
+
+#define PROGRAM 0x20000001
+#define PMAP_PROGRAM 0x20000002
+
+static CLIENT *client;
+static struct timeval minutetimeout = { 60, 0 };
+
+static void call(rpcvers_t version, rpcproc_t procedure, xdrproc_t encode, void *args,
+		 xdrproc_t decode, void *result)
+{
+	enum clnt_stat status;
+
+	clnt_control(client, CLSET_VERS, (char *)&version);
+	status = clnt_call(client, procedure, encode, args, decode, result, minutetimeout);
+	if (status != RPC_SUCCESS) {
+		clnt_perror(client, "rpcinfo: can't contact rpcbind");
+		exit(1);
+	}
+}
+
+static void pmap(rpcproc_t procedure, rpcprog_t program, u_long protocol, u_long port)
+{
+	struct pmap map = { program, 1, protocol, port };
+	u_long result;
+
+	call(PMAPVERS, procedure, (xdrproc_t)xdr_pmap, &map,
+	     procedure == PMAPPROC_GETPORT ? (xdrproc_t)xdr_u_long : (xdrproc_t)xdr_bool, &result);
+}
+
+static void rpcbind(rpcvers_t version, rpcproc_t procedure, rpcvers_t program_version,
+		 char *netid, char *addr)
+{
+	rpcb parms = { PROGRAM, program_version, netid, addr, "test" };
+	char *uaddr = NULL;
+	bool_t result;
+
+	if (procedure == RPCBPROC_GETADDR) {
+		call(version, procedure, (xdrproc_t)xdr_rpcb, &parms, (xdrproc_t)xdr_wrapstring,
+		     &uaddr);
+		xdr_free((xdrproc_t)xdr_wrapstring, (char *)&uaddr);
+	} else {
+		call(version, procedure, (xdrproc_t)xdr_rpcb, &parms, (xdrproc_t)xdr_bool,
+		     &result);
+	}
+}
+
+/* The calls whose counts and registrations the fixtures show. */
+static void calls(void)
+{
+	struct pmaplist *list = NULL;
+	u_int32_t now;
+
+	call(PMAPVERS, PMAPPROC_NULL, (xdrproc_t)xdr_void, NULL, (xdrproc_t)xdr_void, NULL);
+	pmap(PMAPPROC_SET, PMAP_PROGRAM, IPPROTO_UDP, 2052);
+	pmap(PMAPPROC_SET, PMAP_PROGRAM, IPPROTO_UDP, 2053);
+	pmap(PMAPPROC_GETPORT, PMAP_PROGRAM, IPPROTO_UDP, 0);
+	pmap(PMAPPROC_GETPORT, PMAP_PROGRAM, IPPROTO_TCP, 0);
+	call(PMAPVERS, PMAPPROC_DUMP, (xdrproc_t)xdr_void, NULL, (xdrproc_t)xdr_pmaplist_ptr,
+	     &list);
+	xdr_free((xdrproc_t)xdr_pmaplist_ptr, (char *)&list);
+
+	rpcbind(RPCBVERS, RPCBPROC_SET, 2, "tcp", "127.0.0.1.8.4");
+	rpcbind(RPCBVERS, RPCBPROC_GETADDR, 2, "tcp", "");
+	rpcbind(RPCBVERS, RPCBPROC_UNSET, 2, "", "");
+	rpcbind(RPCBVERS, RPCBPROC_GETADDR, 2, "tcp", "");
+
+	rpcbind(RPCBVERS4, RPCBPROC_SET, 1, "udp", "127.0.0.1.8.1");
+	rpcbind(RPCBVERS4, RPCBPROC_SET, 1, "tcp", "127.0.0.1.8.2");
+	rpcbind(RPCBVERS4, RPCBPROC_SET, 1, "tcp6", "::1.8.3");
+	rpcbind(RPCBVERS4, RPCBPROC_UNSET, 3, "", "");
+	rpcbind(RPCBVERS4, RPCBPROC_GETADDR, 1, "udp", "");
+	rpcbind(RPCBVERS4, RPCBPROC_GETADDR, 1, "tcp6", "");
+	rpcbind(RPCBVERS4, RPCBPROC_GETADDR, 1, "udp", "");
+	rpcbind(RPCBVERS4, RPCBPROC_GETADDR, 3, "tcp", "");
+	call(RPCBVERS4, RPCBPROC_GETTIME, (xdrproc_t)xdr_void, NULL, (xdrproc_t)xdr_u_int32_t,
+	     &now);
+}
+
+static void rpcbaddrlist(rpcprog_t program, rpcvers_t version)
+{
+	rpcb_entry_list_ptr head = NULL;
+	struct rpcent *rpc;
+	rpcb parms = { program, version, "tcp", "", "" };
+
+	call(RPCBVERS4, RPCBPROC_GETADDRLIST, (xdrproc_t)xdr_rpcb, &parms,
+	     (xdrproc_t)xdr_rpcb_entry_list_ptr, &head);
+	if (head == NULL) {
+		printf("No remote programs registered.\n");
+	} else {
+		printf("   program vers  tp_family/name/class    address\t\t  service\n");
+		for (; head != NULL; head = head->rpcb_entry_next) {
+			rpcb_entry *re;
+			char buf[128];
+
+			re = &head->rpcb_entry_map;
+			printf("%10u%3u    ", parms.r_prog, parms.r_vers);
+			sprintf(buf, "%s/%s/%s ", re->r_nc_protofmly, re->r_nc_proto,
+				re->r_nc_semantics == NC_TPI_CLTS ? "clts" :
+				re->r_nc_semantics == NC_TPI_COTS ? "cots" :
+								     "cots_ord");
+			printf("%-24s", buf);
+			printf("%-24s", re->r_maddr);
+			rpc = getrpcbynumber(parms.r_prog);
+			if (rpc)
+				printf(" %-13s", rpc->r_name);
+			else
+				printf(" %-13s", "-");
+			printf("\n");
+		}
+	}
+}
+
+static char *spaces(int howmany)
+{
+	static char space_array[] = /* 64 spaces */
+		"                                                                ";
+
+	if (howmany <= 0 || howmany > sizeof(space_array)) {
+		return ("");
+	}
+	return (&space_array[sizeof(space_array) - howmany - 1]);
+}
+
+static void print_rmtcallstat(int rtype, rpcb_stat *infp)
+{
+	rpcbs_rmtcalllist_ptr pr;
+	struct rpcent *rpc;
+
+	if (rtype == RPCBVERS_4_STAT)
+		printf("prog\t\tvers\tproc\tnetid\tindirect success failure\n");
+	else
+		printf("prog\t\tvers\tproc\tnetid\tsuccess\tfailure\n");
+	for (pr = infp->rmtinfo; pr; pr = pr->next) {
+		rpc = getrpcbynumber(pr->prog);
+		if (rpc)
+			printf("%-16s", rpc->r_name);
+		else
+			printf("%-16d", pr->prog);
+		printf("%d\t%d\t%s\t", pr->vers, pr->proc, pr->netid);
+		if (rtype == RPCBVERS_4_STAT)
+			printf("%d\t ", pr->indirect);
+		printf("%d\t%d\n", pr->success, pr->failure);
+	}
+}
+
+static void print_getaddrstat(int rtype, rpcb_stat *infp)
+{
+	rpcbs_addrlist_ptr al;
+	struct rpcent *rpc;
+
+	printf("prog\t\tvers\tnetid\t  success\tfailure\n");
+	for (al = infp->addrinfo; al; al = al->next) {
+		rpc = getrpcbynumber(al->prog);
+		if (rpc)
+			printf("%-16s", rpc->r_name);
+		else
+			printf("%-16d", al->prog);
+		printf("%d\t%s\t  %-12d\t%d\n", al->vers, al->netid, al->success, al->failure);
+	}
+}
+
+/* The counts of procedures FIRST to LAST of version RTYPE, under the names in HDR. */
+static void print_procs(rpcb_stat_byvers inf, int rtype, const char **hdr, int first, int last,
+			int set, int unset, int getaddr, int callit)
+{
+	char fieldbuf[MAXFIELD];
+	char linebuf[MAXLINE];
+	char *cp, *lp;
+	int i, flen, cnt;
+	rpcbs_addrlist_ptr pa;
+	rpcbs_rmtcalllist_ptr pr;
+
+	lp = linebuf;
+	for (i = first; i <= last; i++) {
+		fieldbuf[0] = '\0';
+		if (i == set) {
+			sprintf(fieldbuf, "%d/", inf[rtype].setinfo);
+		} else if (i == unset) {
+			sprintf(fieldbuf, "%d/", inf[rtype].unsetinfo);
+		} else if (i == getaddr) {
+			cnt = 0;
+			for (pa = inf[rtype].addrinfo; pa; pa = pa->next)
+				cnt += pa->success;
+			sprintf(fieldbuf, "%d/", cnt);
+		} else if (i == callit) {
+			cnt = 0;
+			for (pr = inf[rtype].rmtinfo; pr; pr = pr->next)
+				cnt += pr->success;
+			sprintf(fieldbuf, "%d/", cnt);
+		}
+		cp = &fieldbuf[0] + strlen(fieldbuf);
+		sprintf(cp, "%d", inf[rtype].info[i]);
+		flen = strlen(fieldbuf);
+		printf("%s%s", hdr[i], spaces((TABSTOP * (1 + flen / TABSTOP)) - strlen(hdr[i])));
+		sprintf(lp, "%s%s", fieldbuf, spaces(cnt = ((TABSTOP * (1 + flen / TABSTOP)) - flen)));
+		lp += (flen + cnt);
+	}
+	printf("\n%s\n", linebuf);
+}
+
+static void rpcbgetstat(void)
+{
+	rpcb_stat_byvers inf;
+	int j;
+	const char *pmaphdr[] = { "NULL", "SET", "UNSET", "GETPORT", "DUMP", "CALLIT" };
+	const char *rpcbhdr[] = { "NULL", "SET",     "UNSET",   "GETADDR", "DUMP",
+				  "CALLIT", "TIME",  "U2T",     "T2U",     "VERADDR",
+				  "INDRECT", "GETLIST", "GETSTAT" };
+
+	memset((char *)&inf, 0, sizeof(rpcb_stat_byvers));
+	call(RPCBVERS4, RPCBPROC_GETSTAT, (xdrproc_t)xdr_void, NULL,
+	     (xdrproc_t)xdr_rpcb_stat_byvers, &inf);
+
+	printf("PORTMAP (version 2) statistics\n");
+	print_procs(inf, RPCBVERS_2_STAT, pmaphdr, 0, rpcb_highproc_2, PMAPPROC_SET,
+		    PMAPPROC_UNSET, PMAPPROC_GETPORT, PMAPPROC_CALLIT);
+	printf("\n");
+	if (inf[RPCBVERS_2_STAT].info[PMAPPROC_CALLIT]) {
+		printf("PMAP_RMTCALL call statistics\n");
+		print_rmtcallstat(RPCBVERS_2_STAT, &inf[RPCBVERS_2_STAT]);
+		printf("\n");
+	}
+	if (inf[RPCBVERS_2_STAT].info[PMAPPROC_GETPORT]) {
+		printf("PMAP_GETPORT call statistics\n");
+		print_getaddrstat(RPCBVERS_2_STAT, &inf[RPCBVERS_2_STAT]);
+		printf("\n");
+	}
+
+	printf("RPCBIND (version 3) statistics\n");
+	print_procs(inf, RPCBVERS_3_STAT, rpcbhdr, 0, rpcb_highproc_3, RPCBPROC_SET,
+		    RPCBPROC_UNSET, RPCBPROC_GETADDR, RPCBPROC_CALLIT);
+	printf("\n");
+	if (inf[RPCBVERS_3_STAT].info[RPCBPROC_CALLIT]) {
+		printf("RPCB_RMTCALL (version 3) call statistics\n");
+		print_rmtcallstat(RPCBVERS_3_STAT, &inf[RPCBVERS_3_STAT]);
+		printf("\n");
+	}
+	if (inf[RPCBVERS_3_STAT].info[RPCBPROC_GETADDR]) {
+		printf("RPCB_GETADDR (version 3) call statistics\n");
+		print_getaddrstat(RPCBVERS_3_STAT, &inf[RPCBVERS_3_STAT]);
+		printf("\n");
+	}
+
+	printf("RPCBIND (version 4) statistics\n");
+	for (j = 0; j <= 9; j += 9) { /* Just two iterations for printing */
+		print_procs(inf, RPCBVERS_4_STAT, rpcbhdr, j,
+			    rpcb_highproc_4 - 9 + j > 8 ? rpcb_highproc_4 - 9 + j : 8,
+			    RPCBPROC_SET, RPCBPROC_UNSET, RPCBPROC_GETADDR, RPCBPROC_CALLIT);
+	}
+	if (inf[RPCBVERS_4_STAT].info[RPCBPROC_CALLIT] ||
+	    inf[RPCBVERS_4_STAT].info[RPCBPROC_INDIRECT]) {
+		printf("\n");
+		printf("RPCB_RMTCALL (version 4) call statistics\n");
+		print_rmtcallstat(RPCBVERS_4_STAT, &inf[RPCBVERS_4_STAT]);
+	}
+	if (inf[RPCBVERS_4_STAT].info[RPCBPROC_GETADDR]) {
+		printf("\n");
+		printf("RPCB_GETADDR (version 4) call statistics\n");
+		print_getaddrstat(RPCBVERS_4_STAT, &inf[RPCBVERS_4_STAT]);
+	}
+}
+
+int main(int argc, char **argv)
+{
+	struct sockaddr_in addr = { .sin_family = AF_INET };
+	int sock = RPC_ANYSOCK;
+
+	if (argc < 4 || inet_pton(AF_INET, argv[1], &addr.sin_addr) != 1) {
+		fprintf(stderr, "usage: %s HOST PORT calls | -l PROGNUM VERSNUM | -m\n", argv[0]);
+		return 2;
+	}
+	addr.sin_port = htons(atoi(argv[2]));
+	client = clnttcp_create(&addr, RPCBPROG, RPCBVERS4, &sock, 0, 0);
+	if (client == NULL) {
+		fprintf(stderr, "%s\n", clnt_spcreateerror(argv[1]));
+		return 1;
+	}
+	if (argc == 4 && strcmp(argv[3], "calls") == 0) {
+		calls();
+	} else if (argc == 6 && strcmp(argv[3], "-l") == 0) {
+		rpcbaddrlist(strtoul(argv[4], NULL, 10), strtoul(argv[5], NULL, 10));
+	} else if (argc == 4 && strcmp(argv[3], "-m") == 0) {
+		rpcbgetstat();
+	} else {
+		fprintf(stderr, "usage: %s HOST PORT calls | -l PROGNUM VERSNUM | -m\n", argv[0]);
+		return 2;
+	}
+	clnt_destroy(client);
+	return 0;
+}
@@ -0,0 +1,122 @@
+//! `rpcinfo -l` and `rpcinfo -m` against rpcbind-rs, compared with what libtirpc's client
+//! printed after making the same calls, as recorded by tests/capture/capture.sh.
+//!
+//! GETSTAT counts every call the process answers, so the calls are made in a single test, in the
+//! order tests/capture/rpcinfo.c makes them.
+
+use std::{net::SocketAddr, process::Command, sync::Arc, thread};
+
+use rpcbind_rs::{
+    client::{
+        Protocol, RpcBindVersion,
+        blocking::{PortMapperClient, RpcBindClient},
+    },
+    netid::{IPPROTO_TCP, IPPROTO_UDP},
+    xdr_types::{port_mapper::Mapping, rpcbind::RPCB},
+};
+use rpcbind_server::{handle_client, registry::InMemoryRegistry};
+use tokio::net::TcpListener;
+
+const PROGRAM: u32 = 0x2000_0001;
+const PMAP_PROGRAM: u32 = 0x2000_0002;
+
+/// Serves an empty registry over TCP from a thread of its own, returning its address.
+fn serve() -> SocketAddr {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_io()
+        .build()
+        .unwrap();
+    let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let registry = Arc::new(InMemoryRegistry::new());
+        runtime.block_on(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let registry = registry.clone();
+                tokio::spawn(async move { handle_client(stream, registry.as_ref()).await });
+            }
+        })
+    });
+    addr
+}
+
+fn mapping(prot: u32, port: u32) -> Mapping {
+    Mapping {
+        prog: PMAP_PROGRAM,
+        vers: 1,
+        prot,
+        port,
+    }
+}
+
+fn rpcb(r_vers: u32, r_netid: &str, r_addr: &str) -> RPCB {
+    RPCB {
+        r_prog: PROGRAM,
+        r_vers,
+        r_netid: r_netid.to_owned(),
+        r_addr: r_addr.to_owned(),
+        r_owner: "test".to_owned(),
+    }
+}
+
+/// The calls of `calls()` in tests/capture/rpcinfo.c.
+fn calls(addr: SocketAddr) {
+    let mut v2 = PortMapperClient::connect(addr, Protocol::Tcp).unwrap();
+    v2.null().unwrap();
+    assert!(v2.set(mapping(IPPROTO_UDP, 2052)).unwrap());
+    assert!(!v2.set(mapping(IPPROTO_UDP, 2053)).unwrap());
+    assert_eq!(v2.get_port(mapping(IPPROTO_UDP, 0)).unwrap(), 2052);
+    assert_eq!(v2.get_port(mapping(IPPROTO_TCP, 0)).unwrap(), 0);
+    v2.dump().unwrap();
+
+    let mut v3 = RpcBindClient::connect(addr, Protocol::Tcp, RpcBindVersion::V3).unwrap();
+    assert!(v3.set(rpcb(2, "tcp", "127.0.0.1.8.4")).unwrap());
+    assert_eq!(v3.get_addr(rpcb(2, "tcp", "")).unwrap(), "127.0.0.1.8.4");
+    assert!(v3.unset(rpcb(2, "", "")).unwrap());
+    assert_eq!(v3.get_addr(rpcb(2, "tcp", "")).unwrap(), "");
+
+    let mut v4 = RpcBindClient::connect(addr, Protocol::Tcp, RpcBindVersion::V4).unwrap();
+    assert!(v4.set(rpcb(1, "udp", "127.0.0.1.8.1")).unwrap());
+    assert!(v4.set(rpcb(1, "tcp", "127.0.0.1.8.2")).unwrap());
+    assert!(v4.set(rpcb(1, "tcp6", "::1.8.3")).unwrap());
+    assert!(!v4.unset(rpcb(3, "", "")).unwrap());
+    for (version, netid) in [(1, "udp"), (1, "tcp6"), (1, "udp"), (3, "tcp")] {
+        v4.get_addr(rpcb(version, netid, "")).unwrap();
+    }
+    v4.get_time().unwrap();
+}
+
+/// Runs rpcinfo against the server at `addr`, returning what it printed.
+fn rpcinfo(addr: SocketAddr, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_rpcinfo"))
+        .env("RPCBIND_PORT", addr.port().to_string())
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "rpcinfo {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn prints_like_libtirpc() {
+    let addr = serve();
+    calls(addr);
+    let program = PROGRAM.to_string();
+    assert_eq!(
+        rpcinfo(addr, &["-l", "127.0.0.1", &program, "1"]),
+        include_str!("fixtures/addr_list.txt")
+    );
+    assert_eq!(
+        rpcinfo(addr, &["-l", "127.0.0.1", &program, "2"]),
+        include_str!("fixtures/addr_list_empty.txt")
+    );
+    assert_eq!(
+        rpcinfo(addr, &["-m", "127.0.0.1"]),
+        include_str!("fixtures/stats.txt")
+    );
+}